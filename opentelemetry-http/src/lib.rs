@@ -236,6 +236,8 @@ pub mod hyper {
 /// Methods to make working with responses from the [`HttpClient`] trait easier.
 pub trait ResponseExt: Sized {
     /// Turn a response into an error if the HTTP status does not indicate success (200 - 299).
+    ///
+    /// The error is a [`StatusError`].
     fn error_for_status(self) -> Result<Self, HttpError>;
 }
 
@@ -244,11 +246,35 @@ impl<T> ResponseExt for Response<T> {
         if self.status().is_success() {
             Ok(self)
         } else {
-            Err(format!("request failed with status {}", self.status()).into())
+            Err(Box::new(StatusError {
+                status: self.status(),
+            }))
         }
     }
 }
 
+/// Error returned by [`ResponseExt::error_for_status`] for a response whose HTTP status does not
+/// indicate success.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusError {
+    status: http::StatusCode,
+}
+
+impl StatusError {
+    /// The HTTP status of the response.
+    pub fn status(&self) -> http::StatusCode {
+        self.status
+    }
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request failed with status {}", self.status)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
 #[cfg(test)]
 mod tests {
     use http::HeaderValue;
@@ -305,4 +331,20 @@ mod tests {
         assert!(got.contains(&"headername1"));
         assert!(got.contains(&"headername2"));
     }
+
+    #[test]
+    fn error_for_status() {
+        let response = Response::builder().status(503).body(()).unwrap();
+        let error = response.error_for_status().unwrap_err();
+
+        let status_error = error.downcast_ref::<StatusError>().unwrap();
+        assert_eq!(status_error.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            error.to_string(),
+            "request failed with status 503 Service Unavailable"
+        );
+
+        let response = Response::builder().status(204).body(()).unwrap();
+        assert!(response.error_for_status().is_ok());
+    }
 }
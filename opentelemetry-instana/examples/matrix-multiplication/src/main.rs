@@ -1,6 +1,6 @@
 use actix_cors::Cors;
 use actix_web::{web, App, HttpResponse, HttpServer, Responder};
-use opentelemetry_instana::{InstanaExporter,InstanaPropagator};
+use opentelemetry_instana::{InstanaPipeline,InstanaPropagator};
 use opentelemetry::global;
 use opentelemetry::propagation::TextMapPropagator;
 use opentelemetry::trace::{Span,Tracer};
@@ -123,17 +123,10 @@ async fn index() -> impl Responder {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let instana_exporter = InstanaExporter::builder()
-        .with_service(get_resource())
-        .build()
-        .expect("Failed to create instana exporter");
-
-    let tracer_provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
-        .with_batch_exporter(instana_exporter)
+    let _guard = InstanaPipeline::new()
         .with_resource(get_resource())
-        .build();
-
-    global::set_tracer_provider(tracer_provider.clone());
+        .install()
+        .expect("Failed to set up tracing to Instana");
     let _tracer = global::tracer("matrix_multiplier");
 
     println!("Starting matrix multiplication server on http://127.0.0.1:8081");
@@ -3,7 +3,7 @@ use opentelemetry::propagation::TextMapPropagator;
 use std::collections::VecDeque;
 use std::sync::Arc;
 use std::sync::Mutex;
-use opentelemetry_instana::{InstanaExporter,InstanaPropagator};
+use opentelemetry_instana::{InstanaExporter,InstanaExporterOptions,InstanaPropagator};
 use opentelemetry::{global, KeyValue};
 use opentelemetry_sdk::Resource;
 use std::sync::OnceLock;
@@ -100,8 +100,10 @@ async fn main() -> std::io::Result<()> {
         matrix_results: Mutex::new(VecDeque::new()),
     });
 
+    let options = InstanaExporterOptions::from_env().expect("Invalid Instana configuration");
     let instana_exporter = InstanaExporter::builder()
         .with_service(get_resource())
+        .with_options(options)
         .build()
         .expect("Failed to create instana exporter");
 
@@ -0,0 +1,242 @@
+use http::{header::CONTENT_TYPE, Method};
+use opentelemetry_http::HttpClient;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+use url::Url;
+
+use crate::exporter::{
+    agent_url, runtime_http_client, BuildError, InstanaExporterOptions, DEFAULT_CONNECT_TIMEOUT,
+    DEFAULT_TIMEOUT,
+};
+
+const EVENT_PATH: &str = "/com.instana.plugin.generic.event";
+
+/// Severity of a custom event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(into = "i32")]
+pub enum Severity {
+    /// A change, such as a deployment or a configuration change
+    Change,
+    /// An issue with warning severity
+    Warning,
+    /// An issue with critical severity
+    Critical,
+}
+
+impl From<Severity> for i32 {
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Change => -1,
+            Severity::Warning => 5,
+            Severity::Critical => 10,
+        }
+    }
+}
+
+/// A custom event sent to the Instana agent
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CustomEvent {
+    pub title: String,
+    pub text: String,
+    pub severity: Severity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>, // timestamp in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>, // duration in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>, // host the event is attached to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>, // plugin of the entity the event is attached to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>, // id of the entity the event is attached to
+}
+
+impl CustomEvent {
+    /// Create a change event with the given title and text
+    pub fn new(title: impl Into<String>, text: impl Into<String>) -> Self {
+        CustomEvent {
+            title: title.into(),
+            text: text.into(),
+            severity: Severity::Change,
+            timestamp: None,
+            duration: None,
+            host: None,
+            plugin: None,
+            id: None,
+        }
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: SystemTime) -> Self {
+        self.timestamp = timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .ok()
+            .map(|t| t.as_millis() as u64);
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration.as_millis() as u64);
+        self
+    }
+
+    /// Attach the event to a host
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Attach the event to an entity, identified by its plugin and id
+    pub fn with_entity(mut self, plugin: impl Into<String>, id: impl Into<String>) -> Self {
+        self.plugin = Some(plugin.into());
+        self.id = Some(id.into());
+        self
+    }
+}
+
+#[derive(Error, Debug)]
+/// Errors that can occur while sending an event.
+#[non_exhaustive]
+pub enum EventError {
+    /// The event could not be serialized.
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    /// The request could not be built or sent.
+    #[error("Request error: {0}")]
+    Request(String),
+
+    /// The agent rejected the event.
+    #[error("Instana event export failed. Url: {endpoint}, Status Code: {status}")]
+    HttpStatus { endpoint: String, status: u16 },
+}
+
+/// Client sending custom events to the generic event endpoint of the Instana agent
+#[derive(Debug)]
+pub struct InstanaEventClient {
+    client: Arc<dyn HttpClient>,
+    endpoint: String,
+    headers: http::HeaderMap,
+}
+
+impl InstanaEventClient {
+    pub fn builder() -> EventClientBuilder {
+        EventClientBuilder::default()
+    }
+
+    pub fn new(client: Arc<dyn HttpClient>, endpoint: String, headers: http::HeaderMap) -> Self {
+        InstanaEventClient {
+            client,
+            endpoint,
+            headers,
+        }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Send an event to the agent
+    pub async fn send(&self, event: &CustomEvent) -> Result<(), EventError> {
+        let body = serde_json::to_vec(event)?;
+
+        let mut request = http::Request::builder()
+            .method(Method::POST)
+            .uri(&self.endpoint)
+            .header(CONTENT_TYPE, "application/json")
+            .body(bytes::Bytes::from(body))
+            .map_err(|e| EventError::Request(e.to_string()))?;
+
+        for (k, v) in &self.headers {
+            request.headers_mut().insert(k.clone(), v.clone());
+        }
+
+        let response = self
+            .client
+            .send_bytes(request)
+            .await
+            .map_err(|e| EventError::Request(format!("{e:?}")))?;
+
+        if !response.status().is_success() {
+            return Err(EventError::HttpStatus {
+                endpoint: self.endpoint.clone(),
+                status: response.status().as_u16(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+pub struct EventClientBuilder {
+    client: Option<Arc<dyn HttpClient>>,
+    endpoint: Option<String>,
+    headers: http::HeaderMap,
+    timeout: Duration,
+    connect_timeout: Duration,
+}
+
+impl Default for EventClientBuilder {
+    fn default() -> Self {
+        EventClientBuilder {
+            client: None,
+            endpoint: None,
+            headers: http::HeaderMap::new(),
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+        }
+    }
+}
+
+impl EventClientBuilder {
+    /// Send events to the agent the exporter configured by `options` sends spans to
+    pub fn with_options(mut self, options: &InstanaExporterOptions) -> Self {
+        if let Ok(mut url) = Url::parse(&options.endpoint) {
+            url.set_path(EVENT_PATH);
+            self.endpoint = Some(url.to_string());
+        }
+        self.headers = options.headers.clone();
+        self
+    }
+
+    /// Send events to the given URL of the event endpoint
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = Some(endpoint.to_string());
+        self
+    }
+
+    pub fn with_http_client(mut self, client: impl HttpClient + 'static) -> Self {
+        self.client = Some(Arc::new(client));
+        self
+    }
+
+    /// Set the timeout of requests sent by the default HTTP client
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the timeout for connecting to the agent of the default HTTP client
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn build(self) -> Result<InstanaEventClient, BuildError> {
+        let client = match self.client {
+            Some(client) => client,
+            None => runtime_http_client(self.timeout, self.connect_timeout)?,
+        };
+        let endpoint = self
+            .endpoint
+            .unwrap_or_else(|| format!("{}{}", agent_url(), EVENT_PATH));
+
+        Ok(InstanaEventClient::new(client, endpoint, self.headers))
+    }
+}
@@ -0,0 +1,348 @@
+//! Tracing configuration the agent returns when the process announces itself.
+//!
+//! The `configuration.yaml` of the agent can disable tracing, set the secrets
+//! matcher, pick extra HTTP headers, turn off span categories and add span
+//! filter rules. The exporter
+//! announces the process, applies the returned configuration and announces
+//! again periodically and once the agent was unreachable, so that changes of
+//! the agent configuration reach running processes.
+
+use http::{header::CONTENT_TYPE, Method};
+use opentelemetry::{otel_debug, otel_warn};
+use opentelemetry_http::HttpClient;
+use opentelemetry_sdk::trace::SpanData;
+use serde::Deserialize;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use super::config::{self, LogLevel, Secrets};
+use super::process_identity::ProcessIdentity;
+use super::span_filter::{SpanFilter, SpanFilterError};
+
+/// Path of the announce endpoint of the agent
+pub const ANNOUNCE_PATH: &str = "/com.instana.plugin.rust.discovery";
+/// Time after which a failed announce is retried
+pub const ANNOUNCE_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// Default time after which the configuration of the agent is reloaded
+pub const DEFAULT_REMOTE_CONFIG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Category of spans the agent configuration can turn off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanCategory {
+    /// Spans with a `db.system` attribute
+    Databases,
+    /// Spans with a `messaging.system` attribute
+    Messaging,
+    /// HTTP and RPC spans
+    Protocols,
+}
+
+impl SpanCategory {
+    /// Parse the name of a category of the `tracing.disable` section
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "databases" => Some(SpanCategory::Databases),
+            "messaging" => Some(SpanCategory::Messaging),
+            "protocols" => Some(SpanCategory::Protocols),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpanCategory::Databases => "databases",
+            SpanCategory::Messaging => "messaging",
+            SpanCategory::Protocols => "protocols",
+        }
+    }
+
+    /// Category of a span, from its semantic convention attributes
+    pub fn of(span: &SpanData) -> Option<Self> {
+        let has = |keys: &[&str]| {
+            span.attributes
+                .iter()
+                .any(|kv| keys.contains(&kv.key.as_str()))
+        };
+
+        if has(&["db.system", "db.system.name"]) {
+            Some(SpanCategory::Databases)
+        } else if has(&["messaging.system"]) {
+            Some(SpanCategory::Messaging)
+        } else if has(&["http.request.method", "http.method", "rpc.system"]) {
+            Some(SpanCategory::Protocols)
+        } else {
+            None
+        }
+    }
+}
+
+/// Tracing configuration of the agent
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AgentConfig {
+    pub agent_uuid: Option<String>,
+    /// Pid of the process as seen by the agent
+    pub pid: Option<u32>,
+    /// Drop all spans instead of sending them to the agent
+    pub tracing_disabled: bool,
+    /// Attributes whose values are redacted, `None` if not configured
+    pub secrets: Option<Secrets>,
+    /// Names of HTTP headers instrumentations should capture
+    pub extra_http_headers: Vec<String>,
+    /// Categories of spans that are dropped
+    pub disabled_categories: Vec<SpanCategory>,
+    /// Rules of the `tracing.filter` section, added to the local ones
+    pub span_filter: Option<SpanFilter>,
+}
+
+/// Errors that can occur while parsing the agent configuration.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum AgentConfigError {
+    /// The announce response is not valid JSON.
+    #[error("invalid announce response: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The secrets matcher is unknown.
+    #[error("unknown secrets matcher {0:?}")]
+    UnknownMatcher(String),
+
+    /// A secret of the regex matcher is not a valid regular expression.
+    #[error("invalid secrets regex: {0}")]
+    Regex(#[from] regex::Error),
+
+    /// The span filter rules are invalid.
+    #[error(transparent)]
+    Filter(#[from] SpanFilterError),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct AnnounceResponse {
+    agent_uuid: Option<String>,
+    pid: Option<u32>,
+    secrets: Option<AnnounceSecrets>,
+    // Location of the extra headers used by older agents
+    extra_headers: Option<Vec<String>>,
+    tracing: Option<AnnounceTracing>,
+}
+
+#[derive(Deserialize)]
+struct AnnounceSecrets {
+    matcher: String,
+    #[serde(default)]
+    list: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct AnnounceTracing {
+    enabled: Option<bool>,
+    #[serde(rename = "extra-http-headers")]
+    extra_http_headers: Option<Vec<String>>,
+    // `true`, `{"databases": true}` or `[{"databases": true}]`
+    disable: Option<serde_json::Value>,
+    filter: Option<serde_json::Value>,
+}
+
+impl AgentConfig {
+    /// Parse the JSON the agent answers the announce request with
+    pub fn from_announce_response(body: &[u8]) -> Result<Self, AgentConfigError> {
+        let response: AnnounceResponse = serde_json::from_slice(body)?;
+        let tracing = response.tracing.unwrap_or_default();
+
+        let secrets = match response.secrets {
+            Some(secrets) => {
+                let matcher = config::parse_secrets_matcher(&secrets.matcher)
+                    .ok_or(AgentConfigError::UnknownMatcher(secrets.matcher))?;
+                Some(Secrets::new(matcher, secrets.list)?)
+            },
+            None => None,
+        };
+
+        let extra_http_headers = tracing
+            .extra_http_headers
+            .or(response.extra_headers)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        let span_filter = tracing.filter.map(SpanFilter::from_json).transpose()?;
+
+        let mut tracing_disabled = tracing.enabled == Some(false);
+        let mut disabled_categories = Vec::new();
+        match tracing.disable {
+            Some(serde_json::Value::Bool(disable)) => tracing_disabled |= disable,
+            Some(serde_json::Value::Object(categories)) => {
+                collect_categories(&categories, &mut disabled_categories)
+            },
+            Some(serde_json::Value::Array(entries)) => {
+                for entry in entries {
+                    if let serde_json::Value::Object(categories) = entry {
+                        collect_categories(&categories, &mut disabled_categories);
+                    }
+                }
+            },
+            _ => {},
+        }
+
+        Ok(AgentConfig {
+            agent_uuid: response.agent_uuid,
+            pid: response.pid,
+            tracing_disabled,
+            secrets,
+            extra_http_headers,
+            disabled_categories,
+            span_filter,
+        })
+    }
+
+    /// Whether the span belongs to a disabled category
+    pub fn is_disabled(&self, span: &SpanData) -> bool {
+        !self.disabled_categories.is_empty()
+            && SpanCategory::of(span)
+                .is_some_and(|category| self.disabled_categories.contains(&category))
+    }
+}
+
+/// Add the categories set to `true`, unknown categories are ignored
+fn collect_categories(
+    categories: &serde_json::Map<String, serde_json::Value>,
+    disabled: &mut Vec<SpanCategory>,
+) {
+    for (name, value) in categories {
+        if value.as_bool() == Some(true) {
+            if let Some(category) = SpanCategory::from_name(name) {
+                if !disabled.contains(&category) {
+                    disabled.push(category);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Announce {
+    Pending,
+    DueAt(Instant),
+}
+
+/// Configuration of the agent applied by the exporter, reloaded on re-announce
+#[derive(Debug)]
+pub(crate) struct RemoteConfig {
+    config: RwLock<Option<Arc<AgentConfig>>>, // last configuration received
+    local_filter: Option<SpanFilter>, // span filter of the options
+    span_filter: RwLock<Option<Arc<SpanFilter>>>, // local and agent rules
+    announce: Mutex<Announce>,
+    interval: Duration, // between announces reloading the configuration
+    log_level: LogLevel,
+}
+
+impl RemoteConfig {
+    pub(crate) fn new(interval: Duration, log_level: LogLevel, local_filter: Option<SpanFilter>) -> Self {
+        RemoteConfig {
+            config: RwLock::new(None),
+            span_filter: RwLock::new(local_filter.clone().map(Arc::new)),
+            local_filter,
+            announce: Mutex::new(Announce::Pending),
+            interval,
+            log_level,
+        }
+    }
+
+    pub(crate) fn current(&self) -> Option<Arc<AgentConfig>> {
+        self.config.read().ok().and_then(|config| config.clone())
+    }
+
+    /// Span filter of the options merged with the rules of the agent
+    pub(crate) fn span_filter(&self) -> Option<Arc<SpanFilter>> {
+        self.span_filter.read().ok().and_then(|span_filter| span_filter.clone())
+    }
+
+    /// Announce the process to the agent at `agent_url` if not announced yet
+    /// or the interval elapsed, keeping the last configuration if the
+    /// announce fails
+    pub(crate) async fn refresh(
+        &self,
+        client: &dyn HttpClient,
+        agent_url: &str,
+        identity: &ProcessIdentity,
+    ) {
+        let due = match self.announce.lock().as_deref() {
+            Ok(Announce::Pending) => true,
+            Ok(Announce::DueAt(at)) => Instant::now() >= *at,
+            Err(_) => false,
+        };
+        if !due {
+            return;
+        }
+
+        let next = match announce(client, agent_url, identity).await {
+            Ok(config) => {
+                if self.log_level.enabled(LogLevel::Debug) {
+                    otel_debug!(
+                        name: "InstanaExporter.AgentConfigApplied",
+                        agent = agent_url,
+                        tracing_disabled = config.tracing_disabled,
+                        disabled_categories = format!("{:?}", config.disabled_categories)
+                    );
+                }
+                let span_filter = match (&self.local_filter, &config.span_filter) {
+                    (Some(local), Some(agent)) => Some(local.merged(agent)),
+                    (local, agent) => local.as_ref().or(agent.as_ref()).cloned(),
+                };
+                if let Ok(mut current) = self.span_filter.write() {
+                    *current = span_filter.map(Arc::new);
+                }
+                if let Ok(mut current) = self.config.write() {
+                    *current = Some(Arc::new(config));
+                }
+                Announce::DueAt(Instant::now() + self.interval)
+            },
+            Err(error) => {
+                if self.log_level.enabled(LogLevel::Warn) {
+                    otel_warn!(
+                        name: "InstanaExporter.AnnounceFailed",
+                        agent = agent_url,
+                        error = error.to_string()
+                    );
+                }
+                Announce::DueAt(Instant::now() + ANNOUNCE_RETRY_INTERVAL.min(self.interval))
+            },
+        };
+        if let Ok(mut announce) = self.announce.lock() {
+            *announce = next;
+        }
+    }
+
+    /// Announce again on the next export, e.g. after the agent was unreachable
+    pub(crate) fn reannounce(&self) {
+        if let Ok(mut announce) = self.announce.lock() {
+            *announce = Announce::Pending;
+        }
+    }
+}
+
+/// Announce the process to the agent and parse the configuration it returns
+pub(crate) async fn announce(
+    client: &dyn HttpClient,
+    agent_url: &str,
+    identity: &ProcessIdentity,
+) -> anyhow::Result<AgentConfig> {
+    let payload = serde_json::to_vec(&identity.announce_payload())?;
+    let request = http::Request::builder()
+        .method(Method::PUT)
+        .uri(format!("{agent_url}{ANNOUNCE_PATH}"))
+        .header(CONTENT_TYPE, "application/json")
+        .body(bytes::Bytes::from(payload))?;
+
+    let response = client
+        .send_bytes(request)
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    if !response.status().is_success() {
+        anyhow::bail!("agent answered with status {}", response.status().as_u16());
+    }
+    Ok(AgentConfig::from_announce_response(response.body())?)
+}
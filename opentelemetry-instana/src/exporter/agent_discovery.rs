@@ -0,0 +1,208 @@
+//! Discovery of the Instana agent for processes running in containers.
+//!
+//! Candidate hosts are probed in order for an agent, recognized by the
+//! `Server: Instana Agent` header of its answer to `GET /`. The chosen agent
+//! is cached and probed again once the re-probe interval elapsed, or after an
+//! export to it failed.
+
+use http::{header::SERVER, Method};
+use opentelemetry_http::HttpClient;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+use super::{config, defs, BuildError};
+
+/// Value of the `Server` header the agent answers with
+pub const AGENT_SERVER_HEADER: &str = "Instana Agent";
+/// Default location of the kernel routing table
+pub const DEFAULT_ROUTE_TABLE: &str = "/proc/net/route";
+/// Default time after which the chosen agent is probed again
+pub const DEFAULT_REPROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A host the agent may run on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentHost {
+    Host(String),
+    Localhost,
+    /// The default gateway of the routing table, the host of a container
+    DefaultGateway,
+}
+
+#[derive(Debug)]
+struct ProbeResult {
+    agent_url: Option<String>, // None if no candidate answered
+    probed_at: Instant,
+}
+
+/// Discovery chain of the Instana agent
+#[derive(Debug, Clone)]
+pub struct AgentDiscovery {
+    candidates: Vec<AgentHost>,
+    port: u16,
+    route_table: PathBuf,
+    reprobe_interval: Duration,
+    cached: Arc<Mutex<Option<ProbeResult>>>,
+}
+
+impl PartialEq for AgentDiscovery {
+    fn eq(&self, other: &Self) -> bool {
+        self.candidates == other.candidates
+            && self.port == other.port
+            && self.route_table == other.route_table
+            && self.reprobe_interval == other.reprobe_interval
+    }
+}
+
+impl Default for AgentDiscovery {
+    /// Probe localhost, then the default gateway, on the default agent port
+    fn default() -> Self {
+        AgentDiscovery {
+            candidates: vec![AgentHost::Localhost, AgentHost::DefaultGateway],
+            port: defs::DEFAULT_INSTANA_AGENT_PORT as u16,
+            route_table: PathBuf::from(DEFAULT_ROUTE_TABLE),
+            reprobe_interval: DEFAULT_REPROBE_INTERVAL,
+            cached: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl AgentDiscovery {
+    /// Probe `INSTANA_AGENT_HOST` if set, then localhost and the default
+    /// gateway, on `INSTANA_AGENT_PORT` or the default agent port
+    pub fn from_env() -> Result<Self, BuildError> {
+        let mut discovery = Self::default();
+        if let Some(host) = config::var(config::INSTANA_AGENT_HOST) {
+            discovery.candidates.insert(0, AgentHost::Host(host));
+        }
+        if let Some(port) = config::var(config::INSTANA_AGENT_PORT) {
+            discovery.port = config::parse_port(&port)?;
+        }
+        Ok(discovery)
+    }
+
+    /// Replace the hosts to probe, in order
+    pub fn with_candidates(mut self, candidates: Vec<AgentHost>) -> Self {
+        self.candidates = candidates;
+        self
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Read the default gateway from another routing table than `/proc/net/route`
+    pub fn with_route_table(mut self, path: impl Into<PathBuf>) -> Self {
+        self.route_table = path.into();
+        self
+    }
+
+    pub fn with_reprobe_interval(mut self, interval: Duration) -> Self {
+        self.reprobe_interval = interval;
+        self
+    }
+
+    /// The hosts to probe, in order, with the default gateway resolved
+    pub fn candidate_hosts(&self) -> Vec<String> {
+        self.candidates
+            .iter()
+            .filter_map(|candidate| match candidate {
+                AgentHost::Host(host) => Some(host.clone()),
+                AgentHost::Localhost => Some(defs::DEFAULT_INSTANA_AGENT_HOST.to_string()),
+                AgentHost::DefaultGateway => fs::read_to_string(&self.route_table)
+                    .ok()
+                    .and_then(|table| parse_default_gateway(&table))
+                    .map(|gateway| gateway.to_string()),
+            })
+            .collect()
+    }
+
+    /// Base URL of the agent, probing the candidates if nothing is cached or
+    /// the cached result is older than the re-probe interval
+    pub async fn agent_url(&self, client: &dyn HttpClient) -> Option<String> {
+        if let Ok(cached) = self.cached.lock() {
+            if let Some(result) = &*cached {
+                if result.probed_at.elapsed() < self.reprobe_interval {
+                    return result.agent_url.clone();
+                }
+            }
+        }
+
+        let agent_url = self.probe_candidates(client).await;
+
+        if let Ok(mut cached) = self.cached.lock() {
+            *cached = Some(ProbeResult {
+                agent_url: agent_url.clone(),
+                probed_at: Instant::now(),
+            });
+        }
+        agent_url
+    }
+
+    /// Forget the chosen agent, so that the candidates are probed again
+    pub fn invalidate(&self) {
+        if let Ok(mut cached) = self.cached.lock() {
+            *cached = None;
+        }
+    }
+
+    async fn probe_candidates(&self, client: &dyn HttpClient) -> Option<String> {
+        for host in self.candidate_hosts() {
+            let url = format!("http://{}:{}", host, self.port);
+            if is_agent(client, &url).await {
+                return Some(url);
+            }
+        }
+        None
+    }
+}
+
+/// Whether an Instana agent answers at `url`
+pub(crate) async fn is_agent(client: &dyn HttpClient, url: &str) -> bool {
+    let request = match http::Request::builder()
+        .method(Method::GET)
+        .uri(format!("{url}/"))
+        .body(bytes::Bytes::new())
+    {
+        Ok(request) => request,
+        Err(_) => return false,
+    };
+
+    match client.send_bytes(request).await {
+        Ok(response) => response
+            .headers()
+            .get(SERVER)
+            .is_some_and(|server| server == AGENT_SERVER_HEADER),
+        Err(_) => false,
+    }
+}
+
+/// Base URL of the agent serving `endpoint`, without path and query
+pub(crate) fn base_url(endpoint: &str) -> String {
+    Url::parse(endpoint)
+        .map(|mut url| {
+            url.set_path("");
+            url.set_query(None);
+            url.to_string().trim_end_matches('/').to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse the default gateway out of a routing table in the `/proc/net/route` format
+pub fn parse_default_gateway(route_table: &str) -> Option<Ipv4Addr> {
+    route_table.lines().skip(1).find_map(|line| {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        if columns.len() < 3 || columns[1] != "00000000" {
+            return None;
+        }
+        // The kernel writes addresses in host byte order
+        u32::from_str_radix(columns[2], 16)
+            .ok()
+            .filter(|gateway| *gateway != 0)
+            .map(|gateway| Ipv4Addr::from(gateway.to_ne_bytes()))
+    })
+}
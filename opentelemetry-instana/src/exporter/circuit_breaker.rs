@@ -0,0 +1,209 @@
+//! Circuit breaker stopping exports while the agent is unavailable.
+//!
+//! After `failure_threshold` consecutive failed exports the circuit opens and
+//! batches are dropped without contacting the agent. Once the cool-down
+//! elapsed, the next export probes the health endpoint of the agent: the
+//! circuit closes if it answers and opens for another cool-down otherwise.
+
+use http::Method;
+use opentelemetry::{otel_info, otel_warn};
+use opentelemetry_http::HttpClient;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::agent_discovery::base_url;
+use super::config::LogLevel;
+use super::metrics::{ExporterMetrics, FailureReason};
+
+/// Default number of consecutive failures opening the circuit
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+/// Default time the circuit stays open before the agent is probed
+pub const DEFAULT_COOL_DOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitBreakerOptions {
+    /// Number of consecutive failed exports opening the circuit
+    pub failure_threshold: u32,
+    /// Time the circuit stays open before the agent is probed
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreakerOptions {
+    fn default() -> Self {
+        CircuitBreakerOptions {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cool_down: DEFAULT_COOL_DOWN,
+        }
+    }
+}
+
+/// State of the circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Batches are sent to the agent
+    Closed,
+    /// Batches are dropped until the cool-down elapsed
+    Open,
+}
+
+#[derive(Debug)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen, // the agent is being probed
+}
+
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    options: CircuitBreakerOptions,
+    state: Mutex<State>,
+    log_level: LogLevel,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(options: CircuitBreakerOptions, log_level: LogLevel) -> Self {
+        CircuitBreaker {
+            options,
+            state: Mutex::new(State::Closed { failures: 0 }),
+            log_level,
+        }
+    }
+
+    pub(crate) fn state(&self) -> CircuitState {
+        match self.state.lock().as_deref() {
+            Ok(State::Open { .. } | State::HalfOpen) => CircuitState::Open,
+            _ => CircuitState::Closed,
+        }
+    }
+
+    /// Whether a batch may be sent to `endpoint`, probing the agent once the
+    /// cool-down of the open circuit elapsed
+    pub(crate) async fn allow(
+        &self,
+        client: &dyn HttpClient,
+        endpoint: &str,
+        metrics: Option<&ExporterMetrics>,
+    ) -> bool {
+        {
+            let Ok(mut state) = self.state.lock() else {
+                return true;
+            };
+            match *state {
+                State::Closed { .. } => return true,
+                State::Open { until } if Instant::now() >= until => *state = State::HalfOpen,
+                State::Open { .. } | State::HalfOpen => return false,
+            }
+        }
+
+        let mut probe = Probe {
+            breaker: self,
+            finished: false,
+        };
+        let healthy = agent_answers(client, &base_url(endpoint)).await;
+
+        let Ok(mut state) = self.state.lock() else {
+            return healthy;
+        };
+        probe.finished = true;
+        if healthy {
+            *state = State::Closed { failures: 0 };
+            if self.log_level.enabled(LogLevel::Info) {
+                otel_info!(name: "InstanaExporter.CircuitClosed", endpoint = endpoint);
+            }
+            if let Some(metrics) = metrics {
+                metrics.circuit_transition(CircuitState::Closed);
+            }
+        } else {
+            *state = self.reopened();
+            if self.log_level.enabled(LogLevel::Warn) {
+                otel_warn!(name: "InstanaExporter.AgentProbeFailed", endpoint = endpoint);
+            }
+        }
+        healthy
+    }
+
+    /// Record the outcome of an export
+    pub(crate) fn record(&self, failure: Option<FailureReason>, metrics: Option<&ExporterMetrics>) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let unavailable = failure.is_some_and(is_unavailable);
+        let failures = match &*state {
+            State::Closed { .. } if failure.is_none() => 0,
+            State::Closed { failures } if unavailable => failures + 1,
+            State::Closed { failures } => *failures,
+            State::Open { .. } | State::HalfOpen => return,
+        };
+
+        if failures >= self.options.failure_threshold {
+            *state = self.reopened();
+            if self.log_level.enabled(LogLevel::Warn) {
+                otel_warn!(
+                    name: "InstanaExporter.CircuitOpened",
+                    failures = failures,
+                    cool_down_ms = self.options.cool_down.as_millis()
+                );
+            }
+            if let Some(metrics) = metrics {
+                metrics.circuit_transition(CircuitState::Open);
+            }
+        } else {
+            *state = State::Closed { failures };
+        }
+    }
+
+    fn reopened(&self) -> State {
+        State::Open {
+            until: Instant::now() + self.options.cool_down,
+        }
+    }
+}
+
+/// Running probe of the agent, opening the circuit again if the probe is
+/// dropped before it finished, e.g. when the export timed out
+struct Probe<'a> {
+    breaker: &'a CircuitBreaker,
+    finished: bool,
+}
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if let Ok(mut state) = self.breaker.state.lock() {
+            if matches!(*state, State::HalfOpen) {
+                *state = self.breaker.reopened();
+            }
+        }
+    }
+}
+
+/// Whether the agent at `url` answers `GET /`. Only failures which open the
+/// circuit count, so that a proxy in front of the agent answering with a 404
+/// closes it as well.
+async fn agent_answers(client: &dyn HttpClient, url: &str) -> bool {
+    let request = match http::Request::builder()
+        .method(Method::GET)
+        .uri(format!("{url}/"))
+        .body(bytes::Bytes::new())
+    {
+        Ok(request) => request,
+        Err(_) => return false,
+    };
+
+    match client.send_bytes(request).await {
+        Ok(_) => true,
+        Err(error) => !is_unavailable(FailureReason::from_http_error(&error)),
+    }
+}
+
+/// Whether the failure means the agent is unavailable. Client errors and
+/// serialization failures say nothing about the agent.
+fn is_unavailable(failure: FailureReason) -> bool {
+    match failure {
+        FailureReason::Connection | FailureReason::CircuitOpen => true,
+        FailureReason::HttpStatus(status) => status >= 500,
+        FailureReason::Serialization => false,
+    }
+}
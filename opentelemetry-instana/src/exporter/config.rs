@@ -0,0 +1,246 @@
+use regex::Regex;
+use std::env;
+use std::time::Duration;
+
+use super::span_filter::SpanFilter;
+use super::BuildError;
+
+pub(crate) const INSTANA_AGENT_HOST: &str = "INSTANA_AGENT_HOST";
+pub(crate) const INSTANA_AGENT_PORT: &str = "INSTANA_AGENT_PORT";
+pub(crate) const INSTANA_SERVICE_NAME: &str = "INSTANA_SERVICE_NAME";
+pub(crate) const INSTANA_TIMEOUT: &str = "INSTANA_TIMEOUT";
+pub(crate) const INSTANA_DISABLE_TRACING: &str = "INSTANA_DISABLE_TRACING";
+pub(crate) const INSTANA_SECRETS: &str = "INSTANA_SECRETS";
+pub(crate) const INSTANA_EXTRA_HTTP_HEADERS: &str = "INSTANA_EXTRA_HTTP_HEADERS";
+pub(crate) const INSTANA_DEBUG: &str = "INSTANA_DEBUG";
+pub(crate) const INSTANA_LOG_LEVEL: &str = "INSTANA_LOG_LEVEL";
+pub(crate) const INSTANA_TRACING_FILTER: &str = "INSTANA_TRACING_FILTER";
+pub(crate) const INSTANA_CONFIG_PATH: &str = "INSTANA_CONFIG_PATH";
+
+/// Value secret attributes are replaced with
+pub const REDACTED: &str = "<redacted>";
+
+/// How attribute keys are matched against the list of secrets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretsMatcher {
+    Equals,
+    EqualsIgnoreCase,
+    Contains,
+    ContainsIgnoreCase,
+    Regex,
+    None,
+}
+
+/// Attributes whose values are redacted before spans are sent to the agent
+#[derive(Debug, Clone)]
+pub struct Secrets {
+    matcher: SecretsMatcher,
+    list: Vec<String>,
+    regexes: Vec<Regex>, // compiled list, for the regex matcher
+}
+
+impl PartialEq for Secrets {
+    fn eq(&self, other: &Self) -> bool {
+        self.matcher == other.matcher && self.list == other.list
+    }
+}
+
+impl Default for Secrets {
+    /// No attribute is a secret
+    fn default() -> Self {
+        Secrets {
+            matcher: SecretsMatcher::None,
+            list: Vec::new(),
+            regexes: Vec::new(),
+        }
+    }
+}
+
+impl Secrets {
+    /// Create secrets matched by `matcher`. Regular expressions must match the whole key.
+    pub fn new(matcher: SecretsMatcher, list: Vec<String>) -> Result<Self, regex::Error> {
+        let regexes = match matcher {
+            SecretsMatcher::Regex => list
+                .iter()
+                .map(|pattern| Regex::new(&format!("^(?:{pattern})$")))
+                .collect::<Result<_, _>>()?,
+            _ => Vec::new(),
+        };
+        Ok(Secrets {
+            matcher,
+            list,
+            regexes,
+        })
+    }
+
+    pub fn matcher(&self) -> SecretsMatcher {
+        self.matcher
+    }
+
+    pub fn list(&self) -> &[String] {
+        &self.list
+    }
+
+    /// Whether the value of the attribute `key` is a secret
+    pub fn is_secret(&self, key: &str) -> bool {
+        match self.matcher {
+            SecretsMatcher::Equals => self.list.iter().any(|s| key == s),
+            SecretsMatcher::EqualsIgnoreCase => self.list.iter().any(|s| key.eq_ignore_ascii_case(s)),
+            SecretsMatcher::Contains => self.list.iter().any(|s| key.contains(s.as_str())),
+            SecretsMatcher::ContainsIgnoreCase => {
+                let key = key.to_lowercase();
+                self.list
+                    .iter()
+                    .any(|s| key.contains(s.to_lowercase().as_str()))
+            }
+            SecretsMatcher::Regex => self.regexes.iter().any(|r| r.is_match(key)),
+            SecretsMatcher::None => false,
+        }
+    }
+}
+
+/// Verbosity of the exporter's diagnostics, emitted as internal logs with
+/// the `internal-logs` feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    /// Whether diagnostics of the given level are logged
+    pub fn enabled(self, level: LogLevel) -> bool {
+        level <= self
+    }
+}
+
+fn invalid(name: &'static str, value: &str, reason: impl Into<String>) -> BuildError {
+    BuildError::InvalidEnvVar {
+        name,
+        value: value.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Value of the variable `name`, `None` if unset or empty
+pub(crate) fn var(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+pub(crate) fn parse_port(value: &str) -> Result<u16, BuildError> {
+    match value.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(port),
+        _ => Err(invalid(INSTANA_AGENT_PORT, value, "expected a port number")),
+    }
+}
+
+/// Parse a timeout in milliseconds
+pub(crate) fn parse_timeout(value: &str) -> Result<Duration, BuildError> {
+    match value.parse::<u64>() {
+        Ok(ms) if ms > 0 => Ok(Duration::from_millis(ms)),
+        _ => Err(invalid(
+            INSTANA_TIMEOUT,
+            value,
+            "expected a positive number of milliseconds",
+        )),
+    }
+}
+
+pub(crate) fn parse_bool(name: &'static str, value: &str) -> Result<bool, BuildError> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(invalid(name, value, "expected true or false")),
+    }
+}
+
+/// Parse the name of a secrets matcher, e.g. `contains-ignore-case`
+pub(crate) fn parse_secrets_matcher(name: &str) -> Option<SecretsMatcher> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "equals" => Some(SecretsMatcher::Equals),
+        "equals-ignore-case" => Some(SecretsMatcher::EqualsIgnoreCase),
+        "contains" => Some(SecretsMatcher::Contains),
+        "contains-ignore-case" => Some(SecretsMatcher::ContainsIgnoreCase),
+        "regex" => Some(SecretsMatcher::Regex),
+        "none" => Some(SecretsMatcher::None),
+        _ => None,
+    }
+}
+
+/// Parse secrets in the `<matcher>:<secret>,<secret>` format
+pub(crate) fn parse_secrets(value: &str) -> Result<Secrets, BuildError> {
+    let (matcher, list) = value.split_once(':').ok_or_else(|| {
+        invalid(
+            INSTANA_SECRETS,
+            value,
+            "expected <matcher>:<secret>,<secret>",
+        )
+    })?;
+
+    let matcher = parse_secrets_matcher(matcher).ok_or_else(|| {
+        invalid(
+            INSTANA_SECRETS,
+            value,
+            format!("unknown matcher {:?}", matcher.trim()),
+        )
+    })?;
+
+    let list = list
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect();
+
+    Secrets::new(matcher, list).map_err(|e| invalid(INSTANA_SECRETS, value, e.to_string()))
+}
+
+/// Parse a `;` separated list of HTTP header names
+pub(crate) fn parse_extra_http_headers(value: &str) -> Result<Vec<String>, BuildError> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            http::HeaderName::from_bytes(name.as_bytes())
+                .map(|header| header.to_string())
+                .map_err(|_| {
+                    invalid(
+                        INSTANA_EXTRA_HTTP_HEADERS,
+                        value,
+                        format!("invalid header name {name:?}"),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Parse span filter rules in the `tracing.filter` format
+pub(crate) fn parse_tracing_filter(value: &str) -> Result<SpanFilter, BuildError> {
+    SpanFilter::from_yaml(value).map_err(|e| invalid(INSTANA_TRACING_FILTER, value, e.to_string()))
+}
+
+/// Read the span filter rules of the configuration file at `path`
+pub(crate) fn read_tracing_filter(path: &str) -> Result<Option<SpanFilter>, BuildError> {
+    SpanFilter::from_config_file(path).map_err(|e| invalid(INSTANA_CONFIG_PATH, path, e.to_string()))
+}
+
+pub(crate) fn parse_log_level(value: &str) -> Result<LogLevel, BuildError> {
+    match value.to_ascii_lowercase().as_str() {
+        "error" => Ok(LogLevel::Error),
+        "warn" | "warning" => Ok(LogLevel::Warn),
+        "info" => Ok(LogLevel::Info),
+        "debug" | "trace" => Ok(LogLevel::Debug),
+        _ => Err(invalid(
+            INSTANA_LOG_LEVEL,
+            value,
+            "expected error, warn, info or debug",
+        )),
+    }
+}
@@ -0,0 +1,346 @@
+//! Reverse conversion of the spans sent to the agent.
+//!
+//! [`DecodedSpan`] is a typed view of an [`InstanaSpan`], with parsed ids,
+//! kind, timestamps and status, that can be compared with the `SpanData` it
+//! was converted from. Conversions are lossy: timestamps have millisecond
+//! precision, non-entry spans and links only carry the lower 64 bits of the
+//! trace id, and Instana kinds do not tell servers from producers.
+
+use opentelemetry::trace::{SpanId, Status, TraceId};
+use opentelemetry_sdk::trace::SpanData;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+use super::config::REDACTED;
+use super::instana_span::{InstanaKind, InstanaSpan};
+use super::serialize_span::{convert_value_to_json, event_keys, EXPORTED_INTERNAL_TAGS};
+
+const STATUS_OK: &str = "StatusCode::STATUS_OK";
+const STATUS_ERROR: &str = "StatusCode::STATUS_ERROR";
+
+/// Event of a decoded span
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedEvent {
+    /// Key of the event in the `events` map, the event name unless repeated
+    pub key: String,
+    pub timestamp: SystemTime,
+    pub attributes: HashMap<String, serde_json::Value>,
+}
+
+/// Link of a decoded span
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedLink {
+    pub trace_id: TraceId, // lower 64 bits only
+    pub span_id: SpanId,
+    pub attributes: HashMap<String, serde_json::Value>,
+}
+
+/// Typed view of an Instana span
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedSpan {
+    /// The long trace id of entry spans, otherwise the lower 64 bits
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub parent_span_id: SpanId, // SpanId::INVALID for root spans
+    pub kind: InstanaKind,
+    pub name: String,
+    pub start_time: SystemTime,
+    pub duration: Duration,
+    pub synthetic: bool,
+    pub status: Status,
+    pub service: Option<String>,
+    pub attributes: HashMap<String, serde_json::Value>,
+    pub resource: HashMap<String, String>,
+    pub events: Vec<DecodedEvent>, // sorted by key
+    pub links: Vec<DecodedLink>,
+    pub scope_name: String,
+    pub scope_version: Option<String>,
+    pub dropped_attributes_count: u32,
+    pub dropped_events_count: u32,
+    pub dropped_links_count: u32,
+}
+
+/// Errors that can occur while decoding Instana spans.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// The payload is not a list of Instana spans.
+    #[error("invalid payload: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// A field has a value that cannot be decoded.
+    #[error("invalid value {value:?} of {field}")]
+    InvalidField { field: &'static str, value: String },
+}
+
+fn invalid(field: &'static str, value: impl ToString) -> DecodeError {
+    DecodeError::InvalidField {
+        field,
+        value: value.to_string(),
+    }
+}
+
+fn parse_trace_id(field: &'static str, value: &str) -> Result<TraceId, DecodeError> {
+    match value.len() {
+        16 | 32 => TraceId::from_hex(value).map_err(|_| invalid(field, value)),
+        _ => Err(invalid(field, value)),
+    }
+}
+
+fn parse_span_id(field: &'static str, value: &str) -> Result<SpanId, DecodeError> {
+    match value.len() {
+        16 => SpanId::from_hex(value).map_err(|_| invalid(field, value)),
+        _ => Err(invalid(field, value)),
+    }
+}
+
+fn millis(field: &'static str, value: &str) -> Result<SystemTime, DecodeError> {
+    value
+        .parse::<u64>()
+        .map(|ms| SystemTime::UNIX_EPOCH + Duration::from_millis(ms))
+        .map_err(|_| invalid(field, value))
+}
+
+/// Lower 64 bits of a trace id
+fn lower_trace_id(trace_id: TraceId) -> TraceId {
+    TraceId::from(u128::from_be_bytes(trace_id.to_bytes()) & u64::MAX as u128)
+}
+
+/// Milliseconds since the epoch, as sent to the agent
+fn truncate_to_millis(time: SystemTime) -> SystemTime {
+    let since_epoch = time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+    SystemTime::UNIX_EPOCH + Duration::from_millis(since_epoch.as_millis() as u64)
+}
+
+/// Decode a rawtrace payload, a JSON list of Instana spans
+pub fn decode_batch(payload: &[u8]) -> Result<Vec<DecodedSpan>, DecodeError> {
+    let spans: Vec<InstanaSpan> = serde_json::from_slice(payload)?;
+    spans.iter().map(DecodedSpan::from_instana_span).collect()
+}
+
+impl DecodedSpan {
+    pub fn from_instana_span(span: &InstanaSpan) -> Result<Self, DecodeError> {
+        let trace_id = match &span.long_trace_id {
+            Some(long_trace_id) => parse_trace_id("lt", long_trace_id)?,
+            None => parse_trace_id("t", &span.trace_id)?,
+        };
+        let parent_span_id = match &span.parent_span_id {
+            Some(parent) => parse_span_id("p", parent)?,
+            None => SpanId::INVALID,
+        };
+        let kind = InstanaKind::from_code(span.kind).ok_or_else(|| invalid("k", span.kind))?;
+
+        let tags = &span.data.sdk.custom.tags;
+        let status = match tags.otel.status_code.as_deref() {
+            Some(STATUS_OK) => Status::Ok,
+            Some(STATUS_ERROR) => {
+                Status::error(tags.otel.status_description.clone().unwrap_or_default())
+            },
+            Some(other) => return Err(invalid("status_code", other)),
+            None => Status::Unset,
+        };
+
+        let mut events = tags
+            .events
+            .iter()
+            .flatten()
+            .map(|(key, event)| {
+                Ok(DecodedEvent {
+                    key: key.clone(),
+                    timestamp: millis("events.timestamp", &event.timestamp)?,
+                    attributes: event.value.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+        events.sort_by(|a, b| a.key.cmp(&b.key));
+
+        let links = tags
+            .links
+            .iter()
+            .flatten()
+            .map(|link| {
+                Ok(DecodedLink {
+                    trace_id: parse_trace_id("links.t", &link.trace_id)?,
+                    span_id: parse_span_id("links.s", &link.span_id)?,
+                    attributes: link.attributes.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+
+        Ok(DecodedSpan {
+            trace_id,
+            span_id: parse_span_id("s", &span.span_id)?,
+            parent_span_id,
+            kind,
+            name: span.data.sdk.name.clone(),
+            start_time: SystemTime::UNIX_EPOCH + Duration::from_millis(span.timestamp),
+            duration: Duration::from_millis(span.duration),
+            synthetic: span.synthetic,
+            status,
+            service: span.data.service.clone(),
+            attributes: tags.attributes.clone().unwrap_or_default(),
+            resource: tags.resource.clone().unwrap_or_default(),
+            events,
+            links,
+            scope_name: tags.otel.scope_name.clone(),
+            scope_version: tags.otel.scope_version.clone(),
+            dropped_attributes_count: tags.otel.dropped_attributes_count,
+            dropped_events_count: tags.otel.dropped_events_count,
+            dropped_links_count: tags.otel.dropped_links_count,
+        })
+    }
+
+    /// Differences to the span this span was converted from, empty if the
+    /// conversion preserved everything the payload can carry.
+    ///
+    /// Redacted attribute values match any value.
+    pub fn mismatches(&self, span: &SpanData) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        let mut check = |field: &str, decoded: String, expected: String| {
+            if decoded != expected {
+                mismatches.push(format!("{field}: decoded {decoded}, expected {expected}"));
+            }
+        };
+
+        let expected_trace_id = match self.kind {
+            InstanaKind::Entry => span.span_context.trace_id(),
+            _ => lower_trace_id(span.span_context.trace_id()),
+        };
+        check("trace_id", self.trace_id.to_string(), expected_trace_id.to_string());
+        check("span_id", self.span_id.to_string(), span.span_context.span_id().to_string());
+        check("parent_span_id", self.parent_span_id.to_string(), span.parent_span_id.to_string());
+        check(
+            "kind",
+            format!("{:?}", self.kind),
+            format!("{:?}", InstanaKind::of(&span.span_kind)),
+        );
+        check("name", self.name.clone(), span.name.to_string());
+        check(
+            "start_time",
+            format!("{:?}", self.start_time),
+            format!("{:?}", truncate_to_millis(span.start_time)),
+        );
+        let expected_duration = span
+            .end_time
+            .duration_since(span.start_time)
+            .map(|duration| Duration::from_millis(duration.as_millis() as u64))
+            .unwrap_or_default();
+        check(
+            "duration",
+            format!("{:?}", self.duration),
+            format!("{expected_duration:?}"),
+        );
+        check("status", format!("{:?}", self.status), format!("{:?}", span.status));
+        check(
+            "scope_name",
+            self.scope_name.clone(),
+            span.instrumentation_scope.name().to_string(),
+        );
+        check(
+            "scope_version",
+            format!("{:?}", self.scope_version),
+            format!("{:?}", span.instrumentation_scope.version()),
+        );
+        check(
+            "dropped_events_count",
+            self.dropped_events_count.to_string(),
+            span.events.dropped_count.to_string(),
+        );
+        check(
+            "dropped_links_count",
+            self.dropped_links_count.to_string(),
+            span.links.dropped_count.to_string(),
+        );
+
+        let expected_attributes: HashMap<String, serde_json::Value> = span
+            .attributes
+            .iter()
+            .filter(|kv| !EXPORTED_INTERNAL_TAGS.contains(&kv.key.as_str()))
+            .map(|kv| (kv.key.to_string(), convert_value_to_json(&kv.value)))
+            .collect();
+        check(
+            "attributes",
+            sorted(&redacted_like(&self.attributes, &expected_attributes)),
+            sorted(&expected_attributes),
+        );
+
+        let keys = event_keys(span.events.events.iter().map(|event| event.name.as_ref()));
+        let mut expected_events: Vec<DecodedEvent> = span
+            .events
+            .events
+            .iter()
+            .zip(keys)
+            .map(|(event, key)| DecodedEvent {
+                key,
+                timestamp: truncate_to_millis(event.timestamp),
+                attributes: event
+                    .attributes
+                    .iter()
+                    .map(|kv| (kv.key.to_string(), convert_value_to_json(&kv.value)))
+                    .collect(),
+            })
+            .collect();
+        expected_events.sort_by(|a, b| a.key.cmp(&b.key));
+        check(
+            "events",
+            format!("{:?}", sorted_events(&self.events)),
+            format!("{:?}", sorted_events(&expected_events)),
+        );
+
+        let expected_links: Vec<String> = span
+            .links
+            .links
+            .iter()
+            .map(|link| {
+                let attributes = link
+                    .attributes
+                    .iter()
+                    .map(|kv| (kv.key.to_string(), convert_value_to_json(&kv.value)))
+                    .collect();
+                format!(
+                    "{}/{} {}",
+                    lower_trace_id(link.span_context.trace_id()),
+                    link.span_context.span_id(),
+                    sorted(&attributes)
+                )
+            })
+            .collect();
+        let links: Vec<String> = self
+            .links
+            .iter()
+            .map(|link| format!("{}/{} {}", link.trace_id, link.span_id, sorted(&link.attributes)))
+            .collect();
+        check("links", format!("{links:?}"), format!("{expected_links:?}"));
+
+        mismatches
+    }
+}
+
+/// The decoded attributes, with redacted values replaced by the expected ones
+fn redacted_like(
+    decoded: &HashMap<String, serde_json::Value>,
+    expected: &HashMap<String, serde_json::Value>,
+) -> HashMap<String, serde_json::Value> {
+    decoded
+        .iter()
+        .map(|(key, value)| match (value.as_str(), expected.get(key)) {
+            (Some(REDACTED), Some(expected)) => (key.clone(), expected.clone()),
+            _ => (key.clone(), value.clone()),
+        })
+        .collect()
+}
+
+/// Attributes in key order, to compare them
+fn sorted(attributes: &HashMap<String, serde_json::Value>) -> String {
+    let mut attributes: Vec<_> = attributes.iter().collect();
+    attributes.sort_by(|a, b| a.0.cmp(b.0));
+    format!("{attributes:?}")
+}
+
+fn sorted_events(events: &[DecodedEvent]) -> Vec<String> {
+    events
+        .iter()
+        .map(|event| format!("{} {:?} {}", event.key, event.timestamp, sorted(&event.attributes)))
+        .collect()
+}
@@ -0,0 +1,127 @@
+use opentelemetry_http::HttpClient;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::BuildError;
+
+/// Default timeout of a request to the Instana agent
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default timeout for connecting to the Instana agent
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Build the default HTTP client of the exporter.
+///
+/// Within a tokio runtime, this is the async client of the `reqwest-client` or
+/// `hyper-client` feature, which sends on that runtime when called from a
+/// thread outside of it, e.g. the one of a `BatchSpanProcessor`. Otherwise it
+/// is the blocking client of the `reqwest-blocking-client` feature, or the
+/// async client if that feature is disabled.
+#[allow(unreachable_code, unused_variables)]
+pub(crate) fn default_http_client(
+    timeout: Duration,
+    connect_timeout: Duration,
+) -> Result<Arc<dyn HttpClient>, BuildError> {
+    #[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+    if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+        return Ok(Arc::new(RuntimeHttpClient {
+            client: async_http_client(timeout, connect_timeout)?,
+            runtime,
+        }));
+    }
+
+    #[cfg(feature = "reqwest-blocking-client")]
+    return blocking_http_client(timeout, connect_timeout);
+
+    #[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+    return async_http_client(timeout, connect_timeout);
+
+    Err(BuildError::NoHttpClient)
+}
+
+/// Build the HTTP client of the event client and the process collector, which
+/// send from the task of the caller.
+///
+/// An async client is used when called within a tokio runtime, or when the
+/// `reqwest-blocking-client` feature is disabled, the blocking client otherwise.
+#[allow(unreachable_code, unused_variables)]
+pub(crate) fn runtime_http_client(
+    timeout: Duration,
+    connect_timeout: Duration,
+) -> Result<Arc<dyn HttpClient>, BuildError> {
+    #[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+    if !cfg!(feature = "reqwest-blocking-client") || tokio::runtime::Handle::try_current().is_ok() {
+        return async_http_client(timeout, connect_timeout);
+    }
+
+    default_http_client(timeout, connect_timeout)
+}
+
+#[cfg(feature = "reqwest-client")]
+pub(crate) fn async_http_client(
+    timeout: Duration,
+    connect_timeout: Duration,
+) -> Result<Arc<dyn HttpClient>, BuildError> {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(connect_timeout)
+        .build()
+        .map(|client| Arc::new(client) as Arc<dyn HttpClient>)
+        .map_err(|e| BuildError::HttpClient(e.to_string()))
+}
+
+#[cfg(all(feature = "hyper-client", not(feature = "reqwest-client")))]
+pub(crate) fn async_http_client(
+    timeout: Duration,
+    connect_timeout: Duration,
+) -> Result<Arc<dyn HttpClient>, BuildError> {
+    use hyper_util::client::legacy::connect::HttpConnector;
+    use opentelemetry_http::hyper::HyperClient;
+
+    let mut connector = HttpConnector::new();
+    connector.set_connect_timeout(Some(connect_timeout));
+    Ok(Arc::new(HyperClient::new(connector, timeout, None)))
+}
+
+#[cfg(feature = "reqwest-blocking-client")]
+pub(crate) fn blocking_http_client(
+    timeout: Duration,
+    connect_timeout: Duration,
+) -> Result<Arc<dyn HttpClient>, BuildError> {
+    // The blocking client must not be created within an async runtime
+    std::thread::spawn(move || {
+        reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(connect_timeout)
+            .build()
+    })
+    .join()
+    .map_err(|_| BuildError::ThreadSpawnFailed)?
+    .map(|client| Arc::new(client) as Arc<dyn HttpClient>)
+    .map_err(|e| BuildError::HttpClient(e.to_string()))
+}
+
+/// Async client sending on the runtime it was created in when called from a
+/// thread outside of any runtime.
+#[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+#[derive(Debug)]
+struct RuntimeHttpClient {
+    client: Arc<dyn HttpClient>,
+    runtime: tokio::runtime::Handle,
+}
+
+#[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+#[async_trait::async_trait]
+impl HttpClient for RuntimeHttpClient {
+    async fn send_bytes(
+        &self,
+        request: http::Request<bytes::Bytes>,
+    ) -> Result<http::Response<bytes::Bytes>, opentelemetry_http::HttpError> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return self.client.send_bytes(request).await;
+        }
+        let client = self.client.clone();
+        self.runtime
+            .spawn(async move { client.send_bytes(request).await })
+            .await?
+    }
+}
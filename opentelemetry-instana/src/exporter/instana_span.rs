@@ -1,3 +1,4 @@
+use opentelemetry::trace::SpanKind;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -33,6 +34,10 @@ pub struct InstanaSpan {
     pub correlation_type: Option<String>, // correlation type
     #[serde(rename = "tp", skip_serializing_if = "Option::is_none")]
     pub trace_parent: Option<bool>, // trace parent flag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack: Option<Vec<InstanaStackFrame>>, // call-site stack trace
+    #[serde(rename = "b", skip_serializing_if = "Option::is_none")]
+    pub batch: Option<InstanaSpanBatch>, // batched span information
 
     // Data section
     pub data: InstanaSpanData,
@@ -47,6 +52,18 @@ pub struct InstanaSpanData {
     pub sdk: InstanaSdk,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub service: Option<String>, // Service name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer: Option<InstanaSpanPeer>, // Destination of an exit span
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstanaSpanPeer {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>, // peer service name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>, // peer host name or address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<i64>, // peer port
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -108,6 +125,24 @@ pub struct InstanaOtel {
     pub status_description: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstanaSpanBatch {
+    #[serde(rename = "s")]
+    pub size: i64, // number of spans merged into this span
+    #[serde(rename = "d")]
+    pub duration: u64, // summed duration in milliseconds
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstanaStackFrame {
+    #[serde(rename = "m")]
+    pub method: String, // method or function name
+    #[serde(rename = "c", skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>, // source file
+    #[serde(rename = "n", skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>, // line number
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InstanaSpanFrom {
     #[serde(rename = "e", skip_serializing_if = "Option::is_none")]
@@ -117,3 +152,50 @@ pub struct InstanaSpanFrom {
 }
 
 // Made with Bob
+
+/// Kind of an Instana span
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanaKind {
+    Entry,
+    Exit,
+    Intermediate,
+}
+
+impl InstanaKind {
+    /// Parse the `k` field
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            1 => Some(InstanaKind::Entry),
+            2 => Some(InstanaKind::Exit),
+            3 => Some(InstanaKind::Intermediate),
+            _ => None,
+        }
+    }
+
+    /// Kind an OpenTelemetry span is exported as
+    pub fn of(kind: &SpanKind) -> Self {
+        match kind {
+            SpanKind::Server | SpanKind::Producer => InstanaKind::Entry,
+            SpanKind::Client | SpanKind::Consumer => InstanaKind::Exit,
+            SpanKind::Internal => InstanaKind::Intermediate,
+        }
+    }
+
+    /// Value of the `k` field
+    pub fn code(self) -> i32 {
+        match self {
+            InstanaKind::Entry => 1,
+            InstanaKind::Exit => 2,
+            InstanaKind::Intermediate => 3,
+        }
+    }
+
+    /// Name of the kind in the `sdk.type` field and in span filter rules
+    pub fn name(self) -> &'static str {
+        match self {
+            InstanaKind::Entry => "entry",
+            InstanaKind::Exit => "exit",
+            InstanaKind::Intermediate => "intermediate",
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Self-telemetry of the exporter, following the OpenTelemetry SDK
+//! self-observability conventions (`otel.sdk.exporter.*`).
+
+use opentelemetry::metrics::{Counter, Histogram, MeterProvider, UpDownCounter};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_http::{HttpError, StatusError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::circuit_breaker::CircuitState;
+
+pub const SPAN_EXPORTED: &str = "otel.sdk.exporter.span.exported";
+pub const SPAN_INFLIGHT: &str = "otel.sdk.exporter.span.inflight";
+pub const OPERATION_DURATION: &str = "otel.sdk.exporter.operation.duration";
+pub const PAYLOAD_SIZE: &str = "otel.sdk.exporter.payload.size";
+pub const CIRCUIT_TRANSITIONS: &str = "otel.sdk.exporter.circuit_breaker.transitions";
+pub const SPAN_FILTERED: &str = "otel.sdk.exporter.span.filtered";
+
+/// Value of the `otel.component.type` attribute
+pub const COMPONENT_TYPE: &str = "instana_span_exporter";
+
+const OTEL_COMPONENT_TYPE: &str = "otel.component.type";
+const OTEL_COMPONENT_NAME: &str = "otel.component.name";
+const ERROR_TYPE: &str = "error.type";
+const HTTP_RESPONSE_STATUS_CODE: &str = "http.response.status_code";
+const CIRCUIT_STATE: &str = "state";
+const FILTER_RULE: &str = "rule";
+
+/// Why an export failed, recorded as `error.type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FailureReason {
+    Serialization,
+    HttpStatus(u16),
+    Connection,
+    CircuitOpen,
+}
+
+impl FailureReason {
+    /// Classify an error of the HTTP client, which reports non-2xx statuses as errors
+    pub(crate) fn from_http_error(error: &HttpError) -> Self {
+        #[cfg(any(feature = "reqwest-client", feature = "reqwest-blocking-client"))]
+        if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+            return match error.status() {
+                Some(status) => FailureReason::HttpStatus(status.as_u16()),
+                None => FailureReason::Connection,
+            };
+        }
+
+        // `ResponseExt::error_for_status` of the hyper client
+        match error.downcast_ref::<StatusError>() {
+            Some(error) => FailureReason::HttpStatus(error.status().as_u16()),
+            None => FailureReason::Connection,
+        }
+    }
+
+    fn error_type(&self) -> &'static str {
+        match self {
+            FailureReason::Serialization => "serialization",
+            FailureReason::HttpStatus(_) => "http_status",
+            FailureReason::Connection => "connection",
+            FailureReason::CircuitOpen => "circuit_open",
+        }
+    }
+}
+
+/// Instruments recording the exports of one exporter
+#[derive(Debug, Clone)]
+pub(crate) struct ExporterMetrics {
+    span_exported: Counter<u64>,
+    span_inflight: UpDownCounter<i64>,
+    operation_duration: Histogram<f64>,
+    payload_size: Histogram<u64>,
+    circuit_transitions: Counter<u64>,
+    span_filtered: Counter<u64>,
+    attributes: Vec<KeyValue>, // identify the exporter instance
+}
+
+impl ExporterMetrics {
+    pub(crate) fn new(provider: &impl MeterProvider) -> Self {
+        static INSTANCE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let instance = INSTANCE_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        let scope = InstrumentationScope::builder(env!("CARGO_PKG_NAME"))
+            .with_version(env!("CARGO_PKG_VERSION"))
+            .build();
+        let meter = provider.meter_with_scope(scope);
+
+        ExporterMetrics {
+            span_exported: meter
+                .u64_counter(SPAN_EXPORTED)
+                .with_unit("{span}")
+                .with_description("The number of spans for which the export has finished, either successful or failed")
+                .build(),
+            span_inflight: meter
+                .i64_up_down_counter(SPAN_INFLIGHT)
+                .with_unit("{span}")
+                .with_description("The number of spans which were passed to the exporter, but that have not been exported yet")
+                .build(),
+            operation_duration: meter
+                .f64_histogram(OPERATION_DURATION)
+                .with_unit("s")
+                .with_description("The duration of exporting a batch of spans")
+                .build(),
+            payload_size: meter
+                .u64_histogram(PAYLOAD_SIZE)
+                .with_unit("By")
+                .with_description("The size of the payloads sent to the agent")
+                .build(),
+            circuit_transitions: meter
+                .u64_counter(CIRCUIT_TRANSITIONS)
+                .with_unit("{transition}")
+                .with_description("The number of times the circuit breaker opened or closed")
+                .build(),
+            span_filtered: meter
+                .u64_counter(SPAN_FILTERED)
+                .with_unit("{span}")
+                .with_description("The number of spans dropped by span filter rules")
+                .build(),
+            attributes: vec![
+                KeyValue::new(OTEL_COMPONENT_TYPE, COMPONENT_TYPE),
+                KeyValue::new(OTEL_COMPONENT_NAME, format!("{COMPONENT_TYPE}/{instance}")),
+            ],
+        }
+    }
+
+    /// Record that an export of `span_count` spans started. The spans count as
+    /// in flight until the returned guard is dropped, also when the export is
+    /// cancelled.
+    pub(crate) fn export_started(&self, span_count: usize) -> InflightSpans<'_> {
+        self.span_inflight.add(span_count as i64, &self.attributes);
+        InflightSpans {
+            metrics: self,
+            span_count,
+        }
+    }
+
+    pub(crate) fn payload_serialized(&self, size: usize) {
+        self.payload_size.record(size as u64, &self.attributes);
+    }
+
+    /// Record that the circuit breaker changed to `state`
+    pub(crate) fn circuit_transition(&self, state: CircuitState) {
+        let state = match state {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+        };
+        let mut attributes = self.attributes.clone();
+        attributes.push(KeyValue::new(CIRCUIT_STATE, state));
+        self.circuit_transitions.add(1, &attributes);
+    }
+
+    /// Record that the filter rule `rule` dropped `span_count` spans
+    pub(crate) fn spans_filtered(&self, rule: &str, span_count: u64) {
+        let mut attributes = self.attributes.clone();
+        attributes.push(KeyValue::new(FILTER_RULE, rule.to_string()));
+        self.span_filtered.add(span_count, &attributes);
+    }
+
+    /// Record the outcome of an export of `span_count` spans
+    pub(crate) fn export_finished(
+        &self,
+        span_count: usize,
+        duration: Duration,
+        failure: Option<FailureReason>,
+    ) {
+        let mut attributes = self.attributes.clone();
+        if let Some(reason) = failure {
+            attributes.push(KeyValue::new(ERROR_TYPE, reason.error_type()));
+        }
+        self.span_exported.add(span_count as u64, &attributes);
+
+        if let Some(FailureReason::HttpStatus(status)) = failure {
+            attributes.push(KeyValue::new(HTTP_RESPONSE_STATUS_CODE, status as i64));
+        }
+        self.operation_duration
+            .record(duration.as_secs_f64(), &attributes);
+    }
+}
+
+/// Spans of a running export, see [`ExporterMetrics::export_started`]
+#[derive(Debug)]
+pub(crate) struct InflightSpans<'a> {
+    metrics: &'a ExporterMetrics,
+    span_count: usize,
+}
+
+impl Drop for InflightSpans<'_> {
+    fn drop(&mut self) {
+        self.metrics
+            .span_inflight
+            .add(-(self.span_count as i64), &self.metrics.attributes);
+    }
+}
@@ -1,7 +1,18 @@
+pub mod agent_config;
+pub mod agent_discovery;
+pub mod circuit_breaker;
+pub mod config;
 mod defs;
-mod instana_span;
+pub mod deserialize_span;
+mod http_client;
+pub mod instana_span;
+pub mod metrics;
+pub mod process_identity;
 pub mod serialize_span;
+pub mod span_batching;
 pub mod span_data;
+pub mod span_filter;
+pub mod span_schema;
 
 use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
 
@@ -12,13 +23,47 @@ use std::sync::{
 };
 
 use http::{header::CONTENT_TYPE, Method};
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry::otel_debug;
 use opentelemetry::Value;
 use opentelemetry_http::HttpClient;
 use opentelemetry_sdk::trace::SpanExporter;
 use std::env;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use url::Url;
 
+pub use agent_config::{AgentConfig, AgentConfigError, SpanCategory};
+use agent_config::RemoteConfig;
+pub use agent_discovery::{AgentDiscovery, AgentHost};
+use circuit_breaker::CircuitBreaker;
+pub use circuit_breaker::{CircuitBreakerOptions, CircuitState};
+pub use config::{LogLevel, Secrets, SecretsMatcher};
+use metrics::{ExporterMetrics, FailureReason};
+pub use process_identity::ProcessIdentity;
+#[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+use http_client::async_http_client;
+#[cfg(feature = "reqwest-blocking-client")]
+use http_client::blocking_http_client;
+use http_client::default_http_client;
+pub(crate) use http_client::runtime_http_client;
+pub use http_client::{DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT};
+pub use span_batching::SpanBatchingOptions;
+pub use span_filter::{FilterCondition, FilterRule, MatchType, SpanFilter, SpanFilterError};
+
+pub(crate) const RAWTRACE_PATH: &str = "/com.instana.plugin.generic.rawtrace";
+
+/// Base URL of the Instana agent, from `INSTANA_AGENT_HOST` and `INSTANA_AGENT_PORT`
+pub(crate) fn agent_url() -> String {
+    let host = env::var(config::INSTANA_AGENT_HOST)
+        .unwrap_or_else(|_| defs::DEFAULT_INSTANA_AGENT_HOST.to_string());
+
+    let port = env::var(config::INSTANA_AGENT_PORT)
+        .unwrap_or_else(|_| defs::DEFAULT_INSTANA_AGENT_PORT.to_string());
+
+    format!("http://{}:{}", host, port)
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct InstanaExporterOptions {
     pub endpoint: String,
@@ -26,6 +71,29 @@ pub struct InstanaExporterOptions {
     pub source_address: String,
     pub service: String,
     pub headers: http::HeaderMap,
+    /// Merge repetitive short exit spans into batched spans, disabled if `None`
+    pub span_batching: Option<SpanBatchingOptions>,
+    /// Timeout of requests to the agent, overridden by `Builder::with_timeout`
+    pub timeout: Option<Duration>,
+    /// Drop all spans instead of sending them to the agent
+    pub disabled: bool,
+    /// Attributes whose values are redacted
+    pub secrets: Secrets,
+    /// Names of HTTP headers instrumentations should capture
+    pub extra_http_headers: Vec<String>,
+    pub log_level: LogLevel,
+    /// Discover the agent instead of sending spans to `endpoint`, which is
+    /// used as fallback if no agent is found
+    pub agent_discovery: Option<AgentDiscovery>,
+    /// Stop sending to an unavailable agent, disabled if `None`
+    pub circuit_breaker: Option<CircuitBreakerOptions>,
+    /// Announce the process to the agent and apply the tracing configuration
+    /// it returns. Options set locally take precedence.
+    pub remote_config: bool,
+    /// Time after which the configuration of the agent is reloaded
+    pub remote_config_interval: Duration,
+    /// Rules dropping spans before they are sent to the agent
+    pub span_filter: Option<SpanFilter>,
 }
 
 impl Default for InstanaExporterOptions {
@@ -36,21 +104,28 @@ impl Default for InstanaExporterOptions {
             http::HeaderValue::from_static("application/json"),
         );
 
-        let host = env::var("INSTANA_AGENT_HOST")
-            .unwrap_or_else(|_| defs::DEFAULT_INSTANA_AGENT_HOST.to_string());
-
-        let port = env::var("INSTANA_AGENT_PORT")
-            .unwrap_or_else(|_| defs::DEFAULT_INSTANA_AGENT_PORT.to_string());
-
         InstanaExporterOptions {
             endpoint: format!(
-                "http://{}:{}/com.instana.plugin.generic.rawtrace",
-                host, port
+                "http://{}:{}{}",
+                defs::DEFAULT_INSTANA_AGENT_HOST,
+                defs::DEFAULT_INSTANA_AGENT_PORT,
+                RAWTRACE_PATH
             ),
             hostname: String::new(),
             source_address: String::new(),
             service: String::new(),
             headers: headers_,
+            span_batching: None,
+            timeout: None,
+            disabled: false,
+            secrets: Secrets::default(),
+            extra_http_headers: Vec::new(),
+            log_level: LogLevel::default(),
+            agent_discovery: None,
+            circuit_breaker: None,
+            remote_config: false,
+            remote_config_interval: agent_config::DEFAULT_REMOTE_CONFIG_INTERVAL,
+            span_filter: None,
         }
     }
 }
@@ -65,6 +140,60 @@ impl InstanaExporterOptions {
             Err(_) => None,
         }
     }
+
+    /// Read the options from the standard Instana environment variables.
+    ///
+    /// Unset variables keep their default value, malformed values are
+    /// reported as [`BuildError::InvalidEnvVar`].
+    pub fn from_env() -> Result<Self, BuildError> {
+        let mut options = Self::default();
+
+        let host = config::var(config::INSTANA_AGENT_HOST)
+            .unwrap_or_else(|| defs::DEFAULT_INSTANA_AGENT_HOST.to_string());
+        let port = match config::var(config::INSTANA_AGENT_PORT) {
+            Some(port) => config::parse_port(&port)?,
+            None => defs::DEFAULT_INSTANA_AGENT_PORT as u16,
+        };
+        let endpoint = format!("http://{}:{}{}", host, port, RAWTRACE_PATH);
+        options.endpoint = Url::parse(&endpoint)
+            .map_err(|e| BuildError::InvalidEnvVar {
+                name: config::INSTANA_AGENT_HOST,
+                value: host.clone(),
+                reason: e.to_string(),
+            })?
+            .to_string();
+
+        if let Some(service) = config::var(config::INSTANA_SERVICE_NAME) {
+            options.service = service;
+        }
+        if let Some(timeout) = config::var(config::INSTANA_TIMEOUT) {
+            options.timeout = Some(config::parse_timeout(&timeout)?);
+        }
+        if let Some(disabled) = config::var(config::INSTANA_DISABLE_TRACING) {
+            options.disabled = config::parse_bool(config::INSTANA_DISABLE_TRACING, &disabled)?;
+        }
+        if let Some(secrets) = config::var(config::INSTANA_SECRETS) {
+            options.secrets = config::parse_secrets(&secrets)?;
+        }
+        if let Some(headers) = config::var(config::INSTANA_EXTRA_HTTP_HEADERS) {
+            options.extra_http_headers = config::parse_extra_http_headers(&headers)?;
+        }
+        if let Some(level) = config::var(config::INSTANA_LOG_LEVEL) {
+            options.log_level = config::parse_log_level(&level)?;
+        }
+        if let Some(filter) = config::var(config::INSTANA_TRACING_FILTER) {
+            options.span_filter = Some(config::parse_tracing_filter(&filter)?);
+        } else if let Some(path) = config::var(config::INSTANA_CONFIG_PATH) {
+            options.span_filter = config::read_tracing_filter(&path)?;
+        }
+        if let Some(debug) = config::var(config::INSTANA_DEBUG) {
+            if config::parse_bool(config::INSTANA_DEBUG, &debug)? {
+                options.log_level = LogLevel::Debug;
+            }
+        }
+
+        Ok(options)
+    }
 }
 
 #[derive(Error, Debug)]
@@ -75,9 +204,31 @@ pub enum BuildError {
     #[error("Spawning a new thread failed. Unable to create Reqwest-Blocking client.")]
     ThreadSpawnFailed,
 
-    /// No Http client specified.
+    /// No Http client specified, and no HTTP client feature is enabled.
     #[error("no http client specified")]
     NoHttpClient,
+
+    /// The default HTTP client could not be created.
+    #[error("failed to create the http client: {0}")]
+    HttpClient(String),
+
+    /// The pipeline is installed in a `current_thread` tokio runtime, which
+    /// cannot drive the exports of the batch span processor thread.
+    #[error("exports cannot run on a current_thread tokio runtime, use a multi_thread runtime or the reqwest-blocking-client feature")]
+    CurrentThreadRuntime,
+
+    /// The pipeline is installed outside of a tokio runtime, which the async
+    /// HTTP client needs to send.
+    #[error("the async http client needs a tokio runtime, install within a multi_thread runtime or use the reqwest-blocking-client feature")]
+    NoRuntime,
+
+    /// An environment variable has a malformed value.
+    #[error("invalid value {value:?} of {name}: {reason}")]
+    InvalidEnvVar {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
 }
 
 #[derive(Debug)]
@@ -86,6 +237,11 @@ pub struct InstanaExporter {
     options_: InstanaExporterOptions,
     is_shutdown_: AtomicBool,
     resource_: opentelemetry_sdk::Resource,
+    process_identity_: ProcessIdentity,
+    metrics_: Option<ExporterMetrics>,
+    circuit_breaker_: Option<CircuitBreaker>,
+    remote_config_: Option<RemoteConfig>,
+    filtered_spans_: Mutex<Vec<(String, u64)>>, // spans dropped by each exclude rule
 }
 
 impl PartialEq for InstanaExporter {
@@ -102,15 +258,43 @@ impl Default for InstanaExporter {
         let is_shutdown = AtomicBool::new(false);
 
         let resource = Resource::builder_empty().build();
+        let circuit_breaker = options
+            .circuit_breaker
+            .clone()
+            .map(|circuit_breaker| CircuitBreaker::new(circuit_breaker, options.log_level));
+        let remote_config = options
+            .remote_config
+            .then(|| RemoteConfig::new(
+                options.remote_config_interval,
+                options.log_level,
+                options.span_filter.clone(),
+            ));
+        let filtered_spans = filter_counters(&options);
         Self {
             options_: options,
             is_shutdown_: is_shutdown,
             client_: Mutex::new(None),
             resource_: resource,
+            process_identity_: ProcessIdentity::current().clone(),
+            metrics_: None,
+            circuit_breaker_: circuit_breaker,
+            remote_config_: remote_config,
+            filtered_spans_: filtered_spans,
         }
     }
 }
 
+/// Counters of dropped spans for the exclude rules of the span filter, the
+/// rules of the agent configuration are added once they dropped a span
+fn filter_counters(options: &InstanaExporterOptions) -> Mutex<Vec<(String, u64)>> {
+    let rules = options
+        .span_filter
+        .as_ref()
+        .map(|span_filter| span_filter.exclude.as_slice())
+        .unwrap_or_default();
+    Mutex::new(rules.iter().map(|rule| (rule.name.clone(), 0)).collect())
+}
+
 impl SpanExporter for InstanaExporter {
     async fn export(&self, batch: Vec<opentelemetry_sdk::trace::SpanData>) -> OTelSdkResult {
         // Get resource
@@ -134,51 +318,94 @@ impl SpanExporter for InstanaExporter {
             Err(err) => return Err(err),
         };
 
-        // Serialize batch to JSON bytes
-        let export_body = match serialize_span::serialize_batch(self, &batch) {
-            Ok(body) => body,
-            Err(e) => {
-                return Err(OTelSdkError::InternalFailure(format!(
-                    "Serialization error: {}",
-                    e
-                )))
+        // Drop spans if tracing is disabled
+        if self.options_.disabled {
+            return Ok(());
+        }
+
+        // Resolve endpoint
+        let endpoint = match &self.options_.agent_discovery {
+            Some(discovery) => match discovery.agent_url(client.as_ref()).await {
+                Some(agent_url) => format!("{}{}", agent_url, RAWTRACE_PATH),
+                None => self.options_.endpoint.clone(),
             },
+            None => self.options_.endpoint.clone(),
         };
 
-        // Build request
-        let mut request = match http::Request::builder()
-            .method(Method::POST)
-            .uri(&self.options_.endpoint)
-            .header(CONTENT_TYPE, "application/json")
-            .body(export_body)
-        {
-            Ok(req) => req,
-            Err(e) => return Err(OTelSdkError::InternalFailure(e.to_string())),
-        };
+        // Apply the tracing configuration of the agent
+        let mut batch = batch;
+        if let Some(remote_config) = &self.remote_config_ {
+            let agent_url = agent_discovery::base_url(&endpoint);
+            remote_config
+                .refresh(client.as_ref(), &agent_url, &self.process_identity_)
+                .await;
+            if let Some(config) = remote_config.current() {
+                if config.tracing_disabled {
+                    return Ok(());
+                }
+                batch.retain(|span| !config.is_disabled(span));
+            }
+        }
 
-        // Add headers
-        for (k, v) in &self.options_.headers {
-            request.headers_mut().insert(k.clone(), v.clone());
+        // Drop spans matching the filter rules of the options and the agent
+        let remote_filter = self
+            .remote_config_
+            .as_ref()
+            .and_then(|remote_config| remote_config.span_filter());
+        if let Some(span_filter) = remote_filter.as_deref().or(self.options_.span_filter.as_ref()) {
+            batch = self.filter_spans(span_filter, batch);
         }
 
-        // Send request
-        let response = client
-            .send_bytes(request)
-            .await
-            .map_err(|e| OTelSdkError::InternalFailure(format!("{e:?}")))?;
+        let span_count = batch.len();
+        let started = Instant::now();
+        let inflight = self
+            .metrics_
+            .as_ref()
+            .map(|metrics| metrics.export_started(span_count));
+
+        // Drop the batch while the circuit is open
+        let allowed = match &self.circuit_breaker_ {
+            Some(breaker) => {
+                breaker
+                    .allow(client.as_ref(), &endpoint, self.metrics_.as_ref())
+                    .await
+            },
+            None => true,
+        };
+        let result = if allowed {
+            self.send_batch(client, &endpoint, batch).await
+        } else {
+            Err((
+                FailureReason::CircuitOpen,
+                OTelSdkError::InternalFailure(format!(
+                    "Circuit breaker open, dropped {} spans for {}",
+                    span_count, endpoint
+                )),
+            ))
+        };
 
-        // Check response
-        if !response.status().is_success() {
-            let error = format!(
-                "OpenTelemetry trace export failed. Url: {}, Status Code: {}, Response: {:?}",
-                self.options_.endpoint,
-                response.status().as_u16(),
-                response.body()
-            );
-            return Err(OTelSdkError::InternalFailure(error));
+        if let Some(breaker) = &self.circuit_breaker_ {
+            if allowed {
+                let failure = result.as_ref().err().map(|(reason, _)| *reason);
+                breaker.record(failure, self.metrics_.as_ref());
+            }
         }
 
-        Ok(())
+        if let Some(metrics) = &self.metrics_ {
+            let failure = result.as_ref().err().map(|(reason, _)| *reason);
+            metrics.export_finished(span_count, started.elapsed(), failure);
+        }
+        drop(inflight);
+        if self.options_.log_level.enabled(LogLevel::Debug) {
+            otel_debug!(
+                name: "InstanaExporter.BatchExported",
+                endpoint = endpoint.as_str(),
+                spans = span_count,
+                duration_ms = started.elapsed().as_millis(),
+                error = format!("{:?}", result.as_ref().err().map(|(_, err)| err))
+            );
+        }
+        result.map_err(|(_, err)| err)
     }
 
     fn shutdown(&mut self) -> opentelemetry_sdk::error::OTelSdkResult {
@@ -202,9 +429,32 @@ impl SpanExporter for InstanaExporter {
     }
 }
 
-#[derive(Default)]
 pub struct Builder {
     exporter: InstanaExporter,
+    timeout: Option<Duration>,
+    connect_timeout: Duration,
+    process_identity: Option<ProcessIdentity>,
+    metrics: Option<ExporterMetrics>,
+    #[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+    async_client: bool, // use the async client rather than the default one
+    #[cfg(feature = "reqwest-blocking-client")]
+    blocking_client: bool, // use the blocking client rather than the default one
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder {
+            exporter: InstanaExporter::default(),
+            timeout: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            process_identity: None,
+            metrics: None,
+            #[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+            async_client: false,
+            #[cfg(feature = "reqwest-blocking-client")]
+            blocking_client: false,
+        }
+    }
 }
 
 impl Builder {
@@ -223,38 +473,86 @@ impl Builder {
         return self;
     }
 
+    /// Set the timeout of requests sent by the default HTTP client
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the timeout for connecting to the agent of the default HTTP client
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// Replace the identity of the process detected from `/proc/self`
+    pub fn with_process_identity(mut self, identity: ProcessIdentity) -> Self {
+        self.process_identity = Some(identity);
+        self
+    }
+
+    /// Record the exporter's own metrics with meters of the given provider
+    pub fn with_meter_provider(mut self, provider: &impl MeterProvider) -> Self {
+        self.metrics = Some(ExporterMetrics::new(provider));
+        self
+    }
+
+    /// Send with the async client of the `reqwest-client` or `hyper-client`
+    /// feature, also when the exporter is built outside of a tokio runtime.
+    /// Exports must then run within a tokio runtime, e.g. with the span
+    /// processors of the `experimental_async_runtime` feature of
+    /// `opentelemetry_sdk`.
+    #[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+    pub fn with_async_http_client(mut self) -> Self {
+        self.async_client = true;
+        self
+    }
+
+    /// Send with the blocking client of the `reqwest-blocking-client` feature,
+    /// also when the exporter is built within a tokio runtime. Exports must
+    /// then run outside of it, e.g. on the thread of a `BatchSpanProcessor`.
+    #[cfg(feature = "reqwest-blocking-client")]
+    pub fn with_blocking_http_client(mut self) -> Self {
+        self.blocking_client = true;
+        self
+    }
+
     pub fn build(self) -> Result<InstanaExporter, BuildError> {
         let mut http_client = self.exporter.client_.lock().unwrap().take();
         if http_client.is_none() {
-            let client_result = std::thread::spawn(move || {
-                reqwest::blocking::Client::builder()
-                    .build()
-                    .unwrap_or_else(|_| reqwest::blocking::Client::new())
-            })
-            .join();
-            match client_result {
-                Ok(client) => {
-                    http_client = Some(Arc::new(client) as Arc<dyn HttpClient>);
-                },
-                Err(_) => {
-                    return Err(BuildError::ThreadSpawnFailed);
-                },
+            let timeout = self
+                .timeout
+                .or(self.exporter.options_.timeout)
+                .unwrap_or(DEFAULT_TIMEOUT);
+            #[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+            if self.async_client {
+                http_client = Some(async_http_client(timeout, self.connect_timeout)?);
+            }
+            #[cfg(feature = "reqwest-blocking-client")]
+            if self.blocking_client {
+                http_client = Some(blocking_http_client(timeout, self.connect_timeout)?);
+            }
+            if http_client.is_none() {
+                http_client = Some(default_http_client(timeout, self.connect_timeout)?);
             }
         }
         let http_client = http_client.ok_or(BuildError::NoHttpClient)?;
-        Ok(InstanaExporter::new(
+        let mut exporter = InstanaExporter::new(
             http_client,
             self.exporter.options_,
             self.exporter.resource_,
-        ))
+        );
+        if let Some(identity) = self.process_identity {
+            exporter.process_identity_ = identity;
+        }
+        exporter.metrics_ = self.metrics;
+        Ok(exporter)
     }
 }
 
 impl InstanaExporter {
     pub fn builder() -> Builder {
-        Builder {
-            exporter: InstanaExporter::default(),
-        }
+        Builder::default()
     }
 
     pub fn get_resource(&self) -> Resource {
@@ -273,6 +571,88 @@ impl InstanaExporter {
         self.resource_.get(&"process.pid".into())
     }
 
+    /// State of the circuit breaker, `None` if disabled
+    pub fn get_circuit_state(&self) -> Option<CircuitState> {
+        self.circuit_breaker_.as_ref().map(|breaker| breaker.state())
+    }
+
+    /// Number of spans dropped by each exclude rule of the span filter,
+    /// followed by the rules of the agent configuration which dropped spans
+    pub fn get_filtered_span_counts(&self) -> Vec<(String, u64)> {
+        self.filtered_spans_
+            .lock()
+            .map(|counts| counts.clone())
+            .unwrap_or_default()
+    }
+
+    /// Tracing configuration last received from the agent
+    pub fn get_agent_config(&self) -> Option<AgentConfig> {
+        self.remote_config_
+            .as_ref()
+            .and_then(|remote_config| remote_config.current())
+            .map(|config| config.as_ref().clone())
+    }
+
+    /// Names of HTTP headers instrumentations should capture, from the
+    /// options or else the agent configuration
+    pub fn get_extra_http_headers(&self) -> Vec<String> {
+        if !self.options_.extra_http_headers.is_empty() {
+            return self.options_.extra_http_headers.clone();
+        }
+        self.get_agent_config()
+            .map(|config| config.extra_http_headers)
+            .unwrap_or_default()
+    }
+
+    /// Whether the HTTP header attribute `key`, e.g.
+    /// `http.request.header.x-request-id`, is exported. If extra HTTP headers
+    /// are set by the options or else the agent configuration, only those are
+    /// captured. Other attributes are always exported.
+    pub(crate) fn captures_header(&self, key: &str) -> bool {
+        let Some(name) = key
+            .strip_prefix("http.request.header.")
+            .or_else(|| key.strip_prefix("http.response.header."))
+        else {
+            return true;
+        };
+        let captured = |headers: &[String]| {
+            headers.is_empty() || headers.iter().any(|header| header.eq_ignore_ascii_case(name))
+        };
+        if !self.options_.extra_http_headers.is_empty() {
+            return captured(&self.options_.extra_http_headers);
+        }
+        match self.remote_config_.as_ref().and_then(|remote_config| remote_config.current()) {
+            Some(config) => captured(&config.extra_http_headers),
+            None => true,
+        }
+    }
+
+    /// Whether the value of the attribute `key` is redacted, by the secrets of
+    /// the options or else the ones of the agent configuration
+    pub(crate) fn is_secret(&self, key: &str) -> bool {
+        if self.options_.secrets != Secrets::default() {
+            return self.options_.secrets.is_secret(key);
+        }
+        self.remote_config_
+            .as_ref()
+            .and_then(|remote_config| remote_config.current())
+            .and_then(|config| config.secrets.as_ref().map(|secrets| secrets.is_secret(key)))
+            .unwrap_or(false)
+    }
+
+    /// Pid of the process returned by the agent on announce
+    pub(crate) fn get_agent_pid(&self) -> Option<u32> {
+        self.remote_config_
+            .as_ref()
+            .and_then(|remote_config| remote_config.current())
+            .and_then(|config| config.pid)
+    }
+
+    /// Identity of the process as seen by the agent
+    pub fn get_process_identity(&self) -> &ProcessIdentity {
+        &self.process_identity_
+    }
+
     pub fn get_host_id(&self) -> Option<Value> {
         self.resource_.get(&"host.id".into())
     }
@@ -286,13 +666,146 @@ impl InstanaExporter {
     }
 
     pub fn new(client: Arc<dyn HttpClient>, options: InstanaExporterOptions, resource: Resource) -> Self {
+        let circuit_breaker = options
+            .circuit_breaker
+            .clone()
+            .map(|circuit_breaker| CircuitBreaker::new(circuit_breaker, options.log_level));
+        let remote_config = options
+            .remote_config
+            .then(|| RemoteConfig::new(
+                options.remote_config_interval,
+                options.log_level,
+                options.span_filter.clone(),
+            ));
+        let filtered_spans = filter_counters(&options);
         Self {
             options_: options,
             is_shutdown_: AtomicBool::new(false),
             client_: Mutex::new(Some(client)),
             resource_: resource,
+            process_identity_: ProcessIdentity::current().clone(),
+            metrics_: None,
+            circuit_breaker_: circuit_breaker,
+            remote_config_: remote_config,
+            filtered_spans_: filtered_spans,
         }
     }
 
     pub fn build_client(&mut self) {}
+
+    /// Drop the spans matching the exclude rules of `span_filter`
+    fn filter_spans(
+        &self,
+        span_filter: &SpanFilter,
+        batch: Vec<opentelemetry_sdk::trace::SpanData>,
+    ) -> Vec<opentelemetry_sdk::trace::SpanData> {
+        let mut dropped = vec![0u64; span_filter.exclude.len()];
+        let batch = batch
+            .into_iter()
+            .filter(|span| match span_filter.dropped_by(span) {
+                Some(rule) => {
+                    dropped[rule] += 1;
+                    false
+                },
+                None => true,
+            })
+            .collect();
+
+        for (rule, count) in span_filter.exclude.iter().zip(dropped) {
+            if count == 0 {
+                continue;
+            }
+            if let Ok(mut counts) = self.filtered_spans_.lock() {
+                match counts.iter_mut().find(|(name, _)| *name == rule.name) {
+                    Some((_, total)) => *total += count,
+                    None => counts.push((rule.name.clone(), count)),
+                }
+            }
+            if let Some(metrics) = &self.metrics_ {
+                metrics.spans_filtered(&rule.name, count);
+            }
+        }
+        batch
+    }
+
+    /// Serialize and send a batch to the agent
+    async fn send_batch(
+        &self,
+        client: Arc<dyn HttpClient>,
+        endpoint: &str,
+        batch: Vec<opentelemetry_sdk::trace::SpanData>,
+    ) -> Result<(), (FailureReason, OTelSdkError)> {
+        // Merge repetitive short exit spans
+        let batch = match &self.options_.span_batching {
+            Some(span_batching) => span_batching::aggregate_spans(batch, span_batching),
+            None => batch,
+        };
+
+        // Serialize batch to JSON bytes
+        let export_body = match serialize_span::serialize_batch(self, &batch) {
+            Ok(body) => body,
+            Err(e) => {
+                return Err((
+                    FailureReason::Serialization,
+                    OTelSdkError::InternalFailure(format!("Serialization error: {}", e)),
+                ))
+            },
+        };
+        if let Some(metrics) = &self.metrics_ {
+            metrics.payload_serialized(export_body.len());
+        }
+
+        // Build request
+        let mut request = match http::Request::builder()
+            .method(Method::POST)
+            .uri(endpoint)
+            .header(CONTENT_TYPE, "application/json")
+            .body(export_body)
+        {
+            Ok(req) => req,
+            Err(e) => {
+                return Err((
+                    FailureReason::Connection,
+                    OTelSdkError::InternalFailure(e.to_string()),
+                ))
+            },
+        };
+
+        // Add headers
+        for (k, v) in &self.options_.headers {
+            request.headers_mut().insert(k.clone(), v.clone());
+        }
+
+        // Send request
+        let response = client.send_bytes(request).await.map_err(|e| {
+            let reason = FailureReason::from_http_error(&e);
+            // Probe for the agent again on the next export
+            if reason == FailureReason::Connection {
+                if let Some(discovery) = &self.options_.agent_discovery {
+                    discovery.invalidate();
+                }
+                // The agent may have restarted with another configuration
+                if let Some(remote_config) = &self.remote_config_ {
+                    remote_config.reannounce();
+                }
+            }
+            (reason, OTelSdkError::InternalFailure(format!("{e:?}")))
+        })?;
+
+        // Check response
+        if !response.status().is_success() {
+            let error = format!(
+                "OpenTelemetry trace export failed. Url: {}, Status Code: {}, Response: {:?}",
+                endpoint,
+                response.status().as_u16(),
+                response.body()
+            );
+            return Err((
+                FailureReason::HttpStatus(response.status().as_u16()),
+                OTelSdkError::InternalFailure(error),
+            ));
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,116 @@
+//! Identity of the traced process as seen by the Instana agent.
+//!
+//! Within a container, the pid of the process is the one of its pid
+//! namespace, often 1, which the agent on the host cannot match to a
+//! process. Like the other Instana tracers, the host-side pid is read from
+//! `/proc/self/sched`, and the container id from the cpuset and cgroup files.
+
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Length of the container ids of docker, containerd and CRI-O
+const CONTAINER_ID_LENGTH: usize = 64;
+
+/// Pids and container of the traced process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessIdentity {
+    pub pid: u32,                     // pid in the namespace of the process
+    pub host_pid: Option<u32>,        // pid in the parent namespace, if different
+    pub container_id: Option<String>, // id of the container running the process
+    pub cpuset: Option<String>,       // content of the cpuset file
+}
+
+/// Payload announcing the process to the agent
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnouncePayload {
+    pub pid: u32,
+    #[serde(rename = "pidFromParentNS")]
+    pub pid_from_parent_ns: bool,
+    pub name: String,
+    pub args: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_set_file_content: Option<String>,
+}
+
+impl ProcessIdentity {
+    /// Identity of the current process, detected once
+    pub fn current() -> &'static ProcessIdentity {
+        static IDENTITY: OnceLock<ProcessIdentity> = OnceLock::new();
+        IDENTITY.get_or_init(|| Self::detect_from(Path::new("/proc/self"), std::process::id()))
+    }
+
+    /// Detect the identity of the process with the given namespaced pid from
+    /// the `sched`, `cpuset` and `cgroup` files in `proc_dir`
+    pub fn detect_from(proc_dir: &Path, pid: u32) -> Self {
+        let read = |name: &str| fs::read_to_string(proc_dir.join(name)).ok();
+
+        let host_pid = read("sched")
+            .and_then(|sched| parse_sched_pid(&sched))
+            .filter(|sched_pid| *sched_pid != pid);
+
+        let cpuset = read("cpuset").map(|cpuset| cpuset.trim().to_string());
+        let container_id = cpuset
+            .as_deref()
+            .and_then(parse_container_id)
+            .or_else(|| read("cgroup").and_then(|cgroup| parse_container_id(&cgroup)));
+
+        ProcessIdentity {
+            pid,
+            host_pid,
+            container_id,
+            cpuset,
+        }
+    }
+
+    /// The pid the agent knows the process by
+    pub fn agent_pid(&self) -> u32 {
+        self.host_pid.unwrap_or(self.pid)
+    }
+
+    pub fn in_container(&self) -> bool {
+        self.host_pid.is_some() || self.container_id.is_some()
+    }
+
+    /// Payload announcing this process to the agent
+    pub fn announce_payload(&self) -> AnnouncePayload {
+        let name = std::env::current_exe()
+            .map(|exe| exe.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        AnnouncePayload {
+            pid: self.agent_pid(),
+            pid_from_parent_ns: self.host_pid.is_some(),
+            name,
+            args: std::env::args().skip(1).collect(),
+            cpu_set_file_content: self.cpuset.clone(),
+        }
+    }
+}
+
+/// Parse the pid out of the first line of `/proc/<pid>/sched`, e.g.
+/// `server (12345, #threads: 4)`
+pub fn parse_sched_pid(sched: &str) -> Option<u32> {
+    let first_line = sched.lines().next()?;
+    let (_, rest) = first_line.rsplit_once('(')?;
+    let (pid, _) = rest.split_once(',')?;
+    pid.trim().parse().ok()
+}
+
+/// Find a container id in the paths of a cpuset or cgroup file, for both the
+/// cgroup v1 (`4:cpuset:/docker/<id>`) and v2 (`0::/system.slice/docker-<id>.scope`) layouts
+pub fn parse_container_id(content: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        // cgroup lines are `<hierarchy>:<controllers>:<path>`, cpuset is only a path
+        let path = line.rsplit(':').next()?;
+        path.split('/').rev().find_map(|segment| {
+            let segment = segment.trim().trim_end_matches(".scope");
+            let id = segment.rsplit('-').next()?;
+            let is_container_id =
+                id.len() == CONTAINER_ID_LENGTH && id.chars().all(|c| c.is_ascii_hexdigit());
+            is_container_id.then(|| id.to_string())
+        })
+    })
+}
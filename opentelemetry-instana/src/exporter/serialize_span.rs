@@ -1,17 +1,28 @@
 use anyhow::{anyhow, Result};
 use opentelemetry::trace::{SpanId, SpanKind, Status, TraceId};
-use opentelemetry::Value;
+use opentelemetry::{otel_warn, Value};
 use opentelemetry_sdk::trace::SpanData;
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::SystemTime;
 
 use crate::InstanaExporter;
+use crate::exporter::config::{LogLevel, REDACTED};
 use crate::exporter::instana_span::{
-    InstanaCustom, InstanaEvent, InstanaLink, InstanaOtel, InstanaSdk, InstanaSpan,
-    InstanaSpanData, InstanaSpanFrom, InstanaTags,
+    InstanaCustom, InstanaEvent, InstanaKind, InstanaLink, InstanaOtel, InstanaSdk, InstanaSpan,
+    InstanaSpanBatch, InstanaSpanData, InstanaSpanFrom, InstanaSpanPeer, InstanaStackFrame,
+    InstanaTags,
 };
+use crate::exporter::span_batching::{INTERNAL_TAG_BATCH_DURATION, INTERNAL_TAG_BATCH_SIZE};
 use crate::exporter::span_data::GET;
+use crate::stack_trace::INTERNAL_TAG_STACK;
+
+/// Internal tags exported in dedicated fields rather than as attributes
+pub(crate) const EXPORTED_INTERNAL_TAGS: [&str; 3] = [
+    INTERNAL_TAG_STACK,
+    INTERNAL_TAG_BATCH_SIZE,
+    INTERNAL_TAG_BATCH_DURATION,
+];
 
 /// Convert an OpenTelemetry SpanData to an InstanaSpan
 pub fn convert_to_instana_span(exporter: &InstanaExporter, span: &SpanData) -> Result<InstanaSpan> {
@@ -20,11 +31,8 @@ pub fn convert_to_instana_span(exporter: &InstanaExporter, span: &SpanData) -> R
     let span_id = format!("{:016x}", span.span_context.span_id());
 
     // Determine span kind value (1=entry, 2=exit, 3=intermediate)
-    let kind_value = match span.span_kind {
-        SpanKind::Server | SpanKind::Producer => 1,
-        SpanKind::Internal => 3,
-        SpanKind::Client | SpanKind::Consumer => 2,
-    };
+    let kind = InstanaKind::of(&span.span_kind);
+    let kind_value = kind.code();
 
     // Get parent span ID if available
     let parent_id = if span.parent_span_id != SpanId::INVALID {
@@ -34,15 +42,10 @@ pub fn convert_to_instana_span(exporter: &InstanaExporter, span: &SpanData) -> R
     };
 
     // Get long trace ID for entry spans
-    let long_trace_id = match span.span_kind {
-        SpanKind::Server | SpanKind::Producer => {
-            if tid != TraceId::INVALID {
-                Some(trace_id.clone())
-            } else {
-                None
-            }
-        },
-        _ => None,
+    let long_trace_id = if kind == InstanaKind::Entry && tid != TraceId::INVALID {
+        Some(trace_id.clone())
+    } else {
+        None
     };
 
     // Check synthetic flag
@@ -85,6 +88,38 @@ pub fn convert_to_instana_span(exporter: &InstanaExporter, span: &SpanData) -> R
         _ => None,
     };
 
+    // Process stack trace captured by the StackTraceSpanProcessor
+    let stack = match span.get_attribute(INTERNAL_TAG_STACK) {
+        Ok(Value::String(value)) => {
+            match serde_json::from_str::<Vec<InstanaStackFrame>>(value.as_str()) {
+                Ok(frames) => Some(frames),
+                Err(e) => {
+                    if exporter.options_.log_level.enabled(LogLevel::Warn) {
+                        otel_warn!(
+                            name: "InstanaExporter.InvalidStackTrace",
+                            span_id = span_id.as_str(),
+                            error = e.to_string()
+                        );
+                    }
+                    None
+                },
+            }
+        },
+        _ => None,
+    };
+
+    // Process batch information of merged exit spans
+    let batch = match (
+        span.get_attribute(INTERNAL_TAG_BATCH_SIZE),
+        span.get_attribute(INTERNAL_TAG_BATCH_DURATION),
+    ) {
+        (Ok(Value::I64(size)), Ok(Value::I64(duration))) => Some(InstanaSpanBatch {
+            size,
+            duration: duration.max(0) as u64,
+        }),
+        _ => None,
+    };
+
     // Build the data section
     let data = build_data_section(exporter, span)?;
 
@@ -106,6 +141,8 @@ pub fn convert_to_instana_span(exporter: &InstanaExporter, span: &SpanData) -> R
         correlation_id,
         correlation_type,
         trace_parent,
+        stack,
+        batch,
         data,
         from,
     };
@@ -159,14 +196,26 @@ fn extract_correlation_data(span: &SpanData) -> Result<(Option<String>, Option<S
 /// Build the data section of the InstanaSpan
 fn build_data_section(exporter: &InstanaExporter, span: &SpanData) -> Result<InstanaSpanData> {
     // Convert attributes to HashMap
-    let attributes = if !span.attributes.is_empty() {
+    let attributes = {
         let mut attrs = HashMap::new();
         for attr in span.get_attributes() {
-            attrs.insert(attr.key.to_string(), convert_value_to_json(&attr.value));
+            if EXPORTED_INTERNAL_TAGS.contains(&attr.key.as_str())
+                || !exporter.captures_header(attr.key.as_str())
+            {
+                continue;
+            }
+            let value = if exporter.is_secret(attr.key.as_str()) {
+                serde_json::Value::String(REDACTED.to_string())
+            } else {
+                convert_value_to_json(&attr.value)
+            };
+            attrs.insert(attr.key.to_string(), value);
+        }
+        if attrs.is_empty() {
+            None
+        } else {
+            Some(attrs)
         }
-        Some(attrs)
-    } else {
-        None
     };
 
     // Convert resource attributes
@@ -188,7 +237,8 @@ fn build_data_section(exporter: &InstanaExporter, span: &SpanData) -> Result<Ins
     // Convert events
     let events = if !span.events.events.is_empty() {
         let mut events_map = HashMap::new();
-        for event in span.get_events() {
+        let keys = event_keys(span.events.events.iter().map(|event| event.name.as_ref()));
+        for (event, key) in span.get_events().into_iter().zip(keys) {
             let mut attrs = HashMap::new();
             for attr in &event.attributes {
                 attrs.insert(attr.key.to_string(), convert_value_to_json(&attr.value));
@@ -201,7 +251,7 @@ fn build_data_section(exporter: &InstanaExporter, span: &SpanData) -> Result<Ins
                 .to_string();
 
             events_map.insert(
-                event.name.to_string(),
+                key,
                 InstanaEvent {
                     value: attrs,
                     timestamp,
@@ -294,25 +344,99 @@ fn build_data_section(exporter: &InstanaExporter, span: &SpanData) -> Result<Ins
     };
 
     // Get service name
-    let service_name = match exporter.get_service_name() {
-        Some(Value::String(name)) => Some(name.to_string()),
-        _ => None,
-    };
+    let service_name = resolve_service_name(exporter, span);
+
+    // Build peer section for exit spans
+    let peer = build_peer_section(span);
 
     // Build data section
     let data = InstanaSpanData {
         sdk,
         service: service_name,
+        peer,
     };
 
     Ok(data)
 }
 
+/// Resolve the service name of the span.
+///
+/// A `service.name` span attribute takes precedence over a `service.name`
+/// instrumentation scope attribute, which takes precedence over the service
+/// of the exporter's options and the `service.name` of the exporter's resource.
+fn resolve_service_name(exporter: &InstanaExporter, span: &SpanData) -> Option<String> {
+    if let Ok(Value::String(name)) = span.get_attribute("service.name") {
+        return Some(name.to_string());
+    }
+
+    let scope_service_name = span
+        .instrumentation_scope
+        .attributes()
+        .find(|kv| kv.key.as_str() == "service.name")
+        .and_then(|kv| match &kv.value {
+            Value::String(name) => Some(name.to_string()),
+            _ => None,
+        });
+    if scope_service_name.is_some() {
+        return scope_service_name;
+    }
+
+    if !exporter.options_.service.is_empty() {
+        return Some(exporter.options_.service.clone());
+    }
+
+    match exporter.get_service_name() {
+        Some(Value::String(name)) => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// Build the peer section of an exit span from `peer.service`,
+/// `server.address` and `server.port`
+fn build_peer_section(span: &SpanData) -> Option<InstanaSpanPeer> {
+    if !matches!(span.span_kind, SpanKind::Client | SpanKind::Consumer) {
+        return None;
+    }
+
+    let service = match span.get_attribute("peer.service") {
+        Ok(Value::String(name)) => Some(name.to_string()),
+        _ => None,
+    };
+
+    let hostname = match span.get_attribute("server.address") {
+        Ok(Value::String(address)) => Some(address.to_string()),
+        _ => None,
+    };
+
+    let port = match span.get_attribute("server.port") {
+        Ok(Value::I64(port)) => Some(port),
+        _ => None,
+    };
+
+    if service.is_none() && hostname.is_none() && port.is_none() {
+        return None;
+    }
+
+    Some(InstanaSpanPeer {
+        service,
+        hostname,
+        port,
+    })
+}
+
 /// Build the from section of the InstanaSpan
+///
+/// Within a container, the host-side pid takes precedence over the
+/// namespaced `process.pid` of the resource.
 fn build_from_section(exporter: &InstanaExporter) -> InstanaSpanFrom {
-    let process_id = match exporter.get_process_pid() {
-        Some(Value::I64(pid)) => Some(pid),
-        _ => None,
+    // The pid the agent knows the process by, from the announce or else detected
+    let process_id = match exporter.get_agent_pid() {
+        Some(agent_pid) => Some(agent_pid as i64),
+        None => match (exporter.get_process_identity().host_pid, exporter.get_process_pid()) {
+            (Some(host_pid), _) => Some(host_pid as i64),
+            (None, Some(Value::I64(pid))) => Some(pid),
+            _ => None,
+        },
     };
 
     let host_id = match exporter.get_host_id() {
@@ -326,8 +450,26 @@ fn build_from_section(exporter: &InstanaExporter) -> InstanaSpanFrom {
     }
 }
 
+/// Key of each event in the `events` map. Repeated names get `#<n>` appended,
+/// so that events with the same name do not overwrite each other.
+pub(crate) fn event_keys<'a>(names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut used = HashSet::new();
+    names
+        .map(|name| {
+            let mut key = name.to_string();
+            let mut n = 1;
+            while used.contains(&key) {
+                key = format!("{name}#{n}");
+                n += 1;
+            }
+            used.insert(key.clone());
+            key
+        })
+        .collect()
+}
+
 /// Convert OpenTelemetry Value to serde_json::Value
-fn convert_value_to_json(value: &Value) -> serde_json::Value {
+pub(crate) fn convert_value_to_json(value: &Value) -> serde_json::Value {
     match value {
         Value::Bool(v) => json!(v),
         Value::I64(v) => json!(v),
@@ -344,11 +486,7 @@ fn convert_value_to_json(value: &Value) -> serde_json::Value {
 
 /// Convert SpanKind to string representation
 fn convert_span_kind_to_string(span_kind: &SpanKind) -> String {
-    match span_kind {
-        SpanKind::Producer | SpanKind::Server => "entry".to_string(),
-        SpanKind::Internal => "intermediate".to_string(),
-        SpanKind::Client | SpanKind::Consumer => "exit".to_string(),
-    }
+    InstanaKind::of(span_kind).name().to_string()
 }
 
 /// Serialize a batch of spans to JSON
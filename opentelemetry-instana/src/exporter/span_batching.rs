@@ -0,0 +1,132 @@
+use opentelemetry::trace::{SpanId, SpanKind, Status, TraceId};
+use opentelemetry::{KeyValue, Value};
+use opentelemetry_sdk::trace::SpanData;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use crate::exporter::span_data::GET;
+
+/// Span attribute carrying the number of spans merged into a batched span
+pub(crate) const INTERNAL_TAG_BATCH_SIZE: &str = "INTERNAL_TAG_BATCH_SIZE";
+/// Span attribute carrying the summed duration in milliseconds of a batched span
+pub(crate) const INTERNAL_TAG_BATCH_DURATION: &str = "INTERNAL_TAG_BATCH_DURATION";
+
+/// Attributes identifying the target of an exit span
+const TARGET_ATTRIBUTES: [&str; 4] = ["peer.service", "server.address", "server.port", "db.system"];
+
+const DEFAULT_MAX_BATCHED_SPAN_DURATION: Duration = Duration::from_millis(10);
+
+/// Options of the span batching stage of the exporter
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpanBatchingOptions {
+    /// Exit spans lasting at most this long can be merged into a batched span
+    pub max_duration: Duration,
+}
+
+impl Default for SpanBatchingOptions {
+    fn default() -> Self {
+        SpanBatchingOptions {
+            max_duration: DEFAULT_MAX_BATCHED_SPAN_DURATION,
+        }
+    }
+}
+
+#[derive(PartialEq)]
+struct BatchKey {
+    trace_id: TraceId,
+    parent_span_id: SpanId,
+    name: String,
+    target: Vec<Option<Value>>,
+}
+
+struct Batch {
+    span: SpanData,
+    key: Option<BatchKey>,
+    size: i64,
+    duration: Duration,
+}
+
+/// Merge consecutive short exit spans with the same parent, name and target
+/// into one batched span.
+///
+/// Spans are ordered by start time. A span is merged into the previous sibling
+/// under the same parent when both are batchable and share name and target.
+/// Erroneous spans and spans with children in the batch are never merged.
+pub fn aggregate_spans(batch: Vec<SpanData>, options: &SpanBatchingOptions) -> Vec<SpanData> {
+    let parents: HashSet<SpanId> = batch.iter().map(|span| span.parent_span_id).collect();
+
+    let mut spans = batch;
+    spans.sort_by_key(|span| span.start_time);
+
+    let mut batches: Vec<Batch> = Vec::with_capacity(spans.len());
+    let mut last_sibling: HashMap<(TraceId, SpanId), usize> = HashMap::new();
+
+    for span in spans {
+        let duration = span
+            .end_time
+            .duration_since(span.start_time)
+            .unwrap_or_default();
+        let key = batch_key(&span, duration, &parents, options);
+        let sibling = (span.span_context.trace_id(), span.parent_span_id);
+
+        if let (Some(key), Some(&index)) = (&key, last_sibling.get(&sibling)) {
+            let previous = &mut batches[index];
+            if previous.key.as_ref() == Some(key) {
+                previous.size += 1;
+                previous.duration += duration;
+                continue;
+            }
+        }
+
+        last_sibling.insert(sibling, batches.len());
+        batches.push(Batch {
+            span,
+            key,
+            size: 1,
+            duration,
+        });
+    }
+
+    batches
+        .into_iter()
+        .map(|batch| {
+            let mut span = batch.span;
+            if batch.size > 1 {
+                span.attributes
+                    .push(KeyValue::new(INTERNAL_TAG_BATCH_SIZE, batch.size));
+                span.attributes.push(KeyValue::new(
+                    INTERNAL_TAG_BATCH_DURATION,
+                    batch.duration.as_millis() as i64,
+                ));
+            }
+            span
+        })
+        .collect()
+}
+
+/// Build the key of a batchable span, `None` if the span cannot be batched
+fn batch_key(
+    span: &SpanData,
+    duration: Duration,
+    parents: &HashSet<SpanId>,
+    options: &SpanBatchingOptions,
+) -> Option<BatchKey> {
+    let batchable = matches!(span.span_kind, SpanKind::Client | SpanKind::Consumer)
+        && span.parent_span_id != SpanId::INVALID
+        && !matches!(span.status, Status::Error { .. })
+        && duration <= options.max_duration
+        && !parents.contains(&span.span_context.span_id());
+    if !batchable {
+        return None;
+    }
+
+    Some(BatchKey {
+        trace_id: span.span_context.trace_id(),
+        parent_span_id: span.parent_span_id,
+        name: span.name.to_string(),
+        target: TARGET_ATTRIBUTES
+            .iter()
+            .map(|attribute| span.get_attribute(*attribute).ok())
+            .collect(),
+    })
+}
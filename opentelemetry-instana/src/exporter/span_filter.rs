@@ -0,0 +1,302 @@
+//! Filtering of spans before they are sent to the agent.
+//!
+//! Rules use the `tracing.filter` format of the Instana configuration. A span
+//! is dropped when it matches an `exclude` rule and no `include` rule. A rule
+//! matches when all its conditions match, and a condition matches when one of
+//! its values matches.
+//!
+//! ```yaml
+//! exclude:
+//!   - name: Health checks
+//!     attributes:
+//!       - key: url.path
+//!         values: ["/health", "/metrics"]
+//!       - key: kind
+//!         values: [entry]
+//! ```
+
+use opentelemetry::trace::SpanKind;
+use opentelemetry_sdk::trace::SpanData;
+use regex::Regex;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+use super::agent_config::SpanCategory;
+use super::instana_span::InstanaKind;
+
+/// Condition key matching the span name
+pub const KEY_NAME: &str = "name";
+/// Condition key matching the span kind, `entry`, `exit` or `intermediate`
+/// as well as the OpenTelemetry kinds, e.g. `server`
+pub const KEY_KIND: &str = "kind";
+/// Condition key matching the category of the span, e.g. `databases`
+pub const KEY_CATEGORY: &str = "category";
+
+/// How the values of a condition are matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchType {
+    #[default]
+    #[serde(alias = "equals")]
+    Strict,
+    Contains,
+    StartsWith,
+    EndsWith,
+    /// Regular expressions, matching anywhere unless anchored
+    Regex,
+}
+
+/// Condition on the name, kind, category or an attribute of a span
+#[derive(Debug, Clone)]
+pub struct FilterCondition {
+    pub key: String,
+    pub values: Vec<String>,
+    pub match_type: MatchType,
+    regexes: Vec<Regex>, // compiled values, for the regex match type
+}
+
+impl PartialEq for FilterCondition {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.values == other.values && self.match_type == other.match_type
+    }
+}
+
+impl FilterCondition {
+    pub fn new(key: &str, values: Vec<String>, match_type: MatchType) -> Result<Self, regex::Error> {
+        let regexes = match match_type {
+            MatchType::Regex => values
+                .iter()
+                .map(|pattern| Regex::new(pattern))
+                .collect::<Result<_, _>>()?,
+            _ => Vec::new(),
+        };
+        Ok(FilterCondition {
+            key: key.to_string(),
+            values,
+            match_type,
+            regexes,
+        })
+    }
+
+    fn matches(&self, span: &SpanData) -> bool {
+        let candidates: Vec<Cow<'_, str>> = match self.key.as_str() {
+            KEY_NAME => vec![Cow::Borrowed(span.name.as_ref())],
+            KEY_KIND => kind_names(&span.span_kind)
+                .iter()
+                .map(|kind| Cow::Borrowed(*kind))
+                .collect(),
+            KEY_CATEGORY => SpanCategory::of(span)
+                .map(|category| Cow::Borrowed(category.name()))
+                .into_iter()
+                .collect(),
+            key => span
+                .attributes
+                .iter()
+                .filter(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.as_str())
+                .collect(),
+        };
+
+        candidates.iter().any(|candidate| self.matches_value(candidate))
+    }
+
+    fn matches_value(&self, candidate: &str) -> bool {
+        match self.match_type {
+            MatchType::Strict => self.values.iter().any(|value| candidate == value),
+            MatchType::Contains => self.values.iter().any(|value| candidate.contains(value.as_str())),
+            MatchType::StartsWith => self.values.iter().any(|value| candidate.starts_with(value.as_str())),
+            MatchType::EndsWith => self.values.iter().any(|value| candidate.ends_with(value.as_str())),
+            MatchType::Regex => self.regexes.iter().any(|regex| regex.is_match(candidate)),
+        }
+    }
+}
+
+/// Names a span kind is matched by: the Instana kind it is exported as and
+/// the OpenTelemetry kind
+fn kind_names(kind: &SpanKind) -> [&'static str; 2] {
+    let otel_name = match kind {
+        SpanKind::Server => "server",
+        SpanKind::Consumer => "consumer",
+        SpanKind::Client => "client",
+        SpanKind::Producer => "producer",
+        SpanKind::Internal => "internal",
+    };
+    [InstanaKind::of(kind).name(), otel_name]
+}
+
+/// Named rule, matching spans matching all its conditions
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterRule {
+    pub name: String,
+    pub conditions: Vec<FilterCondition>,
+}
+
+impl FilterRule {
+    pub fn new(name: &str, conditions: Vec<FilterCondition>) -> Self {
+        FilterRule {
+            name: name.to_string(),
+            conditions,
+        }
+    }
+
+    pub fn matches(&self, span: &SpanData) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(span))
+    }
+}
+
+/// Rules deciding which spans are dropped before they are sent to the agent
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SpanFilter {
+    /// Spans matching an include rule are never dropped
+    pub include: Vec<FilterRule>,
+    /// Spans matching an exclude rule are dropped
+    pub exclude: Vec<FilterRule>,
+}
+
+/// Errors that can occur while reading span filter rules.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum SpanFilterError {
+    /// The rules are not valid YAML in the `tracing.filter` format.
+    #[error("invalid tracing filter: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    /// The rules returned by the agent are not in the `tracing.filter` format.
+    #[error("invalid tracing filter of the agent: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// A value of a regex condition is not a valid regular expression.
+    #[error("invalid regex in tracing filter rule {rule:?}: {source}")]
+    Regex {
+        rule: String,
+        source: regex::Error,
+    },
+
+    /// The configuration file cannot be read.
+    #[error("cannot read {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+}
+
+#[derive(Deserialize, Default)]
+struct RawFilter {
+    #[serde(default)]
+    deactivate: bool,
+    #[serde(default)]
+    include: Vec<RawRule>,
+    #[serde(default)]
+    exclude: Vec<RawRule>,
+}
+
+#[derive(Deserialize)]
+struct RawRule {
+    name: String,
+    #[serde(default)]
+    attributes: Vec<RawCondition>,
+}
+
+#[derive(Deserialize)]
+struct RawCondition {
+    key: String,
+    values: Vec<String>,
+    #[serde(default)]
+    match_type: MatchType,
+}
+
+#[derive(Deserialize)]
+struct RawConfigFile {
+    tracing: Option<RawTracing>,
+}
+
+#[derive(Deserialize)]
+struct RawTracing {
+    filter: Option<RawFilter>,
+}
+
+impl SpanFilter {
+    /// Parse rules in the `tracing.filter` format, e.g. the value of `INSTANA_TRACING_FILTER`
+    pub fn from_yaml(yaml: &str) -> Result<Self, SpanFilterError> {
+        let filter: RawFilter = serde_yaml::from_str(yaml)?;
+        Self::from_raw(filter)
+    }
+
+    /// Parse the `tracing.filter` section of the configuration the agent
+    /// returns on announce
+    pub fn from_json(value: serde_json::Value) -> Result<Self, SpanFilterError> {
+        let filter: RawFilter = serde_json::from_value(value)?;
+        Self::from_raw(filter)
+    }
+
+    /// Read the `tracing.filter` section of an Instana configuration file,
+    /// `None` if the file has none
+    pub fn from_config_file(path: impl AsRef<Path>) -> Result<Option<Self>, SpanFilterError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|source| SpanFilterError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let config: Option<RawConfigFile> = serde_yaml::from_str(&content)?;
+        config
+            .and_then(|config| config.tracing)
+            .and_then(|tracing| tracing.filter)
+            .map(Self::from_raw)
+            .transpose()
+    }
+
+    fn from_raw(filter: RawFilter) -> Result<Self, SpanFilterError> {
+        if filter.deactivate {
+            return Ok(Self::default());
+        }
+        let rules = |rules: Vec<RawRule>| {
+            rules
+                .into_iter()
+                .map(|rule| {
+                    let conditions = rule
+                        .attributes
+                        .into_iter()
+                        .map(|condition| {
+                            FilterCondition::new(&condition.key, condition.values, condition.match_type)
+                        })
+                        .collect::<Result<_, _>>()
+                        .map_err(|source| SpanFilterError::Regex {
+                            rule: rule.name.clone(),
+                            source,
+                        })?;
+                    Ok(FilterRule::new(&rule.name, conditions))
+                })
+                .collect::<Result<Vec<_>, SpanFilterError>>()
+        };
+
+        Ok(SpanFilter {
+            include: rules(filter.include)?,
+            exclude: rules(filter.exclude)?,
+        })
+    }
+
+    /// Rules of both filters, the ones of `self` first
+    pub fn merged(&self, other: &SpanFilter) -> SpanFilter {
+        SpanFilter {
+            include: self.include.iter().chain(&other.include).cloned().collect(),
+            exclude: self.exclude.iter().chain(&other.exclude).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exclude.is_empty()
+    }
+
+    /// Index of the exclude rule dropping the span, `None` if the span is kept
+    pub fn dropped_by(&self, span: &SpanData) -> Option<usize> {
+        if self.include.iter().any(|rule| rule.matches(span)) {
+            return None;
+        }
+        self.exclude.iter().position(|rule| rule.matches(span))
+    }
+}
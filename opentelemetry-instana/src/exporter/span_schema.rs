@@ -0,0 +1,375 @@
+//! Validation of rawtrace payloads against the schema the agent expects.
+//!
+//! The validator checks the JSON rather than [`InstanaSpan`], so that it also
+//! catches fields the structs would silently drop or default, such as unknown
+//! keys, ids of the wrong length or a `type` contradicting the kind.
+//!
+//! [`InstanaSpan`]: super::instana_span::InstanaSpan
+
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Fields of an Instana span, besides `data` and `f`
+const SPAN_FIELDS: [&str; 15] = [
+    "p", "t", "s", "n", "k", "ts", "d", "sy", "lt", "ec", "crid", "crtp", "tp", "stack", "b",
+];
+
+/// A violation of the schema, with the JSON path of the offending value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Validate a rawtrace payload, returning all violations found
+pub fn validate_payload(payload: &[u8]) -> Result<(), Vec<SchemaViolation>> {
+    match serde_json::from_slice::<Value>(payload) {
+        Ok(value) => validate_value(&value),
+        Err(e) => Err(vec![SchemaViolation {
+            path: "$".to_string(),
+            message: format!("invalid JSON: {e}"),
+        }]),
+    }
+}
+
+/// Validate a rawtrace payload parsed as JSON
+pub fn validate_value(payload: &Value) -> Result<(), Vec<SchemaViolation>> {
+    let mut validator = Validator::default();
+    match payload.as_array() {
+        Some(spans) => {
+            for (i, span) in spans.iter().enumerate() {
+                validator.span(&format!("$[{i}]"), span);
+            }
+        },
+        None => validator.violation("$", "expected a list of spans"),
+    }
+
+    if validator.violations.is_empty() {
+        Ok(())
+    } else {
+        Err(validator.violations)
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    violations: Vec<SchemaViolation>,
+}
+
+impl Validator {
+    fn violation(&mut self, path: &str, message: impl Into<String>) {
+        self.violations.push(SchemaViolation {
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn object<'a>(&mut self, path: &str, value: &'a Value) -> Option<&'a Map<String, Value>> {
+        let object = value.as_object();
+        if object.is_none() {
+            self.violation(path, "expected an object");
+        }
+        object
+    }
+
+    /// Check that a required field is present, returning it
+    fn required<'a>(
+        &mut self,
+        path: &str,
+        object: &'a Map<String, Value>,
+        key: &str,
+    ) -> Option<&'a Value> {
+        let value = object.get(key);
+        if value.is_none() {
+            self.violation(&format!("{path}.{key}"), "missing");
+        }
+        value
+    }
+
+    fn hex_id(&mut self, path: &str, value: &Value, len: usize) {
+        let valid = value.as_str().is_some_and(|id| {
+            id.len() == len && id.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+        });
+        if !valid {
+            self.violation(path, format!("expected {len} lowercase hex digits, got {value}"));
+        }
+    }
+
+    fn string(&mut self, path: &str, value: &Value) {
+        if !value.is_string() {
+            self.violation(path, format!("expected a string, got {value}"));
+        }
+    }
+
+    fn unsigned(&mut self, path: &str, value: &Value) {
+        if !value.is_u64() {
+            self.violation(path, format!("expected a non-negative integer, got {value}"));
+        }
+    }
+
+    fn integer(&mut self, path: &str, value: &Value) {
+        if !value.is_i64() && !value.is_u64() {
+            self.violation(path, format!("expected an integer, got {value}"));
+        }
+    }
+
+    fn boolean(&mut self, path: &str, value: &Value) {
+        if !value.is_boolean() {
+            self.violation(path, format!("expected a boolean, got {value}"));
+        }
+    }
+
+    /// Check an optional field with `check` if present
+    fn optional(
+        &mut self,
+        path: &str,
+        object: &Map<String, Value>,
+        key: &str,
+        check: fn(&mut Self, &str, &Value),
+    ) {
+        if let Some(value) = object.get(key) {
+            check(self, &format!("{path}.{key}"), value);
+        }
+    }
+
+    fn span(&mut self, path: &str, span: &Value) {
+        let Some(span) = self.object(path, span) else {
+            return;
+        };
+
+        for key in span.keys() {
+            if !SPAN_FIELDS.contains(&key.as_str()) && key != "data" && key != "f" {
+                self.violation(&format!("{path}.{key}"), "unknown field");
+            }
+        }
+
+        if let Some(t) = self.required(path, span, "t") {
+            self.hex_id(&format!("{path}.t"), t, 16);
+        }
+        if let Some(s) = self.required(path, span, "s") {
+            self.hex_id(&format!("{path}.s"), s, 16);
+        }
+        if let Some(p) = span.get("p") {
+            self.hex_id(&format!("{path}.p"), p, 16);
+        }
+        if let Some(n) = self.required(path, span, "n") {
+            self.string(&format!("{path}.n"), n);
+        }
+        let kind = match self.required(path, span, "k") {
+            Some(k) => {
+                let kind = k.as_i64().filter(|k| (1..=3).contains(k));
+                if kind.is_none() {
+                    self.violation(
+                        &format!("{path}.k"),
+                        format!("expected 1 (entry), 2 (exit) or 3 (intermediate), got {k}"),
+                    );
+                }
+                kind
+            },
+            None => None,
+        };
+        if let Some(ts) = self.required(path, span, "ts") {
+            self.unsigned(&format!("{path}.ts"), ts);
+        }
+        if let Some(d) = self.required(path, span, "d") {
+            self.unsigned(&format!("{path}.d"), d);
+        }
+        if let Some(sy) = self.required(path, span, "sy") {
+            self.boolean(&format!("{path}.sy"), sy);
+        }
+
+        if let Some(lt) = span.get("lt") {
+            self.hex_id(&format!("{path}.lt"), lt, 32);
+            if kind != Some(1) {
+                self.violation(&format!("{path}.lt"), "only allowed on entry spans");
+            }
+            let lower = lt.as_str().and_then(|lt| lt.get(16..));
+            if lower.is_some() && lower != span.get("t").and_then(Value::as_str) {
+                self.violation(&format!("{path}.lt"), "lower 64 bits differ from t");
+            }
+        }
+        self.optional(path, span, "ec", Self::unsigned);
+        self.optional(path, span, "crid", Self::string);
+        self.optional(path, span, "crtp", Self::string);
+        self.optional(path, span, "tp", Self::boolean);
+        if let Some(stack) = span.get("stack") {
+            self.stack(&format!("{path}.stack"), stack);
+        }
+        if let Some(batch) = span.get("b") {
+            self.batch(&format!("{path}.b"), batch);
+        }
+
+        if let Some(data) = self.required(path, span, "data") {
+            self.data(&format!("{path}.data"), data, kind);
+        }
+        if let Some(from) = self.required(path, span, "f") {
+            if let Some(from) = self.object(&format!("{path}.f"), from) {
+                self.optional(&format!("{path}.f"), from, "e", Self::integer);
+                self.optional(&format!("{path}.f"), from, "h", Self::string);
+            }
+        }
+    }
+
+    fn stack(&mut self, path: &str, stack: &Value) {
+        let Some(frames) = stack.as_array() else {
+            return self.violation(path, "expected a list of frames");
+        };
+        for (i, frame) in frames.iter().enumerate() {
+            let path = format!("{path}[{i}]");
+            if let Some(frame) = self.object(&path, frame) {
+                if let Some(method) = self.required(&path, frame, "m") {
+                    self.string(&format!("{path}.m"), method);
+                }
+                self.optional(&path, frame, "c", Self::string);
+                self.optional(&path, frame, "n", Self::unsigned);
+            }
+        }
+    }
+
+    fn batch(&mut self, path: &str, batch: &Value) {
+        if let Some(batch) = self.object(path, batch) {
+            if let Some(size) = self.required(path, batch, "s") {
+                if size.as_i64().is_none_or(|size| size < 1) {
+                    self.violation(&format!("{path}.s"), "expected a positive number of spans");
+                }
+            }
+            if let Some(duration) = self.required(path, batch, "d") {
+                self.unsigned(&format!("{path}.d"), duration);
+            }
+        }
+    }
+
+    fn data(&mut self, path: &str, data: &Value, kind: Option<i64>) {
+        let Some(data) = self.object(path, data) else {
+            return;
+        };
+        self.optional(path, data, "service", Self::string);
+        if let Some(peer) = data.get("peer") {
+            let peer_path = format!("{path}.peer");
+            if let Some(peer) = self.object(&peer_path, peer) {
+                self.optional(&peer_path, peer, "service", Self::string);
+                self.optional(&peer_path, peer, "hostname", Self::string);
+                self.optional(&peer_path, peer, "port", Self::integer);
+            }
+        }
+
+        let Some(sdk) = self.required(path, data, "sdk") else {
+            return;
+        };
+        let path = format!("{path}.sdk");
+        let Some(sdk) = self.object(&path, sdk) else {
+            return;
+        };
+        if let Some(name) = self.required(&path, sdk, "name") {
+            self.string(&format!("{path}.name"), name);
+        }
+        if let Some(span_type) = self.required(&path, sdk, "type") {
+            let expected = match kind {
+                Some(1) => Some("entry"),
+                Some(2) => Some("exit"),
+                Some(3) => Some("intermediate"),
+                _ => None,
+            };
+            if let Some(expected) = expected {
+                if span_type.as_str() != Some(expected) {
+                    self.violation(
+                        &format!("{path}.type"),
+                        format!("expected \"{expected}\" for the kind, got {span_type}"),
+                    );
+                }
+            }
+        }
+
+        let tags = self
+            .required(&path, sdk, "custom")
+            .and_then(|custom| self.object(&format!("{path}.custom"), custom))
+            .and_then(|custom| self.required(&format!("{path}.custom"), custom, "tags"));
+        if let Some(tags) = tags {
+            self.tags(&format!("{path}.custom.tags"), tags);
+        }
+    }
+
+    fn tags(&mut self, path: &str, tags: &Value) {
+        let Some(tags) = self.object(path, tags) else {
+            return;
+        };
+
+        if let Some(attributes) = tags.get("attributes") {
+            self.object(&format!("{path}.attributes"), attributes);
+        }
+        if let Some(resource) = tags.get("resource") {
+            if let Some(resource) = self.object(&format!("{path}.resource"), resource) {
+                for (key, value) in resource {
+                    self.string(&format!("{path}.resource.{key}"), value);
+                }
+            }
+        }
+        if let Some(events) = tags.get("events") {
+            if let Some(events) = self.object(&format!("{path}.events"), events) {
+                for (key, event) in events {
+                    let event_path = format!("{path}.events.{key}");
+                    if let Some(event) = self.object(&event_path, event) {
+                        if let Some(value) = self.required(&event_path, event, "value") {
+                            self.object(&format!("{event_path}.value"), value);
+                        }
+                        let timestamp = self.required(&event_path, event, "timestamp");
+                        let is_millis = timestamp.and_then(Value::as_str).is_some_and(|ts| {
+                            !ts.is_empty() && ts.chars().all(|c| c.is_ascii_digit())
+                        });
+                        if timestamp.is_some() && !is_millis {
+                            self.violation(
+                                &format!("{event_path}.timestamp"),
+                                "expected milliseconds as a string",
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(links) = tags.get("links") {
+            match links.as_array() {
+                Some(links) => {
+                    for (i, link) in links.iter().enumerate() {
+                        let link_path = format!("{path}.links[{i}]");
+                        if let Some(link) = self.object(&link_path, link) {
+                            if let Some(t) = self.required(&link_path, link, "t") {
+                                self.hex_id(&format!("{link_path}.t"), t, 16);
+                            }
+                            if let Some(s) = self.required(&link_path, link, "s") {
+                                self.hex_id(&format!("{link_path}.s"), s, 16);
+                            }
+                            if let Some(attributes) = self.required(&link_path, link, "attributes") {
+                                self.object(&format!("{link_path}.attributes"), attributes);
+                            }
+                        }
+                    }
+                },
+                None => self.violation(&format!("{path}.links"), "expected a list of links"),
+            }
+        }
+
+        let Some(otel) = self.required(path, tags, "otel") else {
+            return;
+        };
+        let path = format!("{path}.otel");
+        if let Some(otel) = self.object(&path, otel) {
+            if let Some(scope_name) = self.required(&path, otel, "scope.name") {
+                self.string(&format!("{path}.scope.name"), scope_name);
+            }
+            self.optional(&path, otel, "scope.version", Self::string);
+            for key in ["dropped_events_count", "dropped_links_count", "dropped_attributes_count"] {
+                if let Some(count) = self.required(&path, otel, key) {
+                    self.unsigned(&format!("{path}.{key}"), count);
+                }
+            }
+            self.optional(&path, otel, "status_code", Self::string);
+            self.optional(&path, otel, "status_description", Self::string);
+        }
+    }
+}
@@ -1,5 +1,15 @@
+pub mod event;
 pub mod exporter;
+pub mod pipeline;
+pub mod process_metrics;
 pub mod propagator;
+pub mod stack_trace;
+#[cfg(feature = "testing")]
+pub mod testing;
 
-pub use exporter::{InstanaExporter,InstanaExporterOptions};
+pub use event::{CustomEvent, InstanaEventClient, Severity};
+pub use exporter::{InstanaExporter,InstanaExporterOptions,AgentConfig,AgentDiscovery,AgentHost,CircuitBreakerOptions,CircuitState,LogLevel,ProcessIdentity,Secrets,SecretsMatcher,SpanBatchingOptions,SpanCategory,SpanFilter};
+pub use pipeline::{init, InstanaGuard, InstanaPipeline};
+pub use process_metrics::{ProcessCollector, ProcessMetrics, ProcessSnapshot};
 pub use propagator::{InstanaPropagator};
+pub use stack_trace::{StackTraceConfig, StackTraceMode, StackTraceSpanProcessor};
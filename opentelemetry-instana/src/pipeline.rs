@@ -0,0 +1,277 @@
+//! One-call setup of tracing to Instana.
+//!
+//! [`InstanaPipeline`] wires the [`InstanaExporter`] into a batch span
+//! processor and a tracer provider, and registers the provider and the
+//! [`InstanaPropagator`] globally. The returned [`InstanaGuard`] flushes the
+//! remaining spans and shuts the provider down when it is dropped.
+//!
+//! The batch span processor exports from its own thread, without an async
+//! runtime, so the pipeline uses the blocking HTTP client of the
+//! `reqwest-blocking-client` feature. Without it, exports run on the
+//! multi-threaded tokio runtime the pipeline is installed in.
+//!
+//! ```no_run
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let _guard = opentelemetry_instana::init()?;
+//!
+//!     let tracer = opentelemetry::global::tracer("my-service");
+//!     // ...
+//!     Ok(())
+//! }
+//! ```
+
+use opentelemetry::{global, otel_warn, KeyValue};
+use opentelemetry_http::HttpClient;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::trace::{
+    BatchSpanProcessor, IdGenerator, SdkTracerProvider, ShouldSample, SpanProcessor,
+    TracerProviderBuilder,
+};
+use opentelemetry_sdk::Resource;
+#[cfg(not(feature = "reqwest-blocking-client"))]
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+#[cfg(not(feature = "reqwest-blocking-client"))]
+use tokio::runtime::RuntimeFlavor;
+
+use crate::exporter::{BuildError, Builder, InstanaExporter, InstanaExporterOptions};
+use crate::propagator::InstanaPropagator;
+use crate::stack_trace::{StackTraceConfig, StackTraceSpanProcessor};
+
+/// Builder of a tracer provider exporting to Instana
+pub struct InstanaPipeline {
+    exporter: Builder,
+    options: Option<InstanaExporterOptions>, // read from the environment if `None`
+    resource: Option<Resource>,
+    service_name: Option<String>,
+    stack_traces: Option<StackTraceConfig>, // read from the environment if `None`
+    provider: TracerProviderBuilder,
+    install_global: bool,
+    http_client: bool, // a client was set with `with_http_client`
+}
+
+impl Default for InstanaPipeline {
+    fn default() -> Self {
+        InstanaPipeline {
+            exporter: InstanaExporter::builder(),
+            options: None,
+            resource: None,
+            service_name: None,
+            stack_traces: None,
+            provider: SdkTracerProvider::builder(),
+            install_global: true,
+            http_client: false,
+        }
+    }
+}
+
+impl InstanaPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use these options instead of reading them with [`InstanaExporterOptions::from_env`]
+    pub fn with_options(mut self, options: InstanaExporterOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Use this resource instead of the one detected from the `OTEL_*` variables
+    pub fn with_resource(mut self, resource: Resource) -> Self {
+        self.resource = Some(resource);
+        self
+    }
+
+    /// Set the `service.name` of the resource, overriding the service of the options
+    pub fn with_service_name(mut self, name: impl Into<String>) -> Self {
+        self.service_name = Some(name.into());
+        self
+    }
+
+    pub fn with_http_client(mut self, client: impl HttpClient + 'static) -> Self {
+        self.exporter = self.exporter.with_http_client(client);
+        self.http_client = true;
+        self
+    }
+
+    /// Configure the exporter further, e.g. its timeouts or metrics
+    pub fn with_exporter(mut self, configure: impl FnOnce(Builder) -> Builder) -> Self {
+        self.exporter = configure(self.exporter);
+        self
+    }
+
+    /// Use this configuration instead of reading it with [`StackTraceConfig::from_env`]
+    pub fn with_stack_traces(mut self, config: StackTraceConfig) -> Self {
+        self.stack_traces = Some(config);
+        self
+    }
+
+    /// Add a span processor, called before the one exporting to Instana
+    pub fn with_span_processor<P: SpanProcessor + 'static>(mut self, processor: P) -> Self {
+        self.provider = self.provider.with_span_processor(processor);
+        self
+    }
+
+    /// Replace the default sampler, `ParentBased(AlwaysOn)` unless set with `OTEL_TRACES_SAMPLER`
+    pub fn with_sampler<S: ShouldSample + 'static>(mut self, sampler: S) -> Self {
+        self.provider = self.provider.with_sampler(sampler);
+        self
+    }
+
+    pub fn with_id_generator<G: IdGenerator + 'static>(mut self, id_generator: G) -> Self {
+        self.provider = self.provider.with_id_generator(id_generator);
+        self
+    }
+
+    /// Register the tracer provider and the propagator globally, enabled by default
+    pub fn with_install_global(mut self, install_global: bool) -> Self {
+        self.install_global = install_global;
+        self
+    }
+
+    /// Build the tracer provider and register it globally
+    ///
+    /// Without the `reqwest-blocking-client` feature, installing in a
+    /// `current_thread` tokio runtime fails with
+    /// [`BuildError::CurrentThreadRuntime`]: the exports would block on a
+    /// runtime only driven by the thread the pipeline is installed in.
+    /// Installing outside of a tokio runtime fails with
+    /// [`BuildError::NoRuntime`], unless a client was set with
+    /// [`with_http_client`](Self::with_http_client).
+    pub fn install(self) -> Result<InstanaGuard, BuildError> {
+        let options = match self.options {
+            Some(options) => options,
+            None => InstanaExporterOptions::from_env()?,
+        };
+
+        #[cfg(not(feature = "reqwest-blocking-client"))]
+        let runtime = match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::CurrentThread => {
+                return Err(BuildError::CurrentThreadRuntime)
+            },
+            Ok(handle) => Some(handle),
+            // The async clients panic when sending without a runtime
+            Err(_)
+                if cfg!(any(feature = "reqwest-client", feature = "hyper-client"))
+                    && !self.http_client =>
+            {
+                return Err(BuildError::NoRuntime)
+            },
+            Err(_) => None,
+        };
+
+        let service_name = self
+            .service_name
+            .or_else(|| Some(options.service.clone()).filter(|service| !service.is_empty()));
+        let resource = match (self.resource, service_name) {
+            (Some(resource), None) => resource,
+            (Some(resource), Some(name)) => {
+                let attributes = resource
+                    .iter()
+                    .map(|(key, value)| KeyValue::new(key.clone(), value.clone()));
+                // An empty schema url leaves it unset
+                let schema_url = resource.schema_url().unwrap_or_default().to_string();
+                Resource::builder_empty()
+                    .with_schema_url(attributes, schema_url)
+                    .with_service_name(name)
+                    .build()
+            },
+            (None, Some(name)) => Resource::builder().with_service_name(name).build(),
+            (None, None) => Resource::builder().build(),
+        };
+
+        // The batch span processor exports from its own thread
+        #[cfg(feature = "reqwest-blocking-client")]
+        let exporter = self.exporter.with_blocking_http_client();
+        #[cfg(not(feature = "reqwest-blocking-client"))]
+        let exporter = self.exporter;
+        let exporter = exporter
+            .with_service(resource.clone())
+            .with_options(options)
+            .build()?;
+        #[cfg(not(feature = "reqwest-blocking-client"))]
+        let exporter = RuntimeExporter { exporter, runtime };
+        let stack_traces = self.stack_traces.unwrap_or_else(StackTraceConfig::from_env);
+        let processor = StackTraceSpanProcessor::with_config(
+            BatchSpanProcessor::builder(exporter).build(),
+            stack_traces,
+        );
+        let provider = self
+            .provider
+            .with_span_processor(processor)
+            .with_resource(resource)
+            .build();
+
+        if self.install_global {
+            global::set_text_map_propagator(InstanaPropagator::new());
+            global::set_tracer_provider(provider.clone());
+        }
+
+        Ok(InstanaGuard { provider })
+    }
+}
+
+/// Exporter running exports on a tokio runtime, for async HTTP clients
+#[cfg(not(feature = "reqwest-blocking-client"))]
+#[derive(Debug)]
+struct RuntimeExporter {
+    exporter: InstanaExporter,
+    runtime: Option<tokio::runtime::Handle>, // `None` for clients of `with_http_client`
+}
+
+#[cfg(not(feature = "reqwest-blocking-client"))]
+impl SpanExporter for RuntimeExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        match &self.runtime {
+            // Called from the thread of the batch span processor
+            Some(runtime) => runtime.block_on(self.exporter.export(batch)),
+            None => self.exporter.export(batch).await,
+        }
+    }
+
+    fn shutdown(&mut self) -> OTelSdkResult {
+        self.exporter.shutdown()
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.exporter.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.exporter.set_resource(resource);
+    }
+}
+
+/// Set up tracing to Instana from the environment, see [`InstanaPipeline`]
+pub fn init() -> Result<InstanaGuard, BuildError> {
+    InstanaPipeline::new().install()
+}
+
+/// Keeps the tracer provider alive, flushing and shutting it down on drop
+#[derive(Debug)]
+#[must_use = "dropping the guard shuts down tracing"]
+pub struct InstanaGuard {
+    provider: SdkTracerProvider,
+}
+
+impl InstanaGuard {
+    pub fn tracer_provider(&self) -> &SdkTracerProvider {
+        &self.provider
+    }
+
+    /// Export the finished spans without waiting for the next batch
+    pub fn force_flush(&self) -> OTelSdkResult {
+        self.provider.force_flush()
+    }
+}
+
+impl Drop for InstanaGuard {
+    fn drop(&mut self) {
+        // Shutting down flushes the remaining spans
+        match self.provider.shutdown() {
+            Ok(()) | Err(OTelSdkError::AlreadyShutdown) => {},
+            Err(e) => {
+                otel_warn!(name: "InstanaPipeline.ShutdownFailed", error = e.to_string());
+            },
+        }
+    }
+}
@@ -0,0 +1,492 @@
+//! Snapshot and metrics of the traced process, for the Instana process dashboard.
+//!
+//! Like the sensors of other languages, the [`ProcessCollector`] announces
+//! the process to the agent and then periodically posts the metrics read from
+//! `/proc/self`, and the tokio runtime if any, to the plugin endpoint of the
+//! announced entity. The snapshot describing the process is sent with the
+//! first report and every [`SNAPSHOT_INTERVAL`] after that.
+
+use http::{header::CONTENT_TYPE, Method, StatusCode};
+use opentelemetry::otel_debug;
+use opentelemetry_http::HttpClient;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::exporter::agent_config::announce;
+use crate::exporter::agent_discovery::base_url;
+use crate::exporter::metrics::FailureReason;
+use crate::exporter::{
+    agent_url, runtime_http_client, BuildError, InstanaExporterOptions, ProcessIdentity,
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT,
+};
+
+/// Prefix of the plugin endpoint, followed by the pid the agent knows the process by
+pub const PLUGIN_PATH_PREFIX: &str = "/com.instana.plugin.rust.";
+/// Default interval between two reports
+pub const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+/// Interval after which the snapshot is sent again
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Clock ticks per second of the times in `/proc/<pid>/stat`, `USER_HZ`
+const CLOCK_TICKS: f64 = 100.0;
+
+/// Static description of the process
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessSnapshot {
+    pub name: String,
+    pub args: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>, // version of the application
+    pub sensor_version: String,
+    pub os: String,
+    pub arch: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_id: Option<String>,
+    pub dependencies: BTreeMap<String, String>, // crate names and versions
+}
+
+/// Metrics of the process, `None` if not available
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessMetrics {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<CpuMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_switches: Option<ContextSwitches>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_files: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_open_files: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokio: Option<TokioMetrics>,
+}
+
+/// Memory of the process in bytes, from `/proc/<pid>/status`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryMetrics {
+    pub resident: u64,
+    pub peak_resident: u64,
+    #[serde(rename = "virtual")]
+    pub virtual_: u64,
+}
+
+/// CPU time in seconds and page faults, from `/proc/<pid>/stat`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CpuMetrics {
+    pub user: f64,
+    pub system: f64,
+    pub minor_page_faults: u64,
+    pub major_page_faults: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ContextSwitches {
+    pub voluntary: u64,
+    pub involuntary: u64,
+}
+
+/// Metrics of the tokio runtime the collector runs in
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokioMetrics {
+    pub workers: u64,
+    pub alive_tasks: u64,
+    pub global_queue_depth: u64,
+}
+
+impl ProcessMetrics {
+    /// Read the metrics of the current process and tokio runtime
+    pub fn collect() -> Self {
+        let mut metrics = Self::collect_from(Path::new("/proc/self"));
+        metrics.tokio = TokioMetrics::current();
+        metrics
+    }
+
+    /// Read the metrics from the `status`, `stat` and `limits` files and the
+    /// `fd` directory in `proc_dir`
+    pub fn collect_from(proc_dir: &Path) -> Self {
+        let read = |name: &str| fs::read_to_string(proc_dir.join(name)).ok();
+        let status = read("status").map(|status| parse_status(&status));
+        let stat = read("stat").and_then(|stat| parse_stat(&stat));
+
+        ProcessMetrics {
+            memory: status.as_ref().and_then(|status| {
+                Some(MemoryMetrics {
+                    resident: status.get("VmRSS")?,
+                    peak_resident: status.get("VmHWM")?,
+                    virtual_: status.get("VmSize")?,
+                })
+            }),
+            cpu: stat,
+            threads: status.as_ref().and_then(|status| status.get("Threads")),
+            context_switches: status.as_ref().and_then(|status| {
+                Some(ContextSwitches {
+                    voluntary: status.get("voluntary_ctxt_switches")?,
+                    involuntary: status.get("nonvoluntary_ctxt_switches")?,
+                })
+            }),
+            open_files: fs::read_dir(proc_dir.join("fd"))
+                .ok()
+                .map(|entries| entries.count() as u64),
+            max_open_files: read("limits").and_then(|limits| parse_max_open_files(&limits)),
+            tokio: None,
+        }
+    }
+}
+
+impl TokioMetrics {
+    /// Metrics of the runtime of the current thread, `None` outside of a runtime
+    pub fn current() -> Option<Self> {
+        let metrics = tokio::runtime::Handle::try_current().ok()?.metrics();
+        Some(TokioMetrics {
+            workers: metrics.num_workers() as u64,
+            alive_tasks: metrics.num_alive_tasks() as u64,
+            global_queue_depth: metrics.global_queue_depth() as u64,
+        })
+    }
+}
+
+/// Numeric fields of `/proc/<pid>/status`, sizes in bytes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatusFields(BTreeMap<String, u64>);
+
+impl StatusFields {
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.0.get(name).copied()
+    }
+}
+
+/// Parse the numeric fields of `/proc/<pid>/status`, e.g. `VmRSS: 1424 kB`
+pub fn parse_status(status: &str) -> StatusFields {
+    let fields = status
+        .lines()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            let mut parts = value.split_whitespace();
+            let number: u64 = parts.next()?.parse().ok()?;
+            let value = match parts.next() {
+                Some("kB") => number * 1024,
+                Some(_) => return None,
+                None => number,
+            };
+            Some((name.trim().to_string(), value))
+        })
+        .collect();
+    StatusFields(fields)
+}
+
+/// Parse the CPU times and page faults out of `/proc/<pid>/stat`
+pub fn parse_stat(stat: &str) -> Option<CpuMetrics> {
+    // The command name may contain spaces and parentheses
+    let (_, rest) = stat.rsplit_once(')')?;
+    // Fields after the name, starting with the state as field 3
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let field = |number: usize| fields.get(number - 3)?.parse::<u64>().ok();
+
+    Some(CpuMetrics {
+        user: field(14)? as f64 / CLOCK_TICKS,
+        system: field(15)? as f64 / CLOCK_TICKS,
+        minor_page_faults: field(10)?,
+        major_page_faults: field(12)?,
+    })
+}
+
+/// Parse the soft limit of open files out of `/proc/<pid>/limits`
+pub fn parse_max_open_files(limits: &str) -> Option<u64> {
+    let line = limits.lines().find(|line| line.starts_with("Max open files"))?;
+    line["Max open files".len()..]
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Errors that can occur while reporting to the agent.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum ReportError {
+    /// The process could not be announced to the agent.
+    #[error("announce failed: {0}")]
+    Announce(String),
+
+    /// The report could not be serialized.
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    /// The request could not be built or sent.
+    #[error("Request error: {0}")]
+    Request(String),
+
+    /// The agent rejected the report.
+    #[error("Instana process report failed. Url: {endpoint}, Status Code: {status}")]
+    HttpStatus { endpoint: String, status: u16 },
+}
+
+/// Payload of the plugin endpoint
+#[derive(Serialize)]
+struct EntityData<'a> {
+    pid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot: Option<&'a ProcessSnapshot>,
+    metrics: &'a ProcessMetrics,
+}
+
+#[derive(Debug, Default)]
+struct ReportState {
+    pid: Option<u32>,                // pid of the announced entity
+    snapshot_sent: Option<Instant>, // last time the snapshot was sent
+}
+
+/// Collector reporting the process snapshot and metrics to the agent
+#[derive(Debug)]
+pub struct ProcessCollector {
+    client: Arc<dyn HttpClient>,
+    agent_url: String,
+    headers: http::HeaderMap,
+    interval: Duration,
+    identity: ProcessIdentity,
+    proc_dir: PathBuf,
+    snapshot: ProcessSnapshot,
+    state: Mutex<ReportState>,
+}
+
+impl ProcessCollector {
+    pub fn builder() -> ProcessCollectorBuilder {
+        ProcessCollectorBuilder::default()
+    }
+
+    pub fn snapshot(&self) -> &ProcessSnapshot {
+        &self.snapshot
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Pid of the entity the reports are sent to, `None` until announced
+    pub fn entity_pid(&self) -> Option<u32> {
+        self.state.lock().ok().and_then(|state| state.pid)
+    }
+
+    /// Read the current metrics of the process
+    pub fn collect(&self) -> ProcessMetrics {
+        let mut metrics = ProcessMetrics::collect_from(&self.proc_dir);
+        metrics.tokio = TokioMetrics::current();
+        metrics
+    }
+
+    /// Announce the process if needed and send one report. When the agent
+    /// does not know the entity, the process is announced again on the next report.
+    pub async fn report(&self) -> Result<(), ReportError> {
+        let (pid, send_snapshot) = {
+            let state = self.state.lock().map_err(|e| ReportError::Request(e.to_string()))?;
+            let send_snapshot = state
+                .snapshot_sent
+                .is_none_or(|sent| sent.elapsed() >= SNAPSHOT_INTERVAL);
+            (state.pid, send_snapshot)
+        };
+        let pid = match pid {
+            Some(pid) => pid,
+            None => {
+                let config = announce(self.client.as_ref(), &self.agent_url, &self.identity)
+                    .await
+                    .map_err(|e| ReportError::Announce(e.to_string()))?;
+                let pid = config.pid.unwrap_or_else(|| self.identity.agent_pid());
+                if let Ok(mut state) = self.state.lock() {
+                    state.pid = Some(pid);
+                }
+                pid
+            },
+        };
+
+        let metrics = self.collect();
+        let body = serde_json::to_vec(&EntityData {
+            pid,
+            snapshot: send_snapshot.then_some(&self.snapshot),
+            metrics: &metrics,
+        })?;
+        let endpoint = format!("{}{}{}", self.agent_url, PLUGIN_PATH_PREFIX, pid);
+        let mut request = http::Request::builder()
+            .method(Method::POST)
+            .uri(&endpoint)
+            .header(CONTENT_TYPE, "application/json")
+            .body(bytes::Bytes::from(body))
+            .map_err(|e| ReportError::Request(e.to_string()))?;
+        for (k, v) in &self.headers {
+            request.headers_mut().insert(k.clone(), v.clone());
+        }
+
+        // The HTTP clients report non-2xx statuses as errors
+        let status = match self.client.send_bytes(request).await {
+            Ok(response) => response.status(),
+            Err(e) => match FailureReason::from_http_error(&e) {
+                FailureReason::HttpStatus(status) => {
+                    StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+                },
+                _ => return Err(ReportError::Request(format!("{e:?}"))),
+            },
+        };
+        if !status.is_success() {
+            if status == StatusCode::NOT_FOUND {
+                // The agent forgot the entity, e.g. after a restart
+                if let Ok(mut state) = self.state.lock() {
+                    *state = ReportState::default();
+                }
+            }
+            return Err(ReportError::HttpStatus {
+                endpoint,
+                status: status.as_u16(),
+            });
+        }
+
+        if send_snapshot {
+            if let Ok(mut state) = self.state.lock() {
+                state.snapshot_sent = Some(Instant::now());
+            }
+        }
+        Ok(())
+    }
+
+    /// Report on the interval of the collector until the task is aborted.
+    ///
+    /// Must be called within a tokio runtime.
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if let Err(error) = self.report().await {
+                    otel_debug!(name: "ProcessCollector.ReportFailed", error = error.to_string());
+                }
+            }
+        })
+    }
+}
+
+pub struct ProcessCollectorBuilder {
+    client: Option<Arc<dyn HttpClient>>,
+    agent_url: Option<String>,
+    headers: http::HeaderMap,
+    interval: Duration,
+    identity: Option<ProcessIdentity>,
+    proc_dir: PathBuf,
+    version: Option<String>,
+    dependencies: BTreeMap<String, String>,
+}
+
+impl Default for ProcessCollectorBuilder {
+    fn default() -> Self {
+        ProcessCollectorBuilder {
+            client: None,
+            agent_url: None,
+            headers: http::HeaderMap::new(),
+            interval: DEFAULT_REPORT_INTERVAL,
+            identity: None,
+            proc_dir: PathBuf::from("/proc/self"),
+            version: None,
+            dependencies: BTreeMap::new(),
+        }
+    }
+}
+
+impl ProcessCollectorBuilder {
+    /// Report to the agent the exporter configured by `options` sends spans to
+    pub fn with_options(mut self, options: &InstanaExporterOptions) -> Self {
+        self.agent_url = Some(base_url(&options.endpoint));
+        self.headers = options.headers.clone();
+        self
+    }
+
+    /// Report to the agent at the given URL, e.g. `http://localhost:42699`
+    pub fn with_agent_url(mut self, agent_url: &str) -> Self {
+        self.agent_url = Some(agent_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    pub fn with_http_client(mut self, client: impl HttpClient + 'static) -> Self {
+        self.client = Some(Arc::new(client));
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Replace the identity of the process detected from `/proc/self`
+    pub fn with_process_identity(mut self, identity: ProcessIdentity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Read the metrics from this directory instead of `/proc/self`
+    pub fn with_proc_dir(mut self, proc_dir: impl Into<PathBuf>) -> Self {
+        self.proc_dir = proc_dir.into();
+        self
+    }
+
+    /// Set the version of the application shown in the snapshot
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Add a dependency shown in the snapshot, e.g. `("tokio", "1.52.1")`
+    pub fn with_dependency(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.dependencies.insert(name.into(), version.into());
+        self
+    }
+
+    pub fn build(self) -> Result<ProcessCollector, BuildError> {
+        let client = match self.client {
+            Some(client) => client,
+            None => runtime_http_client(DEFAULT_TIMEOUT, DEFAULT_CONNECT_TIMEOUT)?,
+        };
+        let identity = self
+            .identity
+            .unwrap_or_else(|| ProcessIdentity::current().clone());
+        let name = std::env::current_exe()
+            .map(|exe| exe.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut dependencies = self.dependencies;
+        dependencies
+            .entry(env!("CARGO_PKG_NAME").to_string())
+            .or_insert_with(|| env!("CARGO_PKG_VERSION").to_string());
+
+        let snapshot = ProcessSnapshot {
+            name,
+            args: std::env::args().skip(1).collect(),
+            version: self.version,
+            sensor_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            container_id: identity.container_id.clone(),
+            dependencies,
+        };
+
+        Ok(ProcessCollector {
+            client,
+            agent_url: self.agent_url.unwrap_or_else(agent_url),
+            headers: self.headers,
+            interval: self.interval,
+            identity,
+            proc_dir: self.proc_dir,
+            snapshot,
+            state: Mutex::new(ReportState::default()),
+        })
+    }
+}
@@ -1,46 +1,229 @@
 use opentelemetry::{
     propagation::{text_map_propagator::FieldIter, Extractor, Injector, TextMapPropagator},
+    trace::{SpanContext, TraceContextExt},
     Context,
 };
-use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
 
-#[derive(Debug)]
+/// Span context extracted by one of the propagators of a [`CompositePropagator`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedContext {
+    pub propagator: String, // name of the propagator
+    pub span_context: SpanContext,
+}
+
+/// Resolver picking the span context to continue, by its index in the
+/// candidates, which are in the order of the propagators
+pub type ContextResolver = Arc<dyn Fn(&[ExtractedContext]) -> Option<usize> + Send + Sync>;
+
+/// Which span context is continued when several propagators extract one
+#[derive(Clone, Default)]
+pub enum ExtractionPolicy {
+    /// The valid context of the first propagator
+    FirstValid,
+    /// The valid context of the last propagator
+    #[default]
+    LastValid,
+    /// The context picked by the resolver, none if it returns `None`
+    Custom(ContextResolver),
+}
+
+impl fmt::Debug for ExtractionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractionPolicy::FirstValid => f.write_str("FirstValid"),
+            ExtractionPolicy::LastValid => f.write_str("LastValid"),
+            ExtractionPolicy::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// Which propagators inject their headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InjectionPolicy {
+    /// All propagators
+    #[default]
+    All,
+    /// The propagators whose headers were seen in the inbound request, or all
+    /// propagators if the trace did not come in through the composite
+    SeenInbound,
+}
+
+/// How the context of an inbound request was extracted, stored in the
+/// extracted `Context`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InboundPropagation {
+    /// Name of the propagator that supplied the span context, if any
+    pub source: Option<String>,
+    /// Names of the propagators whose headers were present
+    pub seen: Vec<String>,
+}
+
+impl InboundPropagation {
+    pub fn from_context(cx: &Context) -> Option<&Self> {
+        cx.get::<InboundPropagation>()
+    }
+}
+
+struct NamedPropagator {
+    name: String,
+    propagator: Box<dyn TextMapPropagator + Send + Sync>,
+}
+
+#[derive(Default)]
 pub struct CompositePropagator {
-    propagators: Vec<Box<dyn TextMapPropagator + Send + Sync>>,
+    propagators: Vec<NamedPropagator>,
     fields: Vec<String>,
+    extraction: ExtractionPolicy,
+    injection: InjectionPolicy,
+}
+
+impl fmt::Debug for CompositePropagator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompositePropagator")
+            .field("propagators", &self.names())
+            .field("fields", &self.fields)
+            .field("extraction", &self.extraction)
+            .field("injection", &self.injection)
+            .finish()
+    }
 }
 
 impl CompositePropagator {
+    /// Combine the propagators, each named after the first header it handles,
+    /// e.g. `traceparent` or `x-instana-t`
     pub fn new(propagators: Vec<Box<dyn TextMapPropagator + Send + Sync>>) -> Self {
-        let mut fields = HashSet::new();
-        for propagator in &propagators {
-            fields.extend(propagator.fields().map(ToString::to_string));
+        propagators
+            .into_iter()
+            .fold(Self::default(), |composite, propagator| {
+                let name = propagator
+                    .fields()
+                    .next()
+                    .map(str::to_ascii_lowercase)
+                    .unwrap_or_default();
+                composite.with_boxed_propagator(name, propagator)
+            })
+    }
+
+    /// Add a propagator, after the ones already added
+    pub fn with_propagator(
+        self,
+        name: impl Into<String>,
+        propagator: impl TextMapPropagator + Send + Sync + 'static,
+    ) -> Self {
+        self.with_boxed_propagator(name.into(), Box::new(propagator))
+    }
+
+    fn with_boxed_propagator(
+        mut self,
+        name: String,
+        propagator: Box<dyn TextMapPropagator + Send + Sync>,
+    ) -> Self {
+        for field in propagator.fields() {
+            if !self.fields.iter().any(|known| known == field) {
+                self.fields.push(field.to_string());
+            }
         }
+        self.propagators.push(NamedPropagator { name, propagator });
+        self
+    }
 
-        CompositePropagator {
-            propagators,
-            fields: fields.into_iter().collect(),
+    pub fn with_extraction_policy(mut self, policy: ExtractionPolicy) -> Self {
+        self.extraction = policy;
+        self
+    }
+
+    pub fn with_injection_policy(mut self, policy: InjectionPolicy) -> Self {
+        self.injection = policy;
+        self
+    }
+
+    /// Names of the propagators, in order
+    pub fn names(&self) -> Vec<&str> {
+        self.propagators.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    fn resolve(&self, candidates: &[ExtractedContext]) -> Option<usize> {
+        match &self.extraction {
+            ExtractionPolicy::FirstValid => (!candidates.is_empty()).then_some(0),
+            ExtractionPolicy::LastValid => candidates.len().checked_sub(1),
+            ExtractionPolicy::Custom(resolver) => {
+                resolver(candidates).filter(|index| *index < candidates.len())
+            },
         }
     }
 }
 
+/// Whether a header of the propagator is present
+fn is_seen(propagator: &dyn TextMapPropagator, extractor: &dyn Extractor) -> bool {
+    propagator
+        .fields()
+        .any(|field| extractor.get(field).is_some())
+}
+
 impl TextMapPropagator for CompositePropagator {
     /// Encodes the values of the `Context` and injects them into the `Injector`.
     fn inject_context(&self, context: &Context, injector: &mut dyn Injector) {
-        for propagator in &self.propagators {
-            propagator.inject_context(context, injector)
+        let seen = match self.injection {
+            InjectionPolicy::All => None,
+            InjectionPolicy::SeenInbound => {
+                InboundPropagation::from_context(context).map(|inbound| &inbound.seen)
+            },
+        };
+        for named in &self.propagators {
+            if seen.is_none_or(|seen| seen.contains(&named.name)) {
+                named.propagator.inject_context(context, injector)
+            }
         }
     }
 
-    /// Retrieves encoded `Context` information using the `Extractor`. If no data was
-    /// retrieved OR if the retrieved data is invalid, then the current `Context` is
-    /// returned.
+    /// Retrieves encoded `Context` information using the `Extractor`. Values
+    /// of the propagators leaving the span context untouched, such as baggage,
+    /// are kept, the span context is picked by the extraction policy. If no
+    /// data was retrieved OR if the retrieved data is invalid, then the current
+    /// `Context` is returned.
     fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
-        self.propagators
-            .iter()
-            .fold(cx.clone(), |current_cx, propagator| {
-                propagator.extract_with_context(&current_cx, extractor)
-            })
+        let current_span_context = cx.span().span_context().clone();
+        let mut inbound = InboundPropagation::default();
+        let mut candidates = Vec::new();
+        // Values of the propagators leaving the span context untouched
+        let mut extracted = cx.clone();
+
+        for named in &self.propagators {
+            if !is_seen(named.propagator.as_ref(), extractor) {
+                continue;
+            }
+            inbound.seen.push(named.name.clone());
+
+            // A span context extracted by one propagator is not passed to the
+            // next, so that a propagator leaving it untouched is not credited
+            // with the one of another
+            let propagated = named.propagator.extract_with_context(&extracted, extractor);
+            let span_context = propagated.span().span_context().clone();
+            if span_context == current_span_context {
+                extracted = propagated;
+            } else if span_context.is_valid() {
+                candidates.push(ExtractedContext {
+                    propagator: named.name.clone(),
+                    span_context,
+                });
+            }
+        }
+
+        if inbound.seen.is_empty() {
+            return extracted;
+        }
+        match self.resolve(&candidates) {
+            Some(index) => {
+                let winner = candidates.swap_remove(index);
+                inbound.source = Some(winner.propagator);
+                extracted
+                    .with_remote_span_context(winner.span_context)
+                    .with_value(inbound)
+            },
+            None => extracted.with_value(inbound),
+        }
     }
 
     fn fields(&self) -> FieldIter<'_> {
@@ -1,6 +1,9 @@
 mod composite;
 
-pub use composite::CompositePropagator;
+pub use composite::{
+    CompositePropagator, ContextResolver, ExtractedContext, ExtractionPolicy, InboundPropagation,
+    InjectionPolicy,
+};
 
 use opentelemetry::{
     propagation::{text_map_propagator::FieldIter, Extractor, Injector, TextMapPropagator},
@@ -46,12 +49,7 @@ impl InstanaPropagator {
             anyhow::anyhow!("Missing trace ID header: {}", INSTANA_TRACE_ID_HEADER)
         })?;
 
-        // Fast path check for uppercase characters
-        if trace_id_str.chars().any(|c| c.is_ascii_uppercase()) {
-            bail!("TraceId must be lowercase: {}", trace_id_str);
-        }
-
-        let trace_id = TraceId::from_hex(trace_id_str)
+        let trace_id = TraceId::from_hex(&normalize_id(trace_id_str, 32)?)
             .context(format!("Invalid Trace Id: {}", trace_id_str))?;
 
         // Extract and validate span ID
@@ -59,13 +57,8 @@ impl InstanaPropagator {
             .get(INSTANA_SPAN_ID_HEADER)
             .ok_or_else(|| anyhow::anyhow!("Missing span ID header: {}", INSTANA_SPAN_ID_HEADER))?;
 
-        // Fast path check for uppercase characters
-        if span_id_str.chars().any(|c| c.is_ascii_uppercase()) {
-            bail!("SpanId must be lowercase: {}", span_id_str);
-        }
-
-        let span_id =
-            SpanId::from_hex(span_id_str).context(format!("Invalid span id: {}", span_id_str))?;
+        let span_id = SpanId::from_hex(&normalize_id(span_id_str, 16)?)
+            .context(format!("Invalid span id: {}", span_id_str))?;
 
         // Extract and determine sampling flag
         let level_value = extractor
@@ -98,6 +91,26 @@ impl InstanaPropagator {
     }
 }
 
+/// Trim and lowercase an id, left-padding it with zeros to `width` hex digits
+///
+/// Instana tracers send 64-bit trace ids and may drop leading zeros.
+fn normalize_id(value: &str, width: usize) -> Result<String> {
+    let value = value.trim();
+    if value.is_empty() || value.len() > width || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("Invalid id: {}", value);
+    }
+    Ok(format!("{:0>width$}", value.to_ascii_lowercase()))
+}
+
+/// The 16 hex digit form Instana expects if the upper 64 bits are zero
+fn format_trace_id(trace_id: TraceId) -> String {
+    let trace_id = trace_id.to_string();
+    match trace_id.strip_prefix("0000000000000000") {
+        Some(lower) => lower.to_string(),
+        None => trace_id,
+    }
+}
+
 impl TextMapPropagator for InstanaPropagator {
     /// convert the trace-id,span-id and sampling flag from span context into key-value pairs
 
@@ -105,7 +118,7 @@ impl TextMapPropagator for InstanaPropagator {
         let span = cx.span();
         let span_context = span.span_context();
         if span_context.is_valid() {
-            injector.set(INSTANA_TRACE_ID_HEADER, span_context.trace_id().to_string());
+            injector.set(INSTANA_TRACE_ID_HEADER, format_trace_id(span_context.trace_id()));
             injector.set(INSTANA_SPAN_ID_HEADER, span_context.span_id().to_string());
             let level_value = if span_context.is_sampled() {
                 IS_SAMPLED
@@ -0,0 +1,259 @@
+use opentelemetry::trace::{SpanId, SpanKind, Status};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::trace::{Span, SpanData, SpanProcessor};
+use opentelemetry_sdk::Resource;
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::exporter::instana_span::InstanaStackFrame;
+
+/// Span attribute used to hand a captured stack trace over to the exporter.
+/// It is added to the [`SpanData`] when the span ends, out of reach of the
+/// span limits, which could truncate or drop it.
+pub(crate) const INTERNAL_TAG_STACK: &str = "INTERNAL_TAG_STACK";
+
+const INSTANA_STACK_TRACE: &str = "INSTANA_STACK_TRACE";
+const INSTANA_STACK_TRACE_LENGTH: &str = "INSTANA_STACK_TRACE_LENGTH";
+const DEFAULT_STACK_TRACE_LENGTH: usize = 10;
+
+/// Frames of these crates belong to the tracing machinery and are trimmed
+/// from the captured stack.
+const SKIPPED_FRAME_PREFIXES: &[&str] = &[
+    "std::",
+    "core::",
+    "alloc::",
+    "opentelemetry::",
+    "opentelemetry_sdk::",
+    "opentelemetry_instana::",
+    "<opentelemetry",
+    "<std::",
+    "<core::",
+    "<alloc::",
+];
+
+/// Which spans get a stack trace attached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackTraceMode {
+    /// Exit spans when they start and erroneous spans when they end
+    All,
+    /// Erroneous spans when they end
+    Error,
+    /// No stack traces are captured
+    None,
+}
+
+/// Configuration of the [`StackTraceSpanProcessor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackTraceConfig {
+    pub mode: StackTraceMode,
+    pub length: usize,
+}
+
+impl Default for StackTraceConfig {
+    fn default() -> Self {
+        StackTraceConfig {
+            mode: StackTraceMode::None,
+            length: DEFAULT_STACK_TRACE_LENGTH,
+        }
+    }
+}
+
+impl StackTraceConfig {
+    /// Read the configuration from `INSTANA_STACK_TRACE` (`all`, `error` or
+    /// `none`) and `INSTANA_STACK_TRACE_LENGTH`. Missing or unparsable values
+    /// fall back to the defaults, which disable stack trace capture.
+    pub fn from_env() -> Self {
+        let mode = match env::var(INSTANA_STACK_TRACE)
+            .map(|v| v.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            Ok("all") => StackTraceMode::All,
+            Ok("error") => StackTraceMode::Error,
+            _ => StackTraceMode::None,
+        };
+
+        let length = env::var(INSTANA_STACK_TRACE_LENGTH)
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_STACK_TRACE_LENGTH);
+
+        StackTraceConfig { mode, length }
+    }
+}
+
+/// A [`SpanProcessor`] that captures the call-site stack of exit spans and
+/// erroneous spans before passing them on to the wrapped processor.
+///
+/// The stack is exported in the `stack` field of the Instana span.
+#[derive(Debug)]
+pub struct StackTraceSpanProcessor<P: SpanProcessor> {
+    inner: P,
+    config: StackTraceConfig,
+    exit_stacks: Mutex<HashMap<SpanId, KeyValue>>, // of exit spans started and not ended yet
+}
+
+impl<P: SpanProcessor> StackTraceSpanProcessor<P> {
+    /// Wrap `inner`, reading the configuration from the environment
+    pub fn new(inner: P) -> Self {
+        Self::with_config(inner, StackTraceConfig::from_env())
+    }
+
+    pub fn with_config(inner: P, config: StackTraceConfig) -> Self {
+        StackTraceSpanProcessor {
+            inner,
+            config,
+            exit_stacks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> StackTraceConfig {
+        self.config
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.config.mode != StackTraceMode::None && self.config.length > 0
+    }
+
+    fn capture(&self) -> KeyValue {
+        let frames = capture_stack(self.config.length);
+        let stack = serde_json::to_string(&frames).unwrap_or_default();
+        KeyValue::new(INTERNAL_TAG_STACK, stack)
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for StackTraceSpanProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        if self.is_enabled() && self.config.mode == StackTraceMode::All {
+            let is_exit = matches!(
+                span.span_kind(),
+                Some(SpanKind::Client | SpanKind::Consumer)
+            );
+            if is_exit {
+                let span_id = opentelemetry::trace::Span::span_context(span).span_id();
+                if let Ok(mut stacks) = self.exit_stacks.lock() {
+                    stacks.insert(span_id, self.capture());
+                }
+            }
+        }
+        self.inner.on_start(span, cx);
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        if self.is_enabled() {
+            let exit_stack = if self.config.mode == StackTraceMode::All
+                && matches!(span.span_kind, SpanKind::Client | SpanKind::Consumer)
+            {
+                self.exit_stacks
+                    .lock()
+                    .ok()
+                    .and_then(|mut stacks| stacks.remove(&span.span_context.span_id()))
+            } else {
+                None
+            };
+            let stack = exit_stack.or_else(|| {
+                matches!(span.status, Status::Error { .. }).then(|| self.capture())
+            });
+            span.attributes.extend(stack);
+        }
+        self.inner.on_end(span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        // Spans ending after the shutdown are not passed to `on_end`
+        if let Ok(mut stacks) = self.exit_stacks.lock() {
+            stacks.clear();
+        }
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+/// Capture the current stack, drop the frames of the tracing machinery and
+/// keep at most `length` frames.
+fn capture_stack(length: usize) -> Vec<InstanaStackFrame> {
+    let backtrace = Backtrace::force_capture().to_string();
+    parse_backtrace(&backtrace)
+        .into_iter()
+        .filter(|frame| {
+            !SKIPPED_FRAME_PREFIXES
+                .iter()
+                .any(|prefix| frame.method.starts_with(prefix))
+        })
+        .take(length)
+        .collect()
+}
+
+/// Parse the `Display` output of a [`Backtrace`] into stack frames.
+///
+/// Each frame is a `N: symbol` line optionally followed by an
+/// `at file:line:column` line.
+fn parse_backtrace(backtrace: &str) -> Vec<InstanaStackFrame> {
+    let mut frames: Vec<InstanaStackFrame> = Vec::new();
+
+    for line in backtrace.lines() {
+        let line = line.trim();
+        if let Some(location) = line.strip_prefix("at ") {
+            if let Some(frame) = frames.last_mut() {
+                let mut parts = location.rsplitn(3, ':');
+                let _column = parts.next();
+                let line_number = parts.next().and_then(|n| n.parse::<u32>().ok());
+                match (parts.next(), line_number) {
+                    (Some(file), Some(number)) => {
+                        frame.file = Some(file.to_string());
+                        frame.line = Some(number);
+                    },
+                    _ => frame.file = Some(location.to_string()),
+                }
+            }
+        } else if let Some((index, symbol)) = line.split_once(": ") {
+            if !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()) {
+                frames.push(InstanaStackFrame {
+                    method: symbol.to_string(),
+                    file: None,
+                    line: None,
+                });
+            }
+        }
+    }
+
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKTRACE: &str = "   0: std::backtrace::Backtrace::force_capture
+             at /rustc/abc/library/std/src/backtrace.rs:312:9
+   1: opentelemetry_instana::stack_trace::capture_stack
+             at ./src/stack_trace.rs:170:21
+   2: my_service::db::query
+             at ./src/db.rs:42:5
+   3: my_service::main::{{closure}}
+   4: main";
+
+    #[test]
+    fn test_parse_backtrace() {
+        let frames = parse_backtrace(BACKTRACE);
+        assert_eq!(frames.len(), 5);
+
+        assert_eq!(frames[2].method, "my_service::db::query");
+        assert_eq!(frames[2].file.as_deref(), Some("./src/db.rs"));
+        assert_eq!(frames[2].line, Some(42));
+
+        assert_eq!(frames[3].method, "my_service::main::{{closure}}");
+        assert_eq!(frames[3].file, None);
+        assert_eq!(frames[3].line, None);
+    }
+}
@@ -0,0 +1,220 @@
+//! In-process mock of the Instana agent for end-to-end tracing tests.
+//!
+//! The [`MockAgent`] answers agent discovery, accepts spans posted to the
+//! `rawtrace` endpoint and turns them back into [`InstanaSpan`]s that can be
+//! checked with the assertion helpers of [`ReceivedSpans`].
+//!
+//! ```ignore
+//! let agent = MockAgent::start().await;
+//! let exporter = InstanaExporter::builder()
+//!     .with_options(agent.exporter_options())
+//!     .build()?;
+//!
+//! // ... record spans ...
+//!
+//! let spans = agent.received_spans().await;
+//! spans.assert_parent_child("GET /orders", "SELECT orders");
+//! ```
+
+use std::ops::Deref;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::exporter::agent_config::ANNOUNCE_PATH;
+pub use crate::exporter::agent_discovery::AGENT_SERVER_HEADER;
+use crate::exporter::instana_span::InstanaSpan;
+use crate::exporter::span_schema::validate_payload;
+use crate::exporter::RAWTRACE_PATH;
+use crate::InstanaExporterOptions;
+
+/// Path of the announce endpoint of the agent
+pub const DISCOVERY_PATH: &str = ANNOUNCE_PATH;
+
+/// Instana span kind of entry spans
+pub const ENTRY: i32 = 1;
+/// Instana span kind of exit spans
+pub const EXIT: i32 = 2;
+/// Instana span kind of intermediate spans
+pub const INTERMEDIATE: i32 = 3;
+
+/// An in-process Instana agent
+pub struct MockAgent {
+    server: MockServer,
+}
+
+impl MockAgent {
+    /// Start a mock agent answering discovery and accepting spans
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/"))
+            .respond_with(
+                ResponseTemplate::new(200).insert_header("Server", AGENT_SERVER_HEADER),
+            )
+            .mount(&server)
+            .await;
+
+        Mock::given(method("PUT"))
+            .and(path(DISCOVERY_PATH))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Server", AGENT_SERVER_HEADER)
+                    .set_body_json(serde_json::json!({
+                        "pid": std::process::id(),
+                        "agentUuid": "mock-agent",
+                    })),
+            )
+            .mount(&server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path(RAWTRACE_PATH))
+            .respond_with(ResponseTemplate::new(204).insert_header("Server", AGENT_SERVER_HEADER))
+            .mount(&server)
+            .await;
+
+        MockAgent { server }
+    }
+
+    /// Base URL of the agent
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// The underlying mock server, to mount additional endpoints
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Exporter options sending spans to this agent
+    pub fn exporter_options(&self) -> InstanaExporterOptions {
+        InstanaExporterOptions {
+            endpoint: format!("{}{}", self.uri(), RAWTRACE_PATH),
+            ..Default::default()
+        }
+    }
+
+    /// All spans received so far, in the order they were received
+    ///
+    /// # Panics
+    ///
+    /// Panics if a payload cannot be deserialized into Instana spans.
+    pub async fn received_spans(&self) -> ReceivedSpans {
+        let requests = self.server.received_requests().await.unwrap_or_default();
+
+        let spans = requests
+            .iter()
+            .filter(|request| {
+                request.method == wiremock::http::Method::POST && request.url.path() == RAWTRACE_PATH
+            })
+            .flat_map(|request| {
+                serde_json::from_slice::<Vec<InstanaSpan>>(&request.body)
+                    .expect("rawtrace payload is not a list of Instana spans")
+            })
+            .collect();
+
+        ReceivedSpans(spans)
+    }
+
+    /// Assert that every payload received so far matches the schema the agent expects
+    ///
+    /// # Panics
+    ///
+    /// Panics with the violations of the first invalid payload.
+    pub async fn assert_valid_payloads(&self) {
+        let requests = self.server.received_requests().await.unwrap_or_default();
+
+        for request in requests.iter().filter(|request| {
+            request.method == wiremock::http::Method::POST && request.url.path() == RAWTRACE_PATH
+        }) {
+            if let Err(violations) = validate_payload(&request.body) {
+                let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                panic!("invalid rawtrace payload:\n{}", violations.join("\n"));
+            }
+        }
+    }
+
+    /// Forget all received requests
+    pub async fn reset(&self) {
+        self.server.reset().await;
+    }
+}
+
+/// Spans received by a [`MockAgent`]
+#[derive(Debug)]
+pub struct ReceivedSpans(Vec<InstanaSpan>);
+
+impl Deref for ReceivedSpans {
+    type Target = [InstanaSpan];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ReceivedSpans {
+    /// Find the first span with the given name
+    pub fn find_by_name(&self, name: &str) -> Option<&InstanaSpan> {
+        self.0.iter().find(|span| span.data.sdk.name == name)
+    }
+
+    /// All spans with the given name
+    pub fn filter_by_name(&self, name: &str) -> Vec<&InstanaSpan> {
+        self.0
+            .iter()
+            .filter(|span| span.data.sdk.name == name)
+            .collect()
+    }
+
+    /// Number of spans marked as erroneous
+    pub fn error_count(&self) -> i32 {
+        self.0.iter().filter_map(|span| span.error_count).sum()
+    }
+
+    /// Get the span with the given name, panicking if it was not received
+    pub fn expect_span(&self, name: &str) -> &InstanaSpan {
+        self.find_by_name(name).unwrap_or_else(|| {
+            panic!(
+                "no span named {name:?} received, received spans: {:?}",
+                self.names()
+            )
+        })
+    }
+
+    /// Assert that the span named `child` is a child of the span named `parent`
+    pub fn assert_parent_child(&self, parent: &str, child: &str) {
+        let parent_span = self.expect_span(parent);
+        let child_span = self.expect_span(child);
+
+        assert_eq!(
+            child_span.trace_id, parent_span.trace_id,
+            "span {child:?} is not in the trace of span {parent:?}"
+        );
+        assert_eq!(
+            child_span.parent_span_id.as_deref(),
+            Some(parent_span.span_id.as_str()),
+            "span {child:?} is not a child of span {parent:?}"
+        );
+    }
+
+    /// Assert the Instana kind ([`ENTRY`], [`EXIT`] or [`INTERMEDIATE`]) of the span named `name`
+    pub fn assert_kind(&self, name: &str, kind: i32) {
+        assert_eq!(
+            self.expect_span(name).kind,
+            kind,
+            "unexpected kind of span {name:?}"
+        );
+    }
+
+    /// Assert the number of spans marked as erroneous
+    pub fn assert_error_count(&self, count: i32) {
+        assert_eq!(self.error_count(), count, "unexpected number of erroneous spans");
+    }
+
+    fn names(&self) -> Vec<&str> {
+        self.0.iter().map(|span| span.data.sdk.name.as_str()).collect()
+    }
+}
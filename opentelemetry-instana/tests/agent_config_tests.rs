@@ -0,0 +1,452 @@
+use opentelemetry::trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_instana::exporter::agent_config::{AgentConfigError, ANNOUNCE_PATH};
+use opentelemetry_instana::exporter::span_filter::{FilterCondition, FilterRule, MatchType};
+use opentelemetry_instana::{
+    AgentConfig, InstanaExporter, InstanaExporterOptions, Secrets, SecretsMatcher, SpanCategory,
+    SpanFilter,
+};
+use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanExporter, SpanLinks};
+use serde_json::json;
+use std::time::{Duration, SystemTime};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const RAWTRACE_PATH: &str = "/com.instana.plugin.generic.rawtrace";
+
+fn create_test_span_data(name: &'static str, attributes: Vec<KeyValue>) -> SpanData {
+    let time = SystemTime::now();
+
+    SpanData {
+        span_context: SpanContext::new(
+            TraceId::from_hex("0102030405060708090a0b0c0d0e0f10").unwrap(),
+            SpanId::from_hex("0102030405060708").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        ),
+        parent_span_id: SpanId::INVALID,
+        span_kind: SpanKind::Client,
+        name: std::borrow::Cow::Borrowed(name),
+        start_time: time,
+        end_time: time,
+        attributes,
+        dropped_attributes_count: 0,
+        events: SpanEvents::default(),
+        links: SpanLinks::default(),
+        status: Status::Ok,
+        instrumentation_scope: InstrumentationScope::builder("test-instrumentation").build(),
+    }
+}
+
+/// Start an agent answering the announce request with `config`
+async fn start_agent(config: serde_json::Value) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path(ANNOUNCE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(config))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(RAWTRACE_PATH))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Attributes of the first span of a rawtrace payload
+fn attributes(payload: &serde_json::Value) -> &serde_json::Value {
+    &payload[0]["data"]["sdk"]["custom"]["tags"]["attributes"]
+}
+
+fn build_exporter(server: &MockServer, options: InstanaExporterOptions) -> InstanaExporter {
+    let options = InstanaExporterOptions {
+        endpoint: format!("{}{}", server.uri(), RAWTRACE_PATH),
+        remote_config: true,
+        ..options
+    };
+    InstanaExporter::builder()
+        .with_options(options)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter")
+}
+
+async fn requests_to(server: &MockServer, request_path: &str) -> Vec<serde_json::Value> {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter(|request| request.url.path() == request_path)
+        .map(|request| serde_json::from_slice(&request.body).unwrap_or(serde_json::Value::Null))
+        .collect()
+}
+
+#[test]
+fn test_parse_announce_response() {
+    let body = json!({
+        "pid": 1234,
+        "agentUuid": "agent-1",
+        "secrets": { "matcher": "contains-ignore-case", "list": ["key", "pass"] },
+        "tracing": {
+            "extra-http-headers": ["X-Request-Id", "x-tenant"],
+            "disable": [{ "databases": true }, { "logging": true }, { "messaging": false }]
+        }
+    });
+
+    let config = AgentConfig::from_announce_response(body.to_string().as_bytes()).unwrap();
+
+    assert_eq!(config.agent_uuid.as_deref(), Some("agent-1"));
+    assert_eq!(config.pid, Some(1234));
+    assert!(!config.tracing_disabled);
+    assert_eq!(
+        config.secrets,
+        Some(
+            Secrets::new(
+                SecretsMatcher::ContainsIgnoreCase,
+                vec!["key".to_string(), "pass".to_string()]
+            )
+            .unwrap()
+        )
+    );
+    assert_eq!(config.extra_http_headers, vec!["x-request-id", "x-tenant"]);
+    assert_eq!(config.disabled_categories, vec![SpanCategory::Databases]);
+}
+
+#[test]
+fn test_parse_minimal_announce_response() {
+    let config = AgentConfig::from_announce_response(br#"{"pid": 1}"#).unwrap();
+
+    assert_eq!(
+        config,
+        AgentConfig {
+            pid: Some(1),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_parse_disabled_tracing() {
+    let disabled = AgentConfig::from_announce_response(br#"{"tracing": {"disable": true}}"#).unwrap();
+    assert!(disabled.tracing_disabled);
+
+    let not_enabled = AgentConfig::from_announce_response(br#"{"tracing": {"enabled": false}}"#).unwrap();
+    assert!(not_enabled.tracing_disabled);
+}
+
+#[test]
+fn test_parse_legacy_extra_headers() {
+    let config = AgentConfig::from_announce_response(br#"{"extraHeaders": ["X-Legacy"]}"#).unwrap();
+
+    assert_eq!(config.extra_http_headers, vec!["x-legacy"]);
+}
+
+#[test]
+fn test_parse_tracing_filter() {
+    let body = json!({
+        "tracing": {
+            "filter": {
+                "exclude": [{
+                    "name": "Health checks",
+                    "attributes": [{ "key": "url.path", "values": ["/health"] }]
+                }]
+            }
+        }
+    });
+
+    let config = AgentConfig::from_announce_response(body.to_string().as_bytes()).unwrap();
+
+    let condition = FilterCondition::new("url.path", vec!["/health".to_string()], MatchType::Strict).unwrap();
+    assert_eq!(
+        config.span_filter,
+        Some(SpanFilter {
+            include: Vec::new(),
+            exclude: vec![FilterRule::new("Health checks", vec![condition])],
+        })
+    );
+}
+
+#[test]
+fn test_parse_invalid_announce_response() {
+    assert!(matches!(
+        AgentConfig::from_announce_response(b"not json"),
+        Err(AgentConfigError::Json(_))
+    ));
+    assert!(matches!(
+        AgentConfig::from_announce_response(br#"{"secrets": {"matcher": "fuzzy", "list": []}}"#),
+        Err(AgentConfigError::UnknownMatcher(_))
+    ));
+    assert!(matches!(
+        AgentConfig::from_announce_response(br#"{"tracing": {"filter": {"exclude": "all"}}}"#),
+        Err(AgentConfigError::Filter(_))
+    ));
+}
+
+#[test]
+fn test_span_category() {
+    let db = create_test_span_data("SELECT", vec![KeyValue::new("db.system", "postgresql")]);
+    let http = create_test_span_data("GET", vec![KeyValue::new("http.request.method", "GET")]);
+    let internal = create_test_span_data("compute", Vec::new());
+
+    assert_eq!(SpanCategory::of(&db), Some(SpanCategory::Databases));
+    assert_eq!(SpanCategory::of(&http), Some(SpanCategory::Protocols));
+    assert_eq!(SpanCategory::of(&internal), None);
+}
+
+#[tokio::test]
+async fn test_announces_once_and_applies_secrets() {
+    let server = start_agent(json!({
+        "pid": 1,
+        "agentUuid": "agent-1",
+        "secrets": { "matcher": "equals", "list": ["password"] }
+    }))
+    .await;
+    let exporter = build_exporter(&server, InstanaExporterOptions::default());
+
+    let span = create_test_span_data("login", vec![KeyValue::new("password", "hunter2")]);
+    exporter.export(vec![span.clone()]).await.unwrap();
+    exporter.export(vec![span]).await.unwrap();
+
+    let announces = requests_to(&server, ANNOUNCE_PATH).await;
+    assert_eq!(announces.len(), 1);
+    assert!(announces[0]["pid"].is_u64());
+
+    let payloads = requests_to(&server, RAWTRACE_PATH).await;
+    assert_eq!(payloads.len(), 2);
+    assert_eq!(attributes(&payloads[0])["password"], "<redacted>");
+    assert_eq!(
+        exporter.get_agent_config().unwrap().agent_uuid.as_deref(),
+        Some("agent-1")
+    );
+}
+
+#[tokio::test]
+async fn test_reloads_config_on_interval() {
+    let server = start_agent(json!({ "pid": 1 })).await;
+    let options = InstanaExporterOptions {
+        remote_config_interval: Duration::ZERO,
+        ..Default::default()
+    };
+    let exporter = build_exporter(&server, options);
+
+    exporter
+        .export(vec![create_test_span_data("compute", Vec::new())])
+        .await
+        .unwrap();
+    assert_eq!(requests_to(&server, RAWTRACE_PATH).await.len(), 1);
+
+    // The agent configuration changes while the agent keeps running
+    server.reset().await;
+    Mock::given(method("PUT"))
+        .and(path(ANNOUNCE_PATH))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "pid": 1, "tracing": { "disable": true } })),
+        )
+        .mount(&server)
+        .await;
+    exporter
+        .export(vec![create_test_span_data("compute", Vec::new())])
+        .await
+        .unwrap();
+
+    assert_eq!(requests_to(&server, ANNOUNCE_PATH).await.len(), 1);
+    assert!(requests_to(&server, RAWTRACE_PATH).await.is_empty());
+    assert!(exporter.get_agent_config().unwrap().tracing_disabled);
+}
+
+#[tokio::test]
+async fn test_agent_pid_is_sent_as_process() {
+    let server = start_agent(json!({ "pid": 4242 })).await;
+    let exporter = build_exporter(&server, InstanaExporterOptions::default());
+
+    exporter
+        .export(vec![create_test_span_data("compute", Vec::new())])
+        .await
+        .unwrap();
+
+    let payloads = requests_to(&server, RAWTRACE_PATH).await;
+    assert_eq!(payloads[0][0]["f"]["e"], 4242);
+}
+
+#[tokio::test]
+async fn test_local_secrets_take_precedence() {
+    let server = start_agent(json!({
+        "secrets": { "matcher": "equals", "list": ["password"] }
+    }))
+    .await;
+    let options = InstanaExporterOptions {
+        secrets: Secrets::new(SecretsMatcher::Equals, vec!["token".to_string()]).unwrap(),
+        ..Default::default()
+    };
+    let exporter = build_exporter(&server, options);
+
+    let span = create_test_span_data(
+        "login",
+        vec![
+            KeyValue::new("password", "hunter2"),
+            KeyValue::new("token", "abc"),
+        ],
+    );
+    exporter.export(vec![span]).await.unwrap();
+
+    let payloads = requests_to(&server, RAWTRACE_PATH).await;
+    assert_eq!(attributes(&payloads[0])["password"], "hunter2");
+    assert_eq!(attributes(&payloads[0])["token"], "<redacted>");
+}
+
+#[tokio::test]
+async fn test_agent_disables_tracing() {
+    let server = start_agent(json!({ "tracing": { "disable": true } })).await;
+    let exporter = build_exporter(&server, InstanaExporterOptions::default());
+
+    let result = exporter
+        .export(vec![create_test_span_data("compute", Vec::new())])
+        .await;
+
+    assert!(result.is_ok());
+    assert!(requests_to(&server, RAWTRACE_PATH).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_agent_disables_span_categories() {
+    let server = start_agent(json!({
+        "tracing": { "disable": [{ "databases": true }] }
+    }))
+    .await;
+    let exporter = build_exporter(&server, InstanaExporterOptions::default());
+
+    exporter
+        .export(vec![
+            create_test_span_data("SELECT", vec![KeyValue::new("db.system", "postgresql")]),
+            create_test_span_data("GET", vec![KeyValue::new("http.request.method", "GET")]),
+        ])
+        .await
+        .unwrap();
+
+    let payloads = requests_to(&server, RAWTRACE_PATH).await;
+    let spans = payloads[0].as_array().unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["data"]["sdk"]["name"], "GET");
+}
+
+#[tokio::test]
+async fn test_extra_http_headers_from_agent() {
+    let server = start_agent(json!({
+        "tracing": { "extra-http-headers": ["X-Request-Id"] }
+    }))
+    .await;
+    let exporter = build_exporter(&server, InstanaExporterOptions::default());
+    assert!(exporter.get_extra_http_headers().is_empty());
+
+    exporter
+        .export(vec![create_test_span_data(
+            "GET",
+            vec![
+                KeyValue::new("http.request.header.x-request-id", "42"),
+                KeyValue::new("http.request.header.cookie", "session=1"),
+                KeyValue::new("http.response.header.content-type", "text/plain"),
+                KeyValue::new("url.path", "/"),
+            ],
+        )])
+        .await
+        .unwrap();
+
+    assert_eq!(exporter.get_extra_http_headers(), vec!["x-request-id"]);
+    let payloads = requests_to(&server, RAWTRACE_PATH).await;
+    let attributes = attributes(&payloads[0]).as_object().unwrap();
+    let mut keys: Vec<&str> = attributes.keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, ["http.request.header.x-request-id", "url.path"]);
+}
+
+#[tokio::test]
+async fn test_agent_filter_is_merged_with_local_filter() {
+    let server = start_agent(json!({
+        "tracing": {
+            "filter": {
+                "exclude": [{
+                    "name": "Metrics",
+                    "attributes": [{ "key": "name", "values": ["metrics"] }]
+                }]
+            }
+        }
+    }))
+    .await;
+    let local_filter = SpanFilter::from_yaml(
+        r#"
+exclude:
+  - name: Health checks
+    attributes:
+      - key: name
+        values: [health]
+"#,
+    )
+    .unwrap();
+    let options = InstanaExporterOptions {
+        span_filter: Some(local_filter),
+        ..Default::default()
+    };
+    let exporter = build_exporter(&server, options);
+
+    exporter
+        .export(vec![
+            create_test_span_data("health", Vec::new()),
+            create_test_span_data("metrics", Vec::new()),
+            create_test_span_data("compute", Vec::new()),
+        ])
+        .await
+        .unwrap();
+
+    let payloads = requests_to(&server, RAWTRACE_PATH).await;
+    let spans = payloads[0].as_array().unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["data"]["sdk"]["name"], "compute");
+    assert_eq!(
+        exporter.get_filtered_span_counts(),
+        vec![("Health checks".to_string(), 1), ("Metrics".to_string(), 1)]
+    );
+}
+
+#[tokio::test]
+async fn test_failed_announce_keeps_exporting() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(RAWTRACE_PATH))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+    let exporter = build_exporter(&server, InstanaExporterOptions::default());
+
+    exporter
+        .export(vec![create_test_span_data("compute", Vec::new())])
+        .await
+        .unwrap();
+
+    assert_eq!(exporter.get_agent_config(), None);
+    assert_eq!(requests_to(&server, RAWTRACE_PATH).await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_no_announce_without_remote_config() {
+    let server = start_agent(json!({ "tracing": { "disable": true } })).await;
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}{}", server.uri(), RAWTRACE_PATH)).unwrap();
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    exporter
+        .export(vec![create_test_span_data("compute", Vec::new())])
+        .await
+        .unwrap();
+
+    assert!(requests_to(&server, ANNOUNCE_PATH).await.is_empty());
+    assert_eq!(requests_to(&server, RAWTRACE_PATH).await.len(), 1);
+}
@@ -0,0 +1,208 @@
+use opentelemetry::trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState};
+use opentelemetry::InstrumentationScope;
+use opentelemetry_instana::exporter::agent_discovery::parse_default_gateway;
+use opentelemetry_instana::{AgentDiscovery, AgentHost, InstanaExporter, InstanaExporterOptions};
+use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanExporter, SpanLinks};
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const DOCKER_ROUTE_TABLE: &str = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t00000000\t010011AC\t0003\t0\t0\t0\t00000000\t0\t0\t0
+eth0\t000011AC\t00000000\t0001\t0\t0\t0\t0000FFFF\t0\t0\t0
+";
+
+const LOOPBACK_GATEWAY_ROUTE_TABLE: &str = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t00000000\t0100007F\t0003\t0\t0\t0\t00000000\t0\t0\t0
+";
+
+/// Write a fake routing table, unique per test
+fn write_route_table(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "instana-route-{}-{}",
+        std::process::id(),
+        name
+    ));
+    std::fs::write(&path, content).expect("failed to write route table");
+    path
+}
+
+async fn start_agent() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).insert_header("Server", "Instana Agent"))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Discovery of the agent at the default gateway, with an unreachable host probed first
+fn gateway_discovery(server: &MockServer, route_table: PathBuf) -> AgentDiscovery {
+    AgentDiscovery::default()
+        .with_candidates(vec![
+            AgentHost::Host("127.0.0.2".to_string()),
+            AgentHost::DefaultGateway,
+        ])
+        .with_port(server.address().port())
+        .with_route_table(route_table)
+}
+
+fn create_test_span_data() -> SpanData {
+    let time = SystemTime::now();
+
+    SpanData {
+        span_context: SpanContext::new(
+            TraceId::from_hex("0102030405060708090a0b0c0d0e0f10").unwrap(),
+            SpanId::from_hex("0102030405060708").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        ),
+        parent_span_id: SpanId::INVALID,
+        span_kind: SpanKind::Server,
+        name: std::borrow::Cow::Borrowed("test-span"),
+        start_time: time,
+        end_time: time,
+        attributes: Vec::new(),
+        dropped_attributes_count: 0,
+        events: SpanEvents::default(),
+        links: SpanLinks::default(),
+        status: Status::Ok,
+        instrumentation_scope: InstrumentationScope::builder("test-instrumentation").build(),
+    }
+}
+
+#[test]
+fn test_parse_default_gateway() {
+    assert_eq!(
+        parse_default_gateway(DOCKER_ROUTE_TABLE),
+        Some(Ipv4Addr::new(172, 17, 0, 1))
+    );
+}
+
+#[test]
+fn test_parse_default_gateway_without_default_route() {
+    let table = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t000011AC\t00000000\t0001\t0\t0\t0\t0000FFFF\t0\t0\t0
+";
+    assert_eq!(parse_default_gateway(table), None);
+    assert_eq!(parse_default_gateway(""), None);
+}
+
+#[test]
+fn test_candidate_hosts_from_env() {
+    let route_table = write_route_table("candidates", DOCKER_ROUTE_TABLE);
+
+    temp_env::with_vars(
+        [
+            ("INSTANA_AGENT_HOST", Some("agent.example.com")),
+            ("INSTANA_AGENT_PORT", None),
+        ],
+        || {
+            let discovery = AgentDiscovery::from_env()
+                .expect("failed to read discovery")
+                .with_route_table(&route_table);
+
+            assert_eq!(
+                discovery.candidate_hosts(),
+                ["agent.example.com", "localhost", "172.17.0.1"]
+            );
+        },
+    );
+
+    // A missing routing table only removes the default gateway
+    let discovery = AgentDiscovery::default().with_route_table("/nonexistent/route");
+    assert_eq!(discovery.candidate_hosts(), ["localhost"]);
+}
+
+#[tokio::test]
+async fn test_discovers_agent_at_default_gateway() {
+    let server = start_agent().await;
+    let route_table = write_route_table("gateway", LOOPBACK_GATEWAY_ROUTE_TABLE);
+    let discovery = gateway_discovery(&server, route_table);
+    let client = reqwest::Client::new();
+
+    let agent_url = discovery.agent_url(&client).await;
+
+    assert_eq!(
+        agent_url,
+        Some(format!("http://127.0.0.1:{}", server.address().port()))
+    );
+}
+
+#[tokio::test]
+async fn test_skips_hosts_without_agent_header() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).insert_header("Server", "nginx"))
+        .mount(&server)
+        .await;
+    let route_table = write_route_table("no-agent", LOOPBACK_GATEWAY_ROUTE_TABLE);
+    let discovery = gateway_discovery(&server, route_table);
+    let client = reqwest::Client::new();
+
+    assert_eq!(discovery.agent_url(&client).await, None);
+}
+
+#[tokio::test]
+async fn test_caches_and_reprobes_agent() {
+    let server = start_agent().await;
+    let route_table = write_route_table("cache", LOOPBACK_GATEWAY_ROUTE_TABLE);
+    let client = reqwest::Client::new();
+
+    // Cached until the re-probe interval elapsed
+    let discovery = gateway_discovery(&server, route_table.clone());
+    assert!(discovery.agent_url(&client).await.is_some());
+    server.reset().await;
+    assert!(discovery.agent_url(&client).await.is_some());
+
+    // Probed again once invalidated
+    discovery.invalidate();
+    assert_eq!(discovery.agent_url(&client).await, None);
+
+    // Probed again on every call without re-probe interval
+    let discovery =
+        gateway_discovery(&server, route_table).with_reprobe_interval(Duration::ZERO);
+    assert_eq!(discovery.agent_url(&client).await, None);
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).insert_header("Server", "Instana Agent"))
+        .mount(&server)
+        .await;
+    assert!(discovery.agent_url(&client).await.is_some());
+}
+
+#[tokio::test]
+async fn test_exporter_sends_spans_to_discovered_agent() {
+    let server = start_agent().await;
+    Mock::given(method("POST"))
+        .and(path("/com.instana.plugin.generic.rawtrace"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let route_table = write_route_table("export", LOOPBACK_GATEWAY_ROUTE_TABLE);
+
+    let options = InstanaExporterOptions {
+        endpoint: "http://127.0.0.2:1/com.instana.plugin.generic.rawtrace".to_string(),
+        agent_discovery: Some(gateway_discovery(&server, route_table)),
+        ..Default::default()
+    };
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    let result = exporter.export(vec![create_test_span_data()]).await;
+    assert!(result.is_ok());
+}
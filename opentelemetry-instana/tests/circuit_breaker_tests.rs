@@ -0,0 +1,358 @@
+use opentelemetry::trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState};
+use opentelemetry::InstrumentationScope;
+use opentelemetry_instana::exporter::metrics::{CIRCUIT_TRANSITIONS, SPAN_EXPORTED};
+use opentelemetry_instana::{
+    CircuitBreakerOptions, CircuitState, InstanaExporter, InstanaExporterOptions,
+};
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanExporter, SpanLinks};
+use std::time::{Duration, SystemTime};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const RAWTRACE_PATH: &str = "/com.instana.plugin.generic.rawtrace";
+
+fn create_test_span_data() -> SpanData {
+    let time = SystemTime::now();
+
+    SpanData {
+        span_context: SpanContext::new(
+            TraceId::from_hex("0102030405060708090a0b0c0d0e0f10").unwrap(),
+            SpanId::from_hex("0102030405060708").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        ),
+        parent_span_id: SpanId::INVALID,
+        span_kind: SpanKind::Server,
+        name: std::borrow::Cow::Borrowed("test-span"),
+        start_time: time,
+        end_time: time,
+        attributes: Vec::new(),
+        dropped_attributes_count: 0,
+        events: SpanEvents::default(),
+        links: SpanLinks::default(),
+        status: Status::Ok,
+        instrumentation_scope: InstrumentationScope::builder("test-instrumentation").build(),
+    }
+}
+
+async fn mount_rawtrace(server: &MockServer, status: u16) {
+    Mock::given(method("POST"))
+        .and(path(RAWTRACE_PATH))
+        .respond_with(ResponseTemplate::new(status))
+        .mount(server)
+        .await;
+}
+
+async fn mount_health(server: &MockServer, status: u16) {
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(status).insert_header("Server", "Instana Agent"))
+        .mount(server)
+        .await;
+}
+
+async fn health_requests(server: &MockServer) -> usize {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter(|request| request.method.as_str() == "GET")
+        .count()
+}
+
+async fn rawtrace_requests(server: &MockServer) -> usize {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter(|request| request.url.path() == RAWTRACE_PATH)
+        .count()
+}
+
+fn build_exporter(server: &MockServer, breaker: CircuitBreakerOptions) -> InstanaExporter {
+    let options = InstanaExporterOptions {
+        circuit_breaker: Some(breaker),
+        ..InstanaExporterOptions::with_endpoint(&format!("{}{}", server.uri(), RAWTRACE_PATH))
+            .unwrap()
+    };
+    InstanaExporter::builder()
+        .with_options(options)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter")
+}
+
+#[tokio::test]
+async fn test_circuit_opens_after_consecutive_failures() {
+    let server = MockServer::start().await;
+    mount_rawtrace(&server, 503).await;
+    let exporter = build_exporter(
+        &server,
+        CircuitBreakerOptions {
+            failure_threshold: 2,
+            cool_down: Duration::from_secs(3600),
+        },
+    );
+
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Closed));
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Open));
+
+    // Dropped without contacting the agent
+    let result = exporter.export(vec![create_test_span_data()]).await;
+    assert!(result.is_err());
+    assert_eq!(rawtrace_requests(&server).await, 2);
+}
+
+#[tokio::test]
+async fn test_success_resets_failures() {
+    let server = MockServer::start().await;
+    let exporter = build_exporter(
+        &server,
+        CircuitBreakerOptions {
+            failure_threshold: 2,
+            cool_down: Duration::from_secs(3600),
+        },
+    );
+
+    mount_rawtrace(&server, 503).await;
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+
+    server.reset().await;
+    mount_rawtrace(&server, 204).await;
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_ok());
+
+    server.reset().await;
+    mount_rawtrace(&server, 503).await;
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Closed));
+}
+
+#[tokio::test]
+async fn test_client_errors_do_not_open_circuit() {
+    let server = MockServer::start().await;
+    mount_rawtrace(&server, 400).await;
+    let exporter = build_exporter(
+        &server,
+        CircuitBreakerOptions {
+            failure_threshold: 1,
+            cool_down: Duration::from_secs(3600),
+        },
+    );
+
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Closed));
+    assert_eq!(rawtrace_requests(&server).await, 2);
+}
+
+#[tokio::test]
+async fn test_circuit_closes_when_agent_answers() {
+    let server = MockServer::start().await;
+    mount_rawtrace(&server, 503).await;
+    let exporter = build_exporter(
+        &server,
+        CircuitBreakerOptions {
+            failure_threshold: 1,
+            cool_down: Duration::ZERO,
+        },
+    );
+
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Open));
+
+    server.reset().await;
+    mount_health(&server, 200).await;
+    mount_rawtrace(&server, 204).await;
+
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_ok());
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Closed));
+    assert_eq!(health_requests(&server).await, 1);
+    assert_eq!(rawtrace_requests(&server).await, 1);
+}
+
+#[tokio::test]
+async fn test_circuit_stays_open_when_probe_fails() {
+    let server = MockServer::start().await;
+    mount_rawtrace(&server, 503).await;
+    mount_health(&server, 503).await;
+    let exporter = build_exporter(
+        &server,
+        CircuitBreakerOptions {
+            failure_threshold: 1,
+            cool_down: Duration::ZERO,
+        },
+    );
+
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+    assert_eq!(rawtrace_requests(&server).await, 1);
+
+    // The batch is dropped after the failed probe
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Open));
+    assert_eq!(health_requests(&server).await, 1);
+    assert_eq!(rawtrace_requests(&server).await, 1);
+}
+
+#[tokio::test]
+async fn test_cancelled_probe_does_not_block_circuit() {
+    let server = MockServer::start().await;
+    mount_rawtrace(&server, 503).await;
+    let exporter = build_exporter(
+        &server,
+        CircuitBreakerOptions {
+            failure_threshold: 1,
+            cool_down: Duration::ZERO,
+        },
+    );
+
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+
+    server.reset().await;
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .mount(&server)
+        .await;
+    let export = exporter.export(vec![create_test_span_data()]);
+    assert!(tokio::time::timeout(Duration::from_millis(100), export)
+        .await
+        .is_err());
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Open));
+
+    // The next export probes the agent again
+    server.reset().await;
+    mount_health(&server, 200).await;
+    mount_rawtrace(&server, 204).await;
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_ok());
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Closed));
+}
+
+#[tokio::test]
+async fn test_circuit_recovers_with_non_agent_endpoint() {
+    // A proxy which answers `GET /` with a 404
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/proxy/spans"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+    let options = InstanaExporterOptions {
+        circuit_breaker: Some(CircuitBreakerOptions {
+            failure_threshold: 1,
+            cool_down: Duration::ZERO,
+        }),
+        ..InstanaExporterOptions::with_endpoint(&format!("{}/proxy/spans", server.uri())).unwrap()
+    };
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Open));
+
+    server.reset().await;
+    Mock::given(method("POST"))
+        .and(path("/proxy/spans"))
+        .respond_with(ResponseTemplate::new(202))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_ok());
+    assert_eq!(exporter.get_circuit_state(), Some(CircuitState::Closed));
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_circuit_breaker_disabled() {
+    let server = MockServer::start().await;
+    mount_rawtrace(&server, 503).await;
+    // Disabled by default
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}{}", server.uri(), RAWTRACE_PATH)).unwrap();
+    assert_eq!(options.circuit_breaker, None);
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    for _ in 0..10 {
+        assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+    }
+    assert_eq!(exporter.get_circuit_state(), None);
+    assert_eq!(rawtrace_requests(&server).await, 10);
+}
+
+#[tokio::test]
+async fn test_circuit_breaker_metrics() {
+    let server = MockServer::start().await;
+    mount_rawtrace(&server, 503).await;
+
+    let metric_exporter = InMemoryMetricExporter::default();
+    let provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(metric_exporter.clone()).build())
+        .build();
+    let options = InstanaExporterOptions {
+        circuit_breaker: Some(CircuitBreakerOptions {
+            failure_threshold: 1,
+            cool_down: Duration::from_secs(3600),
+        }),
+        ..InstanaExporterOptions::with_endpoint(&format!("{}{}", server.uri(), RAWTRACE_PATH))
+            .unwrap()
+    };
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .with_meter_provider(&provider)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_err());
+
+    provider.force_flush().unwrap();
+    let metrics = metric_exporter.get_finished_metrics().unwrap();
+    let find = |name: &str| {
+        metrics
+            .iter()
+            .flat_map(|rm| rm.scope_metrics())
+            .flat_map(|sm| sm.metrics())
+            .find(|metric| metric.name() == name)
+            .unwrap_or_else(|| panic!("metric {name} not recorded"))
+            .data()
+    };
+
+    match find(CIRCUIT_TRANSITIONS) {
+        AggregatedMetrics::U64(MetricData::Sum(sum)) => {
+            let point = sum.data_points().next().unwrap();
+            assert_eq!(point.value(), 1);
+            assert!(point
+                .attributes()
+                .any(|kv| kv.key.as_str() == "state" && kv.value.as_str() == "open"));
+        }
+        other => panic!("unexpected data {other:?}"),
+    }
+
+    match find(SPAN_EXPORTED) {
+        AggregatedMetrics::U64(MetricData::Sum(sum)) => {
+            assert!(sum.data_points().any(|point| point.value() == 1
+                && point
+                    .attributes()
+                    .any(|kv| kv.key.as_str() == "error.type" && kv.value.as_str() == "circuit_open")));
+        }
+        other => panic!("unexpected data {other:?}"),
+    }
+}
@@ -0,0 +1,270 @@
+use opentelemetry::baggage::BaggageExt;
+use opentelemetry::propagation::text_map_propagator::FieldIter;
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{
+    Span, SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer,
+    TracerProvider,
+};
+use opentelemetry::Context;
+use opentelemetry_instana::propagator::{
+    CompositePropagator, ExtractedContext, ExtractionPolicy, InboundPropagation, InjectionPolicy,
+};
+use opentelemetry_instana::InstanaPropagator;
+use opentelemetry_sdk::propagation::{BaggagePropagator, TraceContextPropagator};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const INSTANA_TRACE_ID: &str = "00000000000000000000000000000abc";
+const W3C_TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+fn composite() -> CompositePropagator {
+    CompositePropagator::new(vec![
+        Box::new(InstanaPropagator::new()),
+        Box::new(TraceContextPropagator::new()),
+        Box::new(BaggagePropagator::new()),
+    ])
+}
+
+fn both_headers() -> HashMap<String, String> {
+    HashMap::from([
+        ("x-instana-t".to_string(), "abc".to_string()),
+        ("x-instana-s".to_string(), "0000000000000def".to_string()),
+        ("x-instana-l".to_string(), "1".to_string()),
+        (
+            "traceparent".to_string(),
+            format!("00-{W3C_TRACE_ID}-00f067aa0ba902b7-01"),
+        ),
+    ])
+}
+
+fn extracted_trace_id(cx: &Context) -> String {
+    cx.span().span_context().trace_id().to_string()
+}
+
+#[test]
+fn test_names_and_fields() {
+    let propagator = composite();
+
+    assert_eq!(propagator.names(), vec!["x-instana-t", "traceparent", "baggage"]);
+    assert_eq!(
+        propagator.fields().collect::<Vec<_>>(),
+        vec!["X-INSTANA-T", "X-INSTANA-S", "X-INSTANA-L", "traceparent", "tracestate", "baggage"]
+    );
+}
+
+#[test]
+fn test_last_valid_is_the_default() {
+    let cx = composite().extract(&both_headers());
+
+    assert_eq!(extracted_trace_id(&cx), W3C_TRACE_ID);
+    let inbound = InboundPropagation::from_context(&cx).unwrap();
+    assert_eq!(inbound.source.as_deref(), Some("traceparent"));
+    assert_eq!(inbound.seen, vec!["x-instana-t", "traceparent"]);
+}
+
+#[test]
+fn test_first_valid() {
+    let propagator = composite().with_extraction_policy(ExtractionPolicy::FirstValid);
+
+    let cx = propagator.extract(&both_headers());
+
+    assert_eq!(extracted_trace_id(&cx), INSTANA_TRACE_ID);
+    assert!(cx.span().span_context().is_remote());
+    let inbound = InboundPropagation::from_context(&cx).unwrap();
+    assert_eq!(inbound.source.as_deref(), Some("x-instana-t"));
+}
+
+#[test]
+fn test_invalid_context_is_skipped() {
+    let propagator = composite().with_extraction_policy(ExtractionPolicy::FirstValid);
+    let mut headers = both_headers();
+    headers.insert("x-instana-t".to_string(), "not hex".to_string());
+
+    let cx = propagator.extract(&headers);
+
+    assert_eq!(extracted_trace_id(&cx), W3C_TRACE_ID);
+    let inbound = InboundPropagation::from_context(&cx).unwrap();
+    assert_eq!(inbound.source.as_deref(), Some("traceparent"));
+    assert_eq!(inbound.seen, vec!["x-instana-t", "traceparent"]);
+}
+
+#[test]
+fn test_custom_resolver() {
+    // Prefer the Instana context, unless it has a 64 bit trace ID
+    let resolver = |candidates: &[ExtractedContext]| {
+        candidates
+            .iter()
+            .position(|candidate| {
+                candidate.propagator == "x-instana-t"
+                    && u128::from_be_bytes(candidate.span_context.trace_id().to_bytes()) > u64::MAX as u128
+            })
+            .or(Some(candidates.len() - 1))
+    };
+    let propagator =
+        composite().with_extraction_policy(ExtractionPolicy::Custom(Arc::new(resolver)));
+
+    let cx = propagator.extract(&both_headers());
+    assert_eq!(extracted_trace_id(&cx), W3C_TRACE_ID);
+
+    let mut headers = both_headers();
+    headers.insert("x-instana-t".to_string(), "1".repeat(32));
+    let cx = propagator.extract(&headers);
+    assert_eq!(extracted_trace_id(&cx), "1".repeat(32));
+}
+
+#[test]
+fn test_custom_resolver_without_choice_keeps_current_span() {
+    let propagator = composite().with_extraction_policy(ExtractionPolicy::Custom(Arc::new(|_| None)));
+    let current = SpanContext::new(
+        TraceId::from_hex("ff000000000000000000000000000001").unwrap(),
+        SpanId::from_hex("ff00000000000001").unwrap(),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    );
+    let cx = Context::new().with_remote_span_context(current.clone());
+
+    let extracted = propagator.extract_with_context(&cx, &both_headers());
+
+    assert_eq!(extracted.span().span_context(), &current);
+    assert_eq!(InboundPropagation::from_context(&extracted).unwrap().source, None);
+}
+
+#[test]
+fn test_unresolved_extraction_keeps_active_span() {
+    let propagator = composite().with_extraction_policy(ExtractionPolicy::Custom(Arc::new(|_| None)));
+    let provider = SdkTracerProvider::builder().build();
+    let span = provider.tracer("test").start("handle request");
+    let current = span.span_context().clone();
+    let cx = Context::current_with_span(span);
+
+    let extracted = propagator.extract_with_context(&cx, &both_headers());
+
+    assert!(extracted.span().is_recording());
+    assert_eq!(extracted.span().span_context(), &current);
+    assert!(InboundPropagation::from_context(&extracted).is_some());
+}
+
+/// Propagator counting its extractions
+#[derive(Debug)]
+struct CountingPropagator {
+    inner: TraceContextPropagator,
+    extractions: Arc<AtomicUsize>,
+}
+
+impl TextMapPropagator for CountingPropagator {
+    fn inject_context(&self, cx: &Context, injector: &mut dyn opentelemetry::propagation::Injector) {
+        self.inner.inject_context(cx, injector)
+    }
+
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        self.extractions.fetch_add(1, Ordering::Relaxed);
+        self.inner.extract_with_context(cx, extractor)
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        self.inner.fields()
+    }
+}
+
+#[test]
+fn test_propagators_extract_once() {
+    let extractions = Arc::new(AtomicUsize::new(0));
+    let propagator = CompositePropagator::new(vec![Box::new(InstanaPropagator::new())]).with_propagator(
+        "traceparent",
+        CountingPropagator {
+            inner: TraceContextPropagator::new(),
+            extractions: extractions.clone(),
+        },
+    );
+
+    let cx = propagator.extract(&both_headers());
+
+    assert_eq!(extracted_trace_id(&cx), W3C_TRACE_ID);
+    assert_eq!(extractions.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_baggage_is_extracted() {
+    let propagator = composite().with_extraction_policy(ExtractionPolicy::FirstValid);
+    let mut headers = both_headers();
+    headers.insert("baggage".to_string(), "tenant=acme".to_string());
+
+    let cx = propagator.extract(&headers);
+
+    assert_eq!(extracted_trace_id(&cx), INSTANA_TRACE_ID);
+    assert_eq!(cx.baggage().get("tenant").map(|value| value.as_str()), Some("acme"));
+    let inbound = InboundPropagation::from_context(&cx).unwrap();
+    assert_eq!(inbound.seen, vec!["x-instana-t", "traceparent", "baggage"]);
+}
+
+#[test]
+fn test_no_headers() {
+    let cx = composite().extract(&HashMap::new());
+
+    assert!(!cx.span().span_context().is_valid());
+    assert!(InboundPropagation::from_context(&cx).is_none());
+}
+
+#[test]
+fn test_inject_all_by_default() {
+    let propagator = composite();
+    let mut inbound = both_headers();
+    inbound.remove("traceparent");
+    let cx = propagator.extract(&inbound);
+
+    let mut headers = HashMap::new();
+    propagator.inject_context(&cx, &mut headers);
+
+    assert!(headers.contains_key("x-instana-t"));
+    assert!(headers.contains_key("traceparent"));
+}
+
+#[test]
+fn test_inject_seen_inbound() {
+    let propagator = composite().with_injection_policy(InjectionPolicy::SeenInbound);
+    let mut inbound = both_headers();
+    inbound.remove("traceparent");
+    let cx = propagator.extract(&inbound);
+
+    let mut headers = HashMap::new();
+    propagator.inject_context(&cx, &mut headers);
+
+    assert_eq!(headers.get("x-instana-t").map(String::as_str), Some("0000000000000abc"));
+    assert!(!headers.contains_key("traceparent"));
+}
+
+#[test]
+fn test_inject_seen_inbound_without_inbound_request() {
+    let propagator = composite().with_injection_policy(InjectionPolicy::SeenInbound);
+    let cx = Context::new().with_remote_span_context(SpanContext::new(
+        TraceId::from_hex(W3C_TRACE_ID).unwrap(),
+        SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    ));
+
+    let mut headers = HashMap::new();
+    propagator.inject_context(&cx, &mut headers);
+
+    assert!(headers.contains_key("x-instana-t"));
+    assert!(headers.contains_key("traceparent"));
+}
+
+#[test]
+fn test_with_propagator() {
+    let propagator = CompositePropagator::default()
+        .with_propagator("w3c", TraceContextPropagator::new())
+        .with_propagator("instana", InstanaPropagator::new())
+        .with_extraction_policy(ExtractionPolicy::FirstValid);
+
+    let cx = propagator.extract(&both_headers());
+
+    assert_eq!(propagator.names(), vec!["w3c", "instana"]);
+    assert_eq!(extracted_trace_id(&cx), W3C_TRACE_ID);
+    let inbound = InboundPropagation::from_context(&cx).unwrap();
+    assert_eq!(inbound.source.as_deref(), Some("w3c"));
+}
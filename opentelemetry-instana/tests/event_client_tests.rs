@@ -0,0 +1,147 @@
+use opentelemetry_instana::event::EventError;
+use opentelemetry_instana::{CustomEvent, InstanaEventClient, InstanaExporterOptions, Severity};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime};
+use wiremock::{
+    matchers::{header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn build_client(endpoint: &str) -> InstanaEventClient {
+    InstanaEventClient::builder()
+        .with_endpoint(endpoint)
+        .with_http_client(reqwest::Client::builder().build().unwrap_or_default())
+        .build()
+        .expect("failed to build event client")
+}
+
+#[test]
+fn test_custom_event_serialization() {
+    let event = CustomEvent::new("Deployment", "Release 1.2.3 rolled out")
+        .with_timestamp(SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_000))
+        .with_duration(Duration::from_secs(60))
+        .with_host("host-1");
+
+    let json_value = serde_json::to_value(&event).unwrap();
+
+    assert_eq!(
+        json_value,
+        json!({
+            "title": "Deployment",
+            "text": "Release 1.2.3 rolled out",
+            "severity": -1,
+            "timestamp": 1_700_000_000_000_u64,
+            "duration": 60000,
+            "host": "host-1",
+        })
+    );
+}
+
+#[test]
+fn test_custom_event_severity_and_entity() {
+    let event = CustomEvent::new("Feature flag flipped", "checkout-v2 enabled")
+        .with_severity(Severity::Critical)
+        .with_entity("com.instana.plugin.process", "1234");
+
+    let json_value = serde_json::to_value(&event).unwrap();
+
+    assert_eq!(json_value["severity"], 10);
+    assert_eq!(json_value["plugin"], "com.instana.plugin.process");
+    assert_eq!(json_value["id"], "1234");
+    assert!(json_value.get("timestamp").is_none());
+    assert!(json_value.get("host").is_none());
+
+    let json_value = serde_json::to_value(event.with_severity(Severity::Warning)).unwrap();
+    assert_eq!(json_value["severity"], 5);
+}
+
+#[test]
+fn test_event_client_endpoint_from_exporter_options() {
+    let options =
+        InstanaExporterOptions::with_endpoint("http://agent:1234/com.instana.plugin.generic.rawtrace")
+            .unwrap();
+
+    let client = InstanaEventClient::builder()
+        .with_options(&options)
+        .with_http_client(reqwest::Client::builder().build().unwrap_or_default())
+        .build()
+        .expect("failed to build event client");
+
+    assert_eq!(
+        client.endpoint(),
+        "http://agent:1234/com.instana.plugin.generic.event"
+    );
+}
+
+#[test]
+fn test_event_client_default_endpoint_from_env() {
+    temp_env::with_vars(
+        [
+            ("INSTANA_AGENT_HOST", Some("agent-host")),
+            ("INSTANA_AGENT_PORT", Some("4242")),
+        ],
+        || {
+            let client = InstanaEventClient::builder()
+                .with_http_client(reqwest::Client::builder().build().unwrap_or_default())
+                .build()
+                .expect("failed to build event client");
+
+            assert_eq!(
+                client.endpoint(),
+                "http://agent-host:4242/com.instana.plugin.generic.event"
+            );
+        },
+    );
+}
+
+#[tokio::test]
+async fn test_event_client_send_success() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/com.instana.plugin.generic.event"))
+        .and(header("content-type", "application/json"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = build_client(&format!(
+        "{}/com.instana.plugin.generic.event",
+        mock_server.uri()
+    ));
+
+    let event = CustomEvent::new("Deployment", "Release 1.2.3 rolled out")
+        .with_severity(Severity::Warning);
+    client.send(&event).await.expect("failed to send event");
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["title"], "Deployment");
+    assert_eq!(body["text"], "Release 1.2.3 rolled out");
+    assert_eq!(body["severity"], 5);
+}
+
+#[tokio::test]
+async fn test_event_client_send_server_error() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&mock_server)
+        .await;
+
+    let client = build_client(&format!(
+        "{}/com.instana.plugin.generic.event",
+        mock_server.uri()
+    ));
+
+    let result = client.send(&CustomEvent::new("title", "text")).await;
+
+    // Depending on the client, the status is reported by the client or by the response
+    match result {
+        Err(EventError::HttpStatus { status, .. }) => assert_eq!(status, 500),
+        Err(EventError::Request(msg)) => assert!(msg.contains("500")),
+        other => panic!("Expected an error, got {other:?}"),
+    }
+}
@@ -0,0 +1,241 @@
+use opentelemetry::trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_instana::exporter::metrics::{
+    OPERATION_DURATION, PAYLOAD_SIZE, SPAN_EXPORTED, SPAN_INFLIGHT,
+};
+use opentelemetry_instana::{InstanaExporter, InstanaExporterOptions};
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, Metric, MetricData, ResourceMetrics};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanExporter, SpanLinks};
+use std::time::{Duration, SystemTime};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn create_test_span_data() -> SpanData {
+    let time = SystemTime::now();
+
+    SpanData {
+        span_context: SpanContext::new(
+            TraceId::from_hex("0102030405060708090a0b0c0d0e0f10").unwrap(),
+            SpanId::from_hex("0102030405060708").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        ),
+        parent_span_id: SpanId::INVALID,
+        span_kind: SpanKind::Server,
+        name: std::borrow::Cow::Borrowed("test-span"),
+        start_time: time,
+        end_time: time,
+        attributes: Vec::new(),
+        dropped_attributes_count: 0,
+        events: SpanEvents::default(),
+        links: SpanLinks::default(),
+        status: Status::Ok,
+        instrumentation_scope: InstrumentationScope::builder("test-instrumentation").build(),
+    }
+}
+
+fn meter_provider() -> (SdkMeterProvider, InMemoryMetricExporter) {
+    let exporter = InMemoryMetricExporter::default();
+    let provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(exporter.clone()).build())
+        .build();
+    (provider, exporter)
+}
+
+/// Export two spans to an agent answering with `status`
+async fn export_with_status(status: u16) -> (SdkMeterProvider, InMemoryMetricExporter, bool) {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/test-path"))
+        .respond_with(ResponseTemplate::new(status))
+        .mount(&mock_server)
+        .await;
+
+    let (provider, metric_exporter) = meter_provider();
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}/test-path", mock_server.uri())).unwrap();
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .with_meter_provider(&provider)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    let result = exporter
+        .export(vec![create_test_span_data(), create_test_span_data()])
+        .await;
+
+    (provider, metric_exporter, result.is_ok())
+}
+
+fn collect(provider: &SdkMeterProvider, exporter: &InMemoryMetricExporter) -> Vec<ResourceMetrics> {
+    provider.force_flush().expect("failed to flush metrics");
+    exporter.get_finished_metrics().expect("failed to get metrics")
+}
+
+fn find_metric<'a>(metrics: &'a [ResourceMetrics], name: &str) -> &'a Metric {
+    metrics
+        .iter()
+        .flat_map(|rm| rm.scope_metrics())
+        .flat_map(|sm| sm.metrics())
+        .find(|metric| metric.name() == name)
+        .unwrap_or_else(|| panic!("metric {name} not recorded"))
+}
+
+fn attribute<'a>(attributes: impl Iterator<Item = &'a KeyValue>, key: &str) -> Option<String> {
+    attributes
+        .into_iter()
+        .find(|kv| kv.key.as_str() == key)
+        .map(|kv| kv.value.to_string())
+}
+
+/// Sum of the exported spans and their `error.type`
+fn exported_spans(metrics: &[ResourceMetrics]) -> (u64, Option<String>) {
+    match find_metric(metrics, SPAN_EXPORTED).data() {
+        AggregatedMetrics::U64(MetricData::Sum(sum)) => {
+            let point = sum.data_points().next().expect("no data point");
+            (point.value(), attribute(point.attributes(), "error.type"))
+        }
+        other => panic!("unexpected data {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_metrics_of_successful_export() {
+    let (provider, metric_exporter, ok) = export_with_status(204).await;
+    assert!(ok);
+
+    let metrics = collect(&provider, &metric_exporter);
+
+    assert_eq!(exported_spans(&metrics), (2, None));
+
+    match find_metric(&metrics, SPAN_INFLIGHT).data() {
+        AggregatedMetrics::I64(MetricData::Sum(sum)) => {
+            let point = sum.data_points().next().unwrap();
+            assert_eq!(point.value(), 0);
+            assert_eq!(
+                attribute(point.attributes(), "otel.component.type").as_deref(),
+                Some("instana_span_exporter")
+            );
+            assert!(attribute(point.attributes(), "otel.component.name")
+                .unwrap()
+                .starts_with("instana_span_exporter/"));
+        }
+        other => panic!("unexpected data {other:?}"),
+    }
+
+    match find_metric(&metrics, PAYLOAD_SIZE).data() {
+        AggregatedMetrics::U64(MetricData::Histogram(histogram)) => {
+            let point = histogram.data_points().next().unwrap();
+            assert_eq!(point.count(), 1);
+            assert!(point.sum() > 0);
+        }
+        other => panic!("unexpected data {other:?}"),
+    }
+
+    let duration = find_metric(&metrics, OPERATION_DURATION);
+    assert_eq!(duration.unit(), "s");
+    match duration.data() {
+        AggregatedMetrics::F64(MetricData::Histogram(histogram)) => {
+            assert_eq!(histogram.data_points().next().unwrap().count(), 1);
+        }
+        other => panic!("unexpected data {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_metrics_of_rejected_export() {
+    let (provider, metric_exporter, ok) = export_with_status(503).await;
+    assert!(!ok);
+
+    let metrics = collect(&provider, &metric_exporter);
+
+    assert_eq!(exported_spans(&metrics), (2, Some("http_status".to_string())));
+
+    match find_metric(&metrics, OPERATION_DURATION).data() {
+        AggregatedMetrics::F64(MetricData::Histogram(histogram)) => {
+            let point = histogram.data_points().next().unwrap();
+            assert_eq!(
+                attribute(point.attributes(), "http.response.status_code").as_deref(),
+                Some("503")
+            );
+        }
+        other => panic!("unexpected data {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_cancelled_export_is_not_inflight() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/test-path"))
+        .respond_with(ResponseTemplate::new(204).set_delay(Duration::from_secs(5)))
+        .mount(&mock_server)
+        .await;
+
+    let (provider, metric_exporter) = meter_provider();
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}/test-path", mock_server.uri())).unwrap();
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .with_meter_provider(&provider)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    // Dropped like the export of a batch span processor timing out
+    let export = exporter.export(vec![create_test_span_data(), create_test_span_data()]);
+    assert!(tokio::time::timeout(Duration::from_millis(100), export)
+        .await
+        .is_err());
+
+    let metrics = collect(&provider, &metric_exporter);
+    match find_metric(&metrics, SPAN_INFLIGHT).data() {
+        AggregatedMetrics::I64(MetricData::Sum(sum)) => {
+            assert_eq!(sum.data_points().next().unwrap().value(), 0);
+        }
+        other => panic!("unexpected data {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_metrics_of_failed_connection() {
+    let (provider, metric_exporter) = meter_provider();
+    // Nothing listens on port 1
+    let options = InstanaExporterOptions::with_endpoint("http://127.0.0.1:1/test-path").unwrap();
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .with_meter_provider(&provider)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    let result = exporter.export(vec![create_test_span_data()]).await;
+    assert!(result.is_err());
+
+    let metrics = collect(&provider, &metric_exporter);
+    assert_eq!(exported_spans(&metrics), (1, Some("connection".to_string())));
+}
+
+#[tokio::test]
+async fn test_no_metrics_without_meter_provider() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&mock_server)
+        .await;
+
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}/test-path", mock_server.uri())).unwrap();
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    assert!(exporter.export(vec![create_test_span_data()]).await.is_ok());
+}
@@ -0,0 +1,250 @@
+use opentelemetry::trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_instana::exporter::{serialize_span, BuildError};
+use opentelemetry_instana::{
+    InstanaExporter, InstanaExporterOptions, LogLevel, Secrets, SecretsMatcher,
+};
+use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanExporter, SpanLinks};
+use opentelemetry_sdk::Resource;
+use std::time::{Duration, SystemTime};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const INSTANA_VARS: [&str; 11] = [
+    "INSTANA_AGENT_HOST",
+    "INSTANA_AGENT_PORT",
+    "INSTANA_SERVICE_NAME",
+    "INSTANA_TIMEOUT",
+    "INSTANA_DISABLE_TRACING",
+    "INSTANA_SECRETS",
+    "INSTANA_EXTRA_HTTP_HEADERS",
+    "INSTANA_DEBUG",
+    "INSTANA_LOG_LEVEL",
+    "INSTANA_TRACING_FILTER",
+    "INSTANA_CONFIG_PATH",
+];
+
+/// Run `f` with only the given Instana variables set
+fn with_instana_env<R>(vars: &[(&str, &str)], f: impl FnOnce() -> R) -> R {
+    let vars: Vec<(&str, Option<&str>)> = INSTANA_VARS
+        .iter()
+        .map(|name| {
+            let value = vars.iter().find(|(n, _)| n == name).map(|(_, v)| *v);
+            (*name, value)
+        })
+        .collect();
+    temp_env::with_vars(vars, f)
+}
+
+fn create_test_span_data(attributes: Vec<KeyValue>) -> SpanData {
+    let time = SystemTime::now();
+
+    SpanData {
+        span_context: SpanContext::new(
+            TraceId::from_hex("0102030405060708090a0b0c0d0e0f10").unwrap(),
+            SpanId::from_hex("0102030405060708").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        ),
+        parent_span_id: SpanId::INVALID,
+        span_kind: SpanKind::Server,
+        name: std::borrow::Cow::Borrowed("test-span"),
+        start_time: time,
+        end_time: time,
+        attributes,
+        dropped_attributes_count: 0,
+        events: SpanEvents::default(),
+        links: SpanLinks::default(),
+        status: Status::Ok,
+        instrumentation_scope: InstrumentationScope::builder("test-instrumentation").build(),
+    }
+}
+
+#[test]
+fn test_options_default_ignores_env() {
+    with_instana_env(
+        &[("INSTANA_AGENT_HOST", "remote-agent"), ("INSTANA_SERVICE_NAME", "env-service")],
+        || {
+            let options = InstanaExporterOptions::default();
+
+            assert_eq!(
+                options.endpoint,
+                "http://localhost:42699/com.instana.plugin.generic.rawtrace"
+            );
+            assert_eq!(options.service, String::new());
+        },
+    );
+}
+
+#[test]
+fn test_options_from_env_without_variables() {
+    with_instana_env(&[], || {
+        let options = InstanaExporterOptions::from_env().expect("failed to read options");
+
+        assert_eq!(options, InstanaExporterOptions::default());
+    });
+}
+
+#[test]
+fn test_options_from_env() {
+    with_instana_env(
+        &[
+            ("INSTANA_AGENT_HOST", "remote-agent"),
+            ("INSTANA_AGENT_PORT", "4242"),
+            ("INSTANA_SERVICE_NAME", "env-service"),
+            ("INSTANA_TIMEOUT", "2500"),
+            ("INSTANA_DISABLE_TRACING", "true"),
+            ("INSTANA_SECRETS", "equals-ignore-case:password, token"),
+            ("INSTANA_EXTRA_HTTP_HEADERS", "X-Request-Id; X-Tenant"),
+            ("INSTANA_LOG_LEVEL", "warn"),
+        ],
+        || {
+            let options = InstanaExporterOptions::from_env().expect("failed to read options");
+
+            assert_eq!(
+                options.endpoint,
+                "http://remote-agent:4242/com.instana.plugin.generic.rawtrace"
+            );
+            assert_eq!(options.service, "env-service");
+            assert_eq!(options.timeout, Some(Duration::from_millis(2500)));
+            assert!(options.disabled);
+            assert_eq!(options.secrets.matcher(), SecretsMatcher::EqualsIgnoreCase);
+            assert_eq!(options.secrets.list(), ["password", "token"]);
+            assert_eq!(options.extra_http_headers, ["x-request-id", "x-tenant"]);
+            assert_eq!(options.log_level, LogLevel::Warn);
+        },
+    );
+}
+
+#[test]
+fn test_options_from_env_debug_overrides_log_level() {
+    with_instana_env(&[("INSTANA_LOG_LEVEL", "error"), ("INSTANA_DEBUG", "1")], || {
+        let options = InstanaExporterOptions::from_env().expect("failed to read options");
+
+        assert_eq!(options.log_level, LogLevel::Debug);
+    });
+}
+
+#[test]
+fn test_log_level_enabled() {
+    assert!(LogLevel::Warn.enabled(LogLevel::Error));
+    assert!(LogLevel::Warn.enabled(LogLevel::Warn));
+    assert!(!LogLevel::Warn.enabled(LogLevel::Info));
+    assert!(LogLevel::Debug.enabled(LogLevel::Debug));
+    assert!(!LogLevel::default().enabled(LogLevel::Debug));
+}
+
+#[test]
+fn test_options_from_env_invalid_values() {
+    let cases = [
+        ("INSTANA_AGENT_PORT", "not-a-port"),
+        ("INSTANA_AGENT_PORT", "70000"),
+        ("INSTANA_TIMEOUT", "-5"),
+        ("INSTANA_DISABLE_TRACING", "maybe"),
+        ("INSTANA_SECRETS", "password"),
+        ("INSTANA_SECRETS", "starts-with:password"),
+        ("INSTANA_SECRETS", "regex:pass(word"),
+        ("INSTANA_EXTRA_HTTP_HEADERS", "X Request Id"),
+        ("INSTANA_DEBUG", "verbose"),
+        ("INSTANA_LOG_LEVEL", "chatty"),
+        ("INSTANA_TRACING_FILTER", "exclude: [{attributes: []}]"),
+        ("INSTANA_CONFIG_PATH", "/nonexistent/instana.yaml"),
+    ];
+
+    for (variable, value) in cases {
+        with_instana_env(&[(variable, value)], || {
+            match InstanaExporterOptions::from_env() {
+                Err(BuildError::InvalidEnvVar { name, value: v, .. }) => {
+                    assert_eq!(name, variable);
+                    assert_eq!(v, value);
+                }
+                other => panic!("expected an error for {variable}={value}, got {other:?}"),
+            }
+        });
+    }
+}
+
+#[test]
+fn test_secrets_matchers() {
+    let list = vec!["pass".to_string(), "api_key".to_string()];
+
+    let equals = Secrets::new(SecretsMatcher::Equals, list.clone()).unwrap();
+    assert!(equals.is_secret("pass"));
+    assert!(!equals.is_secret("PASS"));
+    assert!(!equals.is_secret("password"));
+
+    let equals_ignore_case = Secrets::new(SecretsMatcher::EqualsIgnoreCase, list.clone()).unwrap();
+    assert!(equals_ignore_case.is_secret("API_KEY"));
+    assert!(!equals_ignore_case.is_secret("password"));
+
+    let contains = Secrets::new(SecretsMatcher::Contains, list.clone()).unwrap();
+    assert!(contains.is_secret("db.password"));
+    assert!(!contains.is_secret("db.PASSWORD"));
+
+    let contains_ignore_case =
+        Secrets::new(SecretsMatcher::ContainsIgnoreCase, list.clone()).unwrap();
+    assert!(contains_ignore_case.is_secret("db.PASSWORD"));
+    assert!(!contains_ignore_case.is_secret("db.user"));
+
+    let regex = Secrets::new(SecretsMatcher::Regex, vec!["pass.*".to_string()]).unwrap();
+    assert!(regex.is_secret("password"));
+    assert!(!regex.is_secret("db.password"));
+
+    let none = Secrets::new(SecretsMatcher::None, list).unwrap();
+    assert!(!none.is_secret("pass"));
+}
+
+#[test]
+fn test_options_secrets_and_service_applied_on_serialization() {
+    let options = InstanaExporterOptions {
+        service: "options-service".to_string(),
+        secrets: Secrets::new(SecretsMatcher::ContainsIgnoreCase, vec!["password".to_string()])
+            .unwrap(),
+        ..Default::default()
+    };
+    let exporter = InstanaExporter::builder()
+        .with_service(Resource::builder().with_service_name("resource-service").build())
+        .with_options(options)
+        .build()
+        .expect("failed to build instana exporter");
+
+    let span = create_test_span_data(vec![
+        KeyValue::new("db.Password", "hunter2"),
+        KeyValue::new("db.user", "admin"),
+    ]);
+    let instana_span =
+        serialize_span::convert_to_instana_span(&exporter, &span).expect("failed to convert span");
+
+    assert_eq!(instana_span.data.service.as_deref(), Some("options-service"));
+
+    let attributes = instana_span.data.sdk.custom.tags.attributes.unwrap();
+    assert_eq!(attributes["db.Password"], "<redacted>");
+    assert_eq!(attributes["db.user"], "admin");
+}
+
+#[tokio::test]
+async fn test_disabled_exporter_sends_nothing() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/test-path"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let options = InstanaExporterOptions {
+        disabled: true,
+        ..InstanaExporterOptions::with_endpoint(&format!("{}/test-path", mock_server.uri())).unwrap()
+    };
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .build()
+        .expect("failed to build instana exporter");
+
+    let result = exporter.export(vec![create_test_span_data(Vec::new())]).await;
+    assert!(result.is_ok());
+}
@@ -278,3 +278,123 @@ fn test_instana_exporter_set_resource() {
     // Verify the resource was updated
     assert_eq!(exporter.get_resource(), new_resource);
 }
+
+#[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+#[tokio::test]
+async fn test_instana_exporter_builder_default_client_is_async_in_tokio() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/test-path"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}/test-path", mock_server.uri())).unwrap();
+
+    let exporter = InstanaExporter::builder()
+        .with_service(get_resource())
+        .with_options(options)
+        .build()
+        .expect("failed to build instana exporter");
+
+    let result = exporter.export(vec![create_test_span_data()]).await;
+    assert!(result.is_ok());
+}
+
+#[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_instana_exporter_builder_default_client_in_tokio() {
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/test-path"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}/test-path", mock_server.uri())).unwrap();
+
+    // Built within a tokio runtime, the exporter sends with the async client
+    // on that runtime from the thread of the batch span processor
+    let exporter = InstanaExporter::builder()
+        .with_service(get_resource())
+        .with_options(options)
+        .build()
+        .expect("failed to build instana exporter");
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .build();
+
+    tokio::task::spawn_blocking(move || {
+        provider.tracer("test").in_span("work", |_| {});
+        provider.shutdown().expect("failed to export the span");
+    })
+    .await
+    .unwrap();
+}
+
+#[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+#[tokio::test]
+async fn test_instana_exporter_builder_async_client() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/test-path"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}/test-path", mock_server.uri())).unwrap();
+
+    let exporter = InstanaExporter::builder()
+        .with_service(get_resource())
+        .with_options(options)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    let result = exporter.export(vec![create_test_span_data()]).await;
+    assert!(result.is_ok());
+}
+
+#[cfg(any(feature = "reqwest-client", feature = "hyper-client"))]
+#[tokio::test]
+async fn test_instana_exporter_builder_with_timeout() {
+    use std::time::Duration;
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/test-path"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .mount(&mock_server)
+        .await;
+
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}/test-path", mock_server.uri())).unwrap();
+
+    let exporter = InstanaExporter::builder()
+        .with_service(get_resource())
+        .with_options(options)
+        .with_async_http_client()
+        .with_timeout(Duration::from_millis(100))
+        .with_connect_timeout(Duration::from_millis(100))
+        .build()
+        .expect("failed to build instana exporter");
+
+    let start = std::time::Instant::now();
+    let result = exporter.export(vec![create_test_span_data()]).await;
+
+    assert!(result.is_err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
@@ -225,11 +225,14 @@ fn test_extract_uppercase_trace_id() {
     let cx = Context::current();
     let extracted_cx = propagator.extract_with_context(&cx, &extractor);
 
-    // When extraction fails, the original context is returned
-    // The original context has an invalid span context
     let span = extracted_cx.span();
     let sc = span.span_context();
-    assert!(!sc.is_valid());
+    assert!(sc.is_valid());
+    assert_eq!(
+        sc.trace_id(),
+        TraceId::from_hex("1234567890abcdef1234567890abcdef").unwrap()
+    );
+    assert_eq!(sc.span_id(), SpanId::from_hex("1234567890abcdef").unwrap());
 }
 
 #[test]
@@ -243,11 +246,14 @@ fn test_extract_uppercase_span_id() {
     let cx = Context::current();
     let extracted_cx = propagator.extract_with_context(&cx, &extractor);
 
-    // When extraction fails, the original context is returned
-    // The original context has an invalid span context
     let span = extracted_cx.span();
     let sc = span.span_context();
-    assert!(!sc.is_valid());
+    assert!(sc.is_valid());
+    assert_eq!(
+        sc.trace_id(),
+        TraceId::from_hex("1234567890abcdef1234567890abcdef").unwrap()
+    );
+    assert_eq!(sc.span_id(), SpanId::from_hex("1234567890abcdef").unwrap());
 }
 
 #[test]
@@ -400,3 +406,114 @@ fn test_roundtrip() {
     assert_eq!(extracted_sc.trace_flags(), TraceFlags::SAMPLED);
     assert!(extracted_sc.is_remote()); // Note: This will be true in extracted context
 }
+
+/// Extract a span context from the given Instana headers
+fn extract(trace_id: &str, span_id: &str) -> SpanContext {
+    let propagator = InstanaPropagator::new();
+    let extractor = MockExtractor::new()
+        .with_header("X-INSTANA-T", trace_id)
+        .with_header("X-INSTANA-S", span_id)
+        .with_header("X-INSTANA-L", "1");
+
+    let cx = propagator.extract_with_context(&Context::current(), &extractor);
+    cx.span().span_context().clone()
+}
+
+#[test]
+fn test_extract_64bit_trace_id() {
+    let sc = extract("1234567890abcdef", "1234567890abcdef");
+
+    assert!(sc.is_valid());
+    assert_eq!(
+        sc.trace_id(),
+        TraceId::from_hex("00000000000000001234567890abcdef").unwrap()
+    );
+}
+
+#[test]
+fn test_extract_short_ids_are_left_padded() {
+    let sc = extract("abc123", "42");
+
+    assert!(sc.is_valid());
+    assert_eq!(
+        sc.trace_id(),
+        TraceId::from_hex("00000000000000000000000000abc123").unwrap()
+    );
+    assert_eq!(sc.span_id(), SpanId::from_hex("0000000000000042").unwrap());
+}
+
+#[test]
+fn test_extract_mixed_case_ids() {
+    let sc = extract("1234567890AbCdEf", "FeDcBa0987654321");
+
+    assert!(sc.is_valid());
+    assert_eq!(
+        sc.trace_id(),
+        TraceId::from_hex("00000000000000001234567890abcdef").unwrap()
+    );
+    assert_eq!(sc.span_id(), SpanId::from_hex("fedcba0987654321").unwrap());
+}
+
+#[test]
+fn test_extract_trims_whitespace() {
+    let sc = extract(" 1234567890abcdef1234567890abcdef\t", "  1234567890abcdef ");
+
+    assert!(sc.is_valid());
+    assert_eq!(
+        sc.trace_id(),
+        TraceId::from_hex("1234567890abcdef1234567890abcdef").unwrap()
+    );
+    assert_eq!(sc.span_id(), SpanId::from_hex("1234567890abcdef").unwrap());
+}
+
+#[test]
+fn test_extract_rejects_too_long_ids() {
+    assert!(!extract("01234567890abcdef1234567890abcdef", "1234567890abcdef").is_valid());
+    assert!(!extract("1234567890abcdef", "01234567890abcdef").is_valid());
+}
+
+#[test]
+fn test_extract_rejects_empty_ids() {
+    assert!(!extract("  ", "1234567890abcdef").is_valid());
+    assert!(!extract("1234567890abcdef", "").is_valid());
+}
+
+#[test]
+fn test_inject_64bit_trace_id() {
+    let propagator = InstanaPropagator::new();
+    let mut injector = MockInjector::new();
+
+    let span_context = SpanContext::new(
+        TraceId::from_hex("00000000000000001234567890abcdef").unwrap(),
+        SpanId::from_hex("0000000000000042").unwrap(),
+        TraceFlags::SAMPLED,
+        false,
+        TraceState::default(),
+    );
+    let cx = Context::current().with_remote_span_context(span_context);
+    propagator.inject_context(&cx, &mut injector);
+
+    assert_eq!(
+        injector.data.get("X-INSTANA-T").unwrap(),
+        "1234567890abcdef"
+    );
+    assert_eq!(
+        injector.data.get("X-INSTANA-S").unwrap(),
+        "0000000000000042"
+    );
+}
+
+#[test]
+fn test_roundtrip_64bit_trace_id() {
+    let propagator = InstanaPropagator::new();
+    let mut injector = MockInjector::new();
+
+    let sc = extract("1234567890abcdef", "1234567890abcdef");
+    let cx = Context::current().with_remote_span_context(sc);
+    propagator.inject_context(&cx, &mut injector);
+
+    assert_eq!(
+        injector.data.get("X-INSTANA-T").unwrap(),
+        "1234567890abcdef"
+    );
+}
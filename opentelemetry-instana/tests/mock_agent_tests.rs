@@ -0,0 +1,125 @@
+use opentelemetry::trace::{Span, SpanKind, Status, TraceContextExt, Tracer, TracerProvider};
+use opentelemetry::Context;
+use opentelemetry_http::HttpClient;
+use opentelemetry_instana::testing::{MockAgent, DISCOVERY_PATH, ENTRY, EXIT, INTERMEDIATE};
+use opentelemetry_instana::InstanaExporter;
+use opentelemetry_sdk::trace::{
+    InMemorySpanExporter, SdkTracerProvider, SimpleSpanProcessor, SpanData, SpanExporter,
+};
+use opentelemetry_sdk::Resource;
+use std::sync::Arc;
+
+/// Record an entry span with an intermediate child and a failing exit grandchild
+fn record_trace() -> Vec<SpanData> {
+    let memory_exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_span_processor(SimpleSpanProcessor::new(memory_exporter.clone()))
+        .build();
+    let tracer = provider.tracer("mock-agent-test");
+
+    let entry = tracer
+        .span_builder("GET /orders")
+        .with_kind(SpanKind::Server)
+        .start(&tracer);
+    let entry_cx = Context::current_with_span(entry);
+
+    let intermediate = tracer
+        .span_builder("load orders")
+        .with_kind(SpanKind::Internal)
+        .start_with_context(&tracer, &entry_cx);
+    let intermediate_cx = entry_cx.with_span(intermediate);
+
+    let mut exit = tracer
+        .span_builder("SELECT orders")
+        .with_kind(SpanKind::Client)
+        .start_with_context(&tracer, &intermediate_cx);
+    exit.set_status(Status::error("something went wrong"));
+    exit.end();
+
+    intermediate_cx.span().end();
+    entry_cx.span().end();
+
+    memory_exporter
+        .get_finished_spans()
+        .expect("failed to get spans")
+}
+
+async fn export_to(agent: &MockAgent, spans: Vec<SpanData>) {
+    let client: Arc<dyn HttpClient> =
+        Arc::new(reqwest::Client::builder().build().unwrap_or_default());
+    let resource = Resource::builder().with_service_name("orders").build();
+    let exporter = InstanaExporter::new(client, agent.exporter_options(), resource);
+
+    exporter.export(spans).await.expect("export failed");
+}
+
+#[tokio::test]
+async fn test_mock_agent_receives_spans() {
+    let agent = MockAgent::start().await;
+
+    export_to(&agent, record_trace()).await;
+    agent.assert_valid_payloads().await;
+
+    let spans = agent.received_spans().await;
+    assert_eq!(spans.len(), 3);
+
+    spans.assert_parent_child("GET /orders", "load orders");
+    spans.assert_parent_child("load orders", "SELECT orders");
+
+    spans.assert_kind("GET /orders", ENTRY);
+    spans.assert_kind("load orders", INTERMEDIATE);
+    spans.assert_kind("SELECT orders", EXIT);
+
+    spans.assert_error_count(1);
+
+    let entry = spans.expect_span("GET /orders");
+    assert_eq!(entry.data.service.as_deref(), Some("orders"));
+    assert!(entry.parent_span_id.is_none());
+    assert!(spans.find_by_name("unknown").is_none());
+}
+
+#[tokio::test]
+async fn test_mock_agent_reset() {
+    let agent = MockAgent::start().await;
+
+    export_to(&agent, record_trace()).await;
+    assert_eq!(agent.received_spans().await.len(), 3);
+
+    agent.reset().await;
+    assert!(agent.received_spans().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_mock_agent_answers_discovery() {
+    let agent = MockAgent::start().await;
+    let client = reqwest::Client::new();
+
+    let response = client.get(agent.uri()).send().await.unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers()["server"], "Instana Agent");
+
+    let response = client
+        .put(format!("{}{}", agent.uri(), DISCOVERY_PATH))
+        .body("{}")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let body: serde_json::Value =
+        serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!(body["pid"], std::process::id());
+}
+
+#[tokio::test]
+#[should_panic(expected = "is not a child of span")]
+async fn test_mock_agent_assert_parent_child_fails() {
+    let agent = MockAgent::start().await;
+
+    export_to(&agent, record_trace()).await;
+
+    agent
+        .received_spans()
+        .await
+        .assert_parent_child("GET /orders", "SELECT orders");
+}
@@ -0,0 +1,219 @@
+use opentelemetry::global;
+use opentelemetry::trace::{Tracer, TracerProvider};
+use opentelemetry_instana::exporter::BuildError;
+use opentelemetry_instana::{InstanaExporterOptions, InstanaPipeline, StackTraceConfig};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::trace::{
+    InMemorySpanExporter, Sampler, SimpleSpanProcessor, Span, SpanData, SpanProcessor,
+};
+use opentelemetry_sdk::Resource;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const RAWTRACE_PATH: &str = "/com.instana.plugin.generic.rawtrace";
+
+async fn start_agent() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(RAWTRACE_PATH))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn pipeline(server: &MockServer) -> InstanaPipeline {
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}{}", server.uri(), RAWTRACE_PATH)).unwrap();
+    InstanaPipeline::new()
+        .with_options(options)
+        .with_stack_traces(StackTraceConfig::default())
+        .with_install_global(false)
+}
+
+async fn received_spans(server: &MockServer) -> Vec<serde_json::Value> {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|request| serde_json::from_slice::<Vec<serde_json::Value>>(&request.body).unwrap())
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pipeline_exports_on_drop() {
+    let server = start_agent().await;
+    let pipeline = pipeline(&server).with_service_name("orders");
+
+    tokio::task::spawn_blocking(move || {
+        let guard = pipeline.install().unwrap();
+        let tracer = guard.tracer_provider().tracer("test");
+        tracer.in_span("load orders", |_| {});
+        // Dropping the guard flushes the span
+    })
+    .await
+    .unwrap();
+
+    let spans = received_spans(&server).await;
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["data"]["sdk"]["name"], "load orders");
+    assert_eq!(spans[0]["data"]["service"], "orders");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pipeline_with_extra_processor() {
+    let server = start_agent().await;
+    let in_memory = InMemorySpanExporter::default();
+    let pipeline = pipeline(&server).with_span_processor(SimpleSpanProcessor::new(in_memory.clone()));
+
+    tokio::task::spawn_blocking(move || {
+        let guard = pipeline.install().unwrap();
+        guard.tracer_provider().tracer("test").in_span("work", |_| {});
+        guard.force_flush().unwrap();
+
+        let spans = in_memory.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "work");
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(received_spans(&server).await.len(), 1);
+}
+
+/// Processor recording the resource of the tracer provider
+#[derive(Debug, Clone, Default)]
+struct ResourceRecorder(Arc<Mutex<Option<Resource>>>);
+
+impl SpanProcessor for ResourceRecorder {
+    fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+    fn on_end(&self, _span: SpanData) {}
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        *self.0.lock().unwrap() = Some(resource.clone());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pipeline_service_name_keeps_resource() {
+    let server = start_agent().await;
+    let recorder = ResourceRecorder::default();
+    let resource = Resource::builder_empty()
+        .with_schema_url(
+            [KeyValue::new("deployment.environment.name", "prod")],
+            "https://opentelemetry.io/schemas/1.26.0",
+        )
+        .build();
+    let pipeline = pipeline(&server)
+        .with_resource(resource)
+        .with_service_name("orders")
+        .with_span_processor(recorder.clone());
+
+    tokio::task::spawn_blocking(move || {
+        let _guard = pipeline.install().unwrap();
+    })
+    .await
+    .unwrap();
+
+    let resource = recorder.0.lock().unwrap().clone().unwrap();
+    assert_eq!(resource.schema_url(), Some("https://opentelemetry.io/schemas/1.26.0"));
+    assert_eq!(resource.get(&"service.name".into()), Some("orders".into()));
+    assert_eq!(
+        resource.get(&"deployment.environment.name".into()),
+        Some("prod".into())
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pipeline_with_sampler() {
+    let server = start_agent().await;
+    let pipeline = pipeline(&server).with_sampler(Sampler::AlwaysOff);
+
+    tokio::task::spawn_blocking(move || {
+        let guard = pipeline.install().unwrap();
+        guard.tracer_provider().tracer("test").in_span("work", |_| {});
+    })
+    .await
+    .unwrap();
+
+    assert!(received_spans(&server).await.is_empty());
+}
+
+#[test]
+fn test_pipeline_invalid_env() {
+    temp_env::with_var("INSTANA_TIMEOUT", Some("soon"), || {
+        let result = InstanaPipeline::new().with_install_global(false).install();
+        assert!(matches!(result, Err(BuildError::InvalidEnvVar { .. })));
+    });
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_init_installs_globals() {
+    let server = start_agent().await;
+    let address = *server.address();
+
+    tokio::task::spawn_blocking(move || {
+        let port = address.port().to_string();
+        temp_env::with_vars(
+            [
+                ("INSTANA_AGENT_HOST", Some(address.ip().to_string().as_str())),
+                ("INSTANA_AGENT_PORT", Some(port.as_str())),
+                ("INSTANA_SERVICE_NAME", Some("checkout")),
+                ("INSTANA_STACK_TRACE", None),
+            ],
+            || {
+                let _guard = opentelemetry_instana::init().unwrap();
+                global::tracer("test").in_span("checkout", |_| {});
+                global::get_text_map_propagator(|propagator| {
+                    assert!(propagator.fields().any(|field| field == "X-INSTANA-T"));
+                });
+            },
+        );
+    })
+    .await
+    .unwrap();
+
+    let spans = received_spans(&server).await;
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["data"]["service"], "checkout");
+}
+
+#[cfg(not(feature = "reqwest-blocking-client"))]
+#[tokio::test]
+async fn test_pipeline_rejects_current_thread_runtime() {
+    let server = start_agent().await;
+
+    let result = pipeline(&server).install();
+    assert!(matches!(result, Err(BuildError::CurrentThreadRuntime)));
+}
+
+#[cfg(all(
+    not(feature = "reqwest-blocking-client"),
+    any(feature = "reqwest-client", feature = "hyper-client")
+))]
+#[test]
+fn test_pipeline_rejects_missing_runtime() {
+    let options = InstanaExporterOptions::with_endpoint("http://localhost:42699").unwrap();
+    let result = InstanaPipeline::new()
+        .with_options(options)
+        .with_stack_traces(StackTraceConfig::default())
+        .with_install_global(false)
+        .install();
+
+    assert!(matches!(result, Err(BuildError::NoRuntime)));
+}
@@ -0,0 +1,157 @@
+use opentelemetry::trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_instana::exporter::process_identity::{parse_container_id, parse_sched_pid};
+use opentelemetry_instana::exporter::serialize_span;
+use opentelemetry_instana::{InstanaExporter, ProcessIdentity};
+use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanLinks};
+use opentelemetry_sdk::Resource;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+const DOCKER_CONTAINER_ID: &str =
+    "a2ffe0e97ac22657a2a023ad628e9df837c38a03b1ebc904d3f6d644eb1a1a81";
+const CONTAINERD_CONTAINER_ID: &str =
+    "3f1b2c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f809";
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/proc")
+        .join(name)
+}
+
+fn create_test_span_data() -> SpanData {
+    let time = SystemTime::now();
+
+    SpanData {
+        span_context: SpanContext::new(
+            TraceId::from_hex("0102030405060708090a0b0c0d0e0f10").unwrap(),
+            SpanId::from_hex("0102030405060708").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        ),
+        parent_span_id: SpanId::INVALID,
+        span_kind: SpanKind::Server,
+        name: std::borrow::Cow::Borrowed("test-span"),
+        start_time: time,
+        end_time: time,
+        attributes: Vec::new(),
+        dropped_attributes_count: 0,
+        events: SpanEvents::default(),
+        links: SpanLinks::default(),
+        status: Status::Ok,
+        instrumentation_scope: InstrumentationScope::builder("test-instrumentation").build(),
+    }
+}
+
+#[test]
+fn test_parse_sched_pid() {
+    assert_eq!(parse_sched_pid("node (23817, #threads: 7)\n----"), Some(23817));
+    assert_eq!(parse_sched_pid("my (app) (42, #threads: 1)"), Some(42));
+    assert_eq!(parse_sched_pid("garbage"), None);
+    assert_eq!(parse_sched_pid(""), None);
+}
+
+#[test]
+fn test_parse_container_id() {
+    // cgroup v1
+    assert_eq!(
+        parse_container_id(&format!("4:cpuset:/docker/{DOCKER_CONTAINER_ID}")).as_deref(),
+        Some(DOCKER_CONTAINER_ID)
+    );
+    // cgroup v2 with systemd driver
+    assert_eq!(
+        parse_container_id(&format!("0::/system.slice/docker-{DOCKER_CONTAINER_ID}.scope"))
+            .as_deref(),
+        Some(DOCKER_CONTAINER_ID)
+    );
+    // cpuset
+    assert_eq!(
+        parse_container_id(&format!("/kubepods/besteffort/pod1234/{CONTAINERD_CONTAINER_ID}"))
+            .as_deref(),
+        Some(CONTAINERD_CONTAINER_ID)
+    );
+    // not in a container
+    assert_eq!(parse_container_id("0::/user.slice/user-1000.slice/session-3.scope"), None);
+    assert_eq!(parse_container_id("/"), None);
+}
+
+#[test]
+fn test_detect_cgroup_v1_container() {
+    let identity = ProcessIdentity::detect_from(&fixture("cgroup_v1"), 1);
+
+    assert_eq!(identity.pid, 1);
+    assert_eq!(identity.host_pid, Some(23817));
+    assert_eq!(identity.container_id.as_deref(), Some(DOCKER_CONTAINER_ID));
+    assert_eq!(
+        identity.cpuset,
+        Some(format!("/docker/{DOCKER_CONTAINER_ID}"))
+    );
+    assert_eq!(identity.agent_pid(), 23817);
+    assert!(identity.in_container());
+}
+
+#[test]
+fn test_detect_cgroup_v2_container() {
+    let identity = ProcessIdentity::detect_from(&fixture("cgroup_v2"), 7);
+
+    assert_eq!(identity.host_pid, Some(48211));
+    assert_eq!(identity.container_id.as_deref(), Some(CONTAINERD_CONTAINER_ID));
+    assert_eq!(identity.agent_pid(), 48211);
+    assert!(identity.in_container());
+}
+
+#[test]
+fn test_detect_outside_container() {
+    let identity = ProcessIdentity::detect_from(&fixture("host"), 1);
+
+    assert_eq!(identity.host_pid, None);
+    assert_eq!(identity.container_id, None);
+    assert_eq!(identity.agent_pid(), 1);
+    assert!(!identity.in_container());
+
+    // Missing files leave the namespaced pid
+    let identity = ProcessIdentity::detect_from(&fixture("missing"), 1234);
+    assert_eq!(identity.agent_pid(), 1234);
+    assert!(!identity.in_container());
+}
+
+#[test]
+fn test_announce_payload() {
+    let identity = ProcessIdentity::detect_from(&fixture("cgroup_v1"), 1);
+
+    let payload = serde_json::to_value(identity.announce_payload()).unwrap();
+
+    assert_eq!(payload["pid"], 23817);
+    assert_eq!(payload["pidFromParentNS"], true);
+    assert_eq!(
+        payload["cpuSetFileContent"],
+        format!("/docker/{DOCKER_CONTAINER_ID}")
+    );
+    assert!(payload["name"].is_string());
+    assert!(payload["args"].is_array());
+}
+
+#[test]
+fn test_from_section_uses_host_pid() {
+    let resource = Resource::builder()
+        .with_service_name("test-service")
+        .with_attribute(KeyValue::new("process.pid", 1))
+        .build();
+
+    let exporter = InstanaExporter::builder()
+        .with_service(resource.clone())
+        .with_process_identity(ProcessIdentity::detect_from(&fixture("cgroup_v1"), 1))
+        .build()
+        .expect("failed to build instana exporter");
+    let span = serialize_span::convert_to_instana_span(&exporter, &create_test_span_data()).unwrap();
+    assert_eq!(span.from.process_id, Some(23817));
+
+    let exporter = InstanaExporter::builder()
+        .with_service(resource)
+        .with_process_identity(ProcessIdentity::detect_from(&fixture("host"), 1))
+        .build()
+        .expect("failed to build instana exporter");
+    let span = serialize_span::convert_to_instana_span(&exporter, &create_test_span_data()).unwrap();
+    assert_eq!(span.from.process_id, Some(1));
+}
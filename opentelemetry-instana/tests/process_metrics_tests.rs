@@ -0,0 +1,245 @@
+use opentelemetry_instana::exporter::agent_config::ANNOUNCE_PATH;
+use opentelemetry_instana::process_metrics::{
+    parse_max_open_files, parse_stat, parse_status, ContextSwitches, CpuMetrics, MemoryMetrics,
+    ReportError,
+};
+use opentelemetry_instana::{InstanaExporterOptions, ProcessCollector, ProcessIdentity, ProcessMetrics};
+use serde_json::json;
+use std::path::PathBuf;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const PLUGIN_PATH: &str = "/com.instana.plugin.rust.4242";
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/proc/metrics")
+}
+
+fn identity() -> ProcessIdentity {
+    ProcessIdentity {
+        pid: 1,
+        host_pid: Some(4242),
+        container_id: None,
+        cpuset: None,
+    }
+}
+
+/// Start an agent answering the announce request with the pid 4242
+async fn start_agent() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path(ANNOUNCE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "pid": 4242 })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(PLUGIN_PATH))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn build_collector(server: &MockServer) -> ProcessCollector {
+    ProcessCollector::builder()
+        .with_agent_url(&server.uri())
+        .with_process_identity(identity())
+        .with_proc_dir(fixture())
+        .with_version("2.1.0")
+        .with_dependency("tokio", "1.52.1")
+        .build()
+        .expect("failed to build process collector")
+}
+
+async fn requests_to(server: &MockServer, request_path: &str) -> Vec<serde_json::Value> {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter(|request| request.url.path() == request_path)
+        .map(|request| serde_json::from_slice(&request.body).unwrap_or(serde_json::Value::Null))
+        .collect()
+}
+
+#[test]
+fn test_parse_status() {
+    let status = parse_status("Name:\tserver\nVmRSS:\t  1424 kB\nThreads:\t4\nState:\tS (sleeping)\n");
+
+    assert_eq!(status.get("VmRSS"), Some(1424 * 1024));
+    assert_eq!(status.get("Threads"), Some(4));
+    assert_eq!(status.get("Name"), None);
+    assert_eq!(status.get("State"), None);
+}
+
+#[test]
+fn test_parse_stat_with_parentheses_in_name() {
+    let stat = std::fs::read_to_string(fixture().join("stat")).unwrap();
+
+    assert_eq!(
+        parse_stat(&stat),
+        Some(CpuMetrics {
+            user: 2.5,
+            system: 0.75,
+            minor_page_faults: 1520,
+            major_page_faults: 3,
+        })
+    );
+    assert_eq!(parse_stat("4242 (server) S 1"), None);
+}
+
+#[test]
+fn test_parse_max_open_files() {
+    let limits = std::fs::read_to_string(fixture().join("limits")).unwrap();
+
+    assert_eq!(parse_max_open_files(&limits), Some(1024));
+    assert_eq!(parse_max_open_files("Max processes 10 10 processes"), None);
+}
+
+#[test]
+fn test_collect_from_fixture() {
+    let metrics = ProcessMetrics::collect_from(&fixture());
+
+    assert_eq!(
+        metrics.memory,
+        Some(MemoryMetrics {
+            resident: 10240 * 1024,
+            peak_resident: 12288 * 1024,
+            virtual_: 102400 * 1024,
+        })
+    );
+    assert_eq!(metrics.threads, Some(6));
+    assert_eq!(
+        metrics.context_switches,
+        Some(ContextSwitches {
+            voluntary: 321,
+            involuntary: 12,
+        })
+    );
+    assert_eq!(metrics.open_files, Some(5));
+    assert_eq!(metrics.max_open_files, Some(1024));
+    assert_eq!(metrics.tokio, None);
+}
+
+#[test]
+fn test_collect_from_missing_dir() {
+    let metrics = ProcessMetrics::collect_from(&fixture().join("missing"));
+
+    assert_eq!(metrics, ProcessMetrics::default());
+}
+
+#[tokio::test]
+async fn test_collect_current_process() {
+    let metrics = ProcessMetrics::collect();
+
+    assert!(metrics.memory.is_some_and(|memory| memory.resident > 0));
+    assert!(metrics.threads.is_some_and(|threads| threads >= 1));
+    assert_eq!(metrics.tokio.map(|tokio| tokio.workers), Some(1));
+}
+
+#[tokio::test]
+async fn test_report_announces_and_sends_snapshot_once() {
+    let server = start_agent().await;
+    let collector = build_collector(&server);
+
+    collector.report().await.unwrap();
+    collector.report().await.unwrap();
+
+    assert_eq!(requests_to(&server, ANNOUNCE_PATH).await.len(), 1);
+    assert_eq!(collector.entity_pid(), Some(4242));
+
+    let reports = requests_to(&server, PLUGIN_PATH).await;
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0]["pid"], 4242);
+    assert_eq!(reports[0]["snapshot"]["version"], "2.1.0");
+    assert_eq!(reports[0]["snapshot"]["dependencies"]["tokio"], "1.52.1");
+    assert_eq!(
+        reports[0]["snapshot"]["dependencies"]["opentelemetry_instana"],
+        env!("CARGO_PKG_VERSION")
+    );
+    assert_eq!(reports[0]["metrics"]["memory"]["resident"], 10240 * 1024);
+    assert_eq!(reports[0]["metrics"]["cpu"]["user"], 2.5);
+    assert_eq!(reports[0]["metrics"]["openFiles"], 5);
+    assert_eq!(reports[0]["metrics"]["tokio"]["workers"], 1);
+    assert!(reports[1].get("snapshot").is_none());
+    assert_eq!(reports[1]["metrics"]["threads"], 6);
+}
+
+#[tokio::test]
+async fn test_unknown_entity_is_announced_again() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path(ANNOUNCE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "pid": 4242 })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(PLUGIN_PATH))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    let collector = build_collector(&server);
+
+    let result = collector.report().await;
+
+    assert!(matches!(result, Err(ReportError::HttpStatus { status: 404, .. })));
+    assert_eq!(collector.entity_pid(), None);
+
+    let _ = collector.report().await;
+    assert_eq!(requests_to(&server, ANNOUNCE_PATH).await.len(), 2);
+    // The snapshot is sent again to the new entity
+    assert!(requests_to(&server, PLUGIN_PATH).await[1].get("snapshot").is_some());
+}
+
+#[tokio::test]
+async fn test_failed_announce() {
+    let server = MockServer::start().await;
+    let collector = build_collector(&server);
+
+    let result = collector.report().await;
+
+    assert!(matches!(result, Err(ReportError::Announce(_))));
+    assert!(requests_to(&server, PLUGIN_PATH).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_pid_falls_back_to_process_identity() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path(ANNOUNCE_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(PLUGIN_PATH))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    let collector = build_collector(&server);
+
+    collector.report().await.unwrap();
+
+    assert_eq!(collector.entity_pid(), Some(identity().agent_pid()));
+}
+
+#[tokio::test]
+async fn test_spawned_collector_reports_periodically() {
+    let server = start_agent().await;
+    let options =
+        InstanaExporterOptions::with_endpoint(&format!("{}/com.instana.plugin.generic.rawtrace", server.uri()))
+            .unwrap();
+    let collector = ProcessCollector::builder()
+        .with_options(&options)
+        .with_process_identity(identity())
+        .with_interval(std::time::Duration::from_millis(20))
+        .build()
+        .unwrap();
+
+    let handle = collector.spawn();
+    tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+    handle.abort();
+
+    assert!(requests_to(&server, PLUGIN_PATH).await.len() >= 2);
+}
@@ -592,4 +592,95 @@ fn test_serialize_empty_collections() {
     assert!(!tags.as_object().unwrap().contains_key("links"));
 }
 
+#[test]
+fn test_serialize_with_span_service_name_override() {
+    let exporter = InstanaExporter::builder()
+        .with_service(get_resource())
+        .build()
+        .expect("failed to build instana exporter");
+
+    let mut span = create_test_span_data(SpanKind::Server, false);
+    span.attributes
+        .push(KeyValue::new("service.name", "span-service"));
+    span.instrumentation_scope = InstrumentationScope::builder("test-instrumentation")
+        .with_attributes(vec![KeyValue::new("service.name", "scope-service")])
+        .build();
+
+    let json_value = serialize_and_parse(&exporter, &span).expect("Failed to serialize span");
+
+    // The span attribute wins over the scope attribute and the resource
+    assert_eq!(json_value["data"]["service"], "span-service");
+}
+
+#[test]
+fn test_serialize_with_scope_service_name_override() {
+    let exporter = InstanaExporter::builder()
+        .with_service(get_resource())
+        .build()
+        .expect("failed to build instana exporter");
+
+    let mut span = create_test_span_data(SpanKind::Server, false);
+    span.instrumentation_scope = InstrumentationScope::builder("test-instrumentation")
+        .with_attributes(vec![KeyValue::new("service.name", "scope-service")])
+        .build();
+
+    let json_value = serialize_and_parse(&exporter, &span).expect("Failed to serialize span");
+
+    // The scope attribute wins over the resource
+    assert_eq!(json_value["data"]["service"], "scope-service");
+}
+
+#[test]
+fn test_serialize_exit_span_with_peer() {
+    let exporter = InstanaExporter::builder()
+        .with_service(get_resource())
+        .build()
+        .expect("failed to build instana exporter");
+
+    let mut span = create_test_span_data(SpanKind::Client, false);
+    span.attributes.push(KeyValue::new("peer.service", "payments-api"));
+    span.attributes
+        .push(KeyValue::new("server.address", "payments.example.com"));
+    span.attributes.push(KeyValue::new("server.port", 443));
+
+    let json_value = serialize_and_parse(&exporter, &span).expect("Failed to serialize span");
+
+    let peer = &json_value["data"]["peer"];
+    assert_eq!(peer["service"], "payments-api");
+    assert_eq!(peer["hostname"], "payments.example.com");
+    assert_eq!(peer["port"], 443);
+
+    // The exporting service is unchanged
+    assert_eq!(json_value["data"]["service"], "test-service");
+}
+
+#[test]
+fn test_serialize_peer_ignored_for_entry_span() {
+    let exporter = InstanaExporter::builder()
+        .with_service(get_resource())
+        .build()
+        .expect("failed to build instana exporter");
+
+    let mut span = create_test_span_data(SpanKind::Server, false);
+    span.attributes.push(KeyValue::new("peer.service", "payments-api"));
+
+    let json_value = serialize_and_parse(&exporter, &span).expect("Failed to serialize span");
+
+    assert!(json_value["data"]["peer"].is_null());
+}
+
+#[test]
+fn test_serialize_exit_span_without_peer_attributes() {
+    let exporter = InstanaExporter::builder()
+        .with_service(get_resource())
+        .build()
+        .expect("failed to build instana exporter");
+
+    let span = create_test_span_data(SpanKind::Client, false);
+
+    let json_value = serialize_and_parse(&exporter, &span).expect("Failed to serialize span");
+
+    assert!(!json_value["data"].as_object().unwrap().contains_key("peer"));
+}
+
 // Made with Bob
@@ -0,0 +1,188 @@
+use opentelemetry::trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_http::HttpClient;
+use opentelemetry_instana::exporter::{serialize_span, span_batching};
+use opentelemetry_instana::{InstanaExporter, InstanaExporterOptions, SpanBatchingOptions};
+use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanExporter, SpanLinks};
+use opentelemetry_sdk::Resource;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const PARENT_SPAN_ID: &str = "0807060504030201";
+
+fn create_exit_span(span_id: u64, name: &'static str, start_ms: u64, duration_ms: u64) -> SpanData {
+    let start_time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_000 + start_ms);
+
+    SpanData {
+        span_context: SpanContext::new(
+            TraceId::from_hex("0102030405060708090a0b0c0d0e0f10").unwrap(),
+            SpanId::from(span_id),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        ),
+        parent_span_id: SpanId::from_hex(PARENT_SPAN_ID).unwrap(),
+        span_kind: SpanKind::Client,
+        name: std::borrow::Cow::Borrowed(name),
+        start_time,
+        end_time: start_time + Duration::from_millis(duration_ms),
+        attributes: vec![
+            KeyValue::new("db.system", "postgresql"),
+            KeyValue::new("server.address", "db.example.com"),
+        ],
+        dropped_attributes_count: 0,
+        events: SpanEvents::default(),
+        links: SpanLinks::default(),
+        status: Status::Unset,
+        instrumentation_scope: InstrumentationScope::builder("test-instrumentation").build(),
+    }
+}
+
+fn serialize(spans: &[SpanData]) -> Value {
+    let exporter = InstanaExporter::builder()
+        .with_service(Resource::builder().with_service_name("test-service").build())
+        .build()
+        .expect("failed to build instana exporter");
+
+    let bytes =
+        serialize_span::serialize_batch(&exporter, spans).expect("Failed to serialize batch");
+    serde_json::from_slice(&bytes).expect("Failed to parse JSON")
+}
+
+#[test]
+fn test_consecutive_short_exit_spans_are_merged() {
+    let spans = vec![
+        create_exit_span(3, "SELECT", 2, 1),
+        create_exit_span(1, "SELECT", 0, 1),
+        create_exit_span(2, "SELECT", 1, 0),
+    ];
+
+    let aggregated = span_batching::aggregate_spans(spans, &SpanBatchingOptions::default());
+    assert_eq!(aggregated.len(), 1);
+
+    let json_value = serialize(&aggregated);
+    let span = &json_value[0];
+
+    // The earliest span represents the batch
+    assert_eq!(span["s"], "0000000000000001");
+    assert_eq!(span["b"]["s"], 3);
+    assert_eq!(span["b"]["d"], 2);
+
+    // Batch bookkeeping does not leak into the attributes
+    let attributes = span["data"]["sdk"]["custom"]["tags"]["attributes"]
+        .as_object()
+        .unwrap();
+    assert!(!attributes.contains_key("INTERNAL_TAG_BATCH_SIZE"));
+    assert!(!attributes.contains_key("INTERNAL_TAG_BATCH_DURATION"));
+    assert_eq!(attributes["db.system"], "postgresql");
+}
+
+#[test]
+fn test_batching_stops_at_different_sibling() {
+    let spans = vec![
+        create_exit_span(1, "SELECT", 0, 1),
+        create_exit_span(2, "SELECT", 1, 1),
+        create_exit_span(3, "UPDATE", 2, 1),
+        create_exit_span(4, "SELECT", 3, 1),
+    ];
+
+    let aggregated = span_batching::aggregate_spans(spans, &SpanBatchingOptions::default());
+    let json_value = serialize(&aggregated);
+    let spans = json_value.as_array().unwrap();
+
+    assert_eq!(spans.len(), 3);
+    assert_eq!(spans[0]["b"]["s"], 2);
+    assert!(spans[1]["b"].is_null());
+    assert!(spans[2]["b"].is_null());
+}
+
+#[test]
+fn test_unbatchable_spans_are_kept() {
+    // Too long
+    let long_span = create_exit_span(1, "SELECT", 0, 50);
+
+    // Different target
+    let mut other_target = create_exit_span(2, "SELECT", 60, 1);
+    other_target.attributes = vec![KeyValue::new("server.address", "replica.example.com")];
+
+    // Erroneous
+    let mut failed = create_exit_span(3, "SELECT", 61, 1);
+    failed.status = Status::error("connection reset");
+
+    // Entry span
+    let mut entry = create_exit_span(4, "SELECT", 62, 1);
+    entry.span_kind = SpanKind::Server;
+
+    let spans = vec![long_span, other_target, failed, entry];
+    let aggregated = span_batching::aggregate_spans(spans, &SpanBatchingOptions::default());
+
+    assert_eq!(aggregated.len(), 4);
+    assert!(serialize(&aggregated)
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|span| span["b"].is_null()));
+}
+
+#[test]
+fn test_spans_with_children_are_not_merged() {
+    let first = create_exit_span(1, "SELECT", 0, 1);
+    let second = create_exit_span(2, "SELECT", 1, 1);
+    let mut child = create_exit_span(3, "SELECT", 1, 1);
+    child.parent_span_id = SpanId::from(2);
+
+    let aggregated =
+        span_batching::aggregate_spans(vec![first, second, child], &SpanBatchingOptions::default());
+
+    assert_eq!(aggregated.len(), 3);
+}
+
+#[test]
+fn test_max_duration_is_configurable() {
+    let spans = vec![
+        create_exit_span(1, "SELECT", 0, 50),
+        create_exit_span(2, "SELECT", 50, 50),
+    ];
+    let options = SpanBatchingOptions {
+        max_duration: Duration::from_millis(100),
+    };
+
+    let aggregated = span_batching::aggregate_spans(spans, &options);
+    assert_eq!(aggregated.len(), 1);
+}
+
+#[tokio::test]
+async fn test_exporter_sends_batched_spans() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/test-path"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let mut options =
+        InstanaExporterOptions::with_endpoint(&format!("{}/test-path", mock_server.uri())).unwrap();
+    options.span_batching = Some(SpanBatchingOptions::default());
+
+    let client: Arc<dyn HttpClient> =
+        Arc::new(reqwest::Client::builder().build().unwrap_or_default());
+    let exporter = InstanaExporter::new(client, options, Resource::builder().build());
+
+    let batch = (0..20)
+        .map(|i| create_exit_span(i + 1, "SELECT", i, 0))
+        .collect();
+    exporter.export(batch).await.expect("export failed");
+
+    let requests = mock_server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+
+    let json_value: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(json_value.as_array().unwrap().len(), 1);
+    assert_eq!(json_value[0]["b"]["s"], 20);
+}
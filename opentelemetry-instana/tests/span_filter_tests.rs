@@ -0,0 +1,353 @@
+use opentelemetry::trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_instana::exporter::metrics::SPAN_FILTERED;
+use opentelemetry_instana::exporter::{
+    serialize_span, FilterCondition, FilterRule, MatchType, SpanFilter, SpanFilterError,
+};
+use opentelemetry_instana::{InstanaExporter, InstanaExporterOptions};
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanExporter, SpanLinks};
+use std::io::Write;
+use std::time::SystemTime;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const RAWTRACE_PATH: &str = "/com.instana.plugin.generic.rawtrace";
+
+const FILTER: &str = r#"
+include:
+  - name: Failing health checks
+    attributes:
+      - key: http.response.status_code
+        values: ["500", "503"]
+exclude:
+  - name: Health checks
+    attributes:
+      - key: url.path
+        values: ["/health", "/metrics"]
+      - key: kind
+        values: [entry]
+  - name: Redis PING
+    attributes:
+      - key: db.query.text
+        values: ["PING"]
+        match_type: startswith
+  - name: Internal polling
+    attributes:
+      - key: name
+        values: ["^poll-[0-9]+$"]
+        match_type: regex
+"#;
+
+fn create_test_span_data(name: &'static str, kind: SpanKind, attributes: Vec<KeyValue>) -> SpanData {
+    let time = SystemTime::now();
+
+    SpanData {
+        span_context: SpanContext::new(
+            TraceId::from_hex("0102030405060708090a0b0c0d0e0f10").unwrap(),
+            SpanId::from_hex("0102030405060708").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        ),
+        parent_span_id: SpanId::INVALID,
+        span_kind: kind,
+        name: std::borrow::Cow::Borrowed(name),
+        start_time: time,
+        end_time: time,
+        attributes,
+        dropped_attributes_count: 0,
+        events: SpanEvents::default(),
+        links: SpanLinks::default(),
+        status: Status::Ok,
+        instrumentation_scope: InstrumentationScope::builder("test-instrumentation").build(),
+    }
+}
+
+fn health_check(status: i64) -> SpanData {
+    create_test_span_data(
+        "GET /health",
+        SpanKind::Server,
+        vec![
+            KeyValue::new("url.path", "/health"),
+            KeyValue::new("http.response.status_code", status),
+        ],
+    )
+}
+
+fn rule_name(filter: &SpanFilter, span: &SpanData) -> Option<String> {
+    filter
+        .dropped_by(span)
+        .map(|rule| filter.exclude[rule].name.clone())
+}
+
+#[test]
+fn test_parse_filter() {
+    let filter = SpanFilter::from_yaml(FILTER).unwrap();
+
+    assert_eq!(filter.include.len(), 1);
+    assert_eq!(filter.exclude.len(), 3);
+    assert_eq!(
+        filter.exclude[1],
+        FilterRule::new(
+            "Redis PING",
+            vec![FilterCondition::new("db.query.text", vec!["PING".to_string()], MatchType::StartsWith)
+                .unwrap()]
+        )
+    );
+}
+
+#[test]
+fn test_exclude_rules() {
+    let filter = SpanFilter::from_yaml(FILTER).unwrap();
+
+    assert_eq!(rule_name(&filter, &health_check(200)).as_deref(), Some("Health checks"));
+    assert_eq!(
+        rule_name(
+            &filter,
+            &create_test_span_data(
+                "PING",
+                SpanKind::Client,
+                vec![KeyValue::new("db.query.text", "PING hello")]
+            )
+        )
+        .as_deref(),
+        Some("Redis PING")
+    );
+    assert_eq!(
+        rule_name(&filter, &create_test_span_data("poll-42", SpanKind::Internal, Vec::new()))
+            .as_deref(),
+        Some("Internal polling")
+    );
+    assert_eq!(
+        rule_name(&filter, &create_test_span_data("poll-all", SpanKind::Internal, Vec::new())),
+        None
+    );
+}
+
+#[test]
+fn test_all_conditions_must_match() {
+    let filter = SpanFilter::from_yaml(FILTER).unwrap();
+
+    // Health check requested by this service, not an entry
+    let exit = create_test_span_data(
+        "GET /health",
+        SpanKind::Client,
+        vec![KeyValue::new("url.path", "/health")],
+    );
+    assert_eq!(filter.dropped_by(&exit), None);
+}
+
+#[test]
+fn test_include_rules_take_precedence() {
+    let filter = SpanFilter::from_yaml(FILTER).unwrap();
+
+    assert_eq!(filter.dropped_by(&health_check(503)), None);
+}
+
+#[test]
+fn test_match_types() {
+    let span = create_test_span_data(
+        "SELECT orders",
+        SpanKind::Client,
+        vec![KeyValue::new("db.system", "postgresql")],
+    );
+    let matches = |key: &str, value: &str, match_type| {
+        let rule = FilterRule::new(
+            "rule",
+            vec![FilterCondition::new(key, vec![value.to_string()], match_type).unwrap()],
+        );
+        rule.matches(&span)
+    };
+
+    assert!(matches("name", "SELECT orders", MatchType::Strict));
+    assert!(!matches("name", "SELECT", MatchType::Strict));
+    assert!(matches("name", "orders", MatchType::Contains));
+    assert!(matches("name", "SELECT", MatchType::StartsWith));
+    assert!(matches("name", "orders", MatchType::EndsWith));
+    assert!(matches("db.system", "^postgres", MatchType::Regex));
+    assert!(matches("kind", "exit", MatchType::Strict));
+    assert!(matches("kind", "client", MatchType::Strict));
+    assert!(matches("category", "databases", MatchType::Strict));
+    assert!(!matches("missing.attribute", "", MatchType::Contains));
+}
+
+#[test]
+fn test_kind_matches_exported_kind() {
+    let kind_rule = |kind: &str| {
+        FilterRule::new(
+            "rule",
+            vec![FilterCondition::new("kind", vec![kind.to_string()], MatchType::Strict).unwrap()],
+        )
+    };
+    let exported_kind = |span: &SpanData| {
+        serialize_span::convert_to_instana_span(&InstanaExporter::default(), span)
+            .unwrap()
+            .kind
+    };
+
+    // Producers are exported as entries and consumers as exits
+    let producer = create_test_span_data("publish", SpanKind::Producer, Vec::new());
+    assert_eq!(exported_kind(&producer), 1);
+    assert!(kind_rule("entry").matches(&producer));
+    assert!(!kind_rule("exit").matches(&producer));
+    assert!(kind_rule("producer").matches(&producer));
+
+    let consumer = create_test_span_data("receive", SpanKind::Consumer, Vec::new());
+    assert_eq!(exported_kind(&consumer), 2);
+    assert!(kind_rule("exit").matches(&consumer));
+    assert!(!kind_rule("entry").matches(&consumer));
+    assert!(kind_rule("consumer").matches(&consumer));
+}
+
+#[test]
+fn test_deactivated_filter() {
+    let filter = SpanFilter::from_yaml(&format!("deactivate: true\n{FILTER}")).unwrap();
+
+    assert!(filter.is_empty());
+    assert_eq!(filter.dropped_by(&health_check(200)), None);
+}
+
+#[test]
+fn test_invalid_filter() {
+    assert!(matches!(
+        SpanFilter::from_yaml("exclude: [{attributes: []}]"),
+        Err(SpanFilterError::Yaml(_))
+    ));
+    assert!(matches!(
+        SpanFilter::from_yaml(
+            "exclude: [{name: broken, attributes: [{key: name, values: ['('], match_type: regex}]}]"
+        ),
+        Err(SpanFilterError::Regex { rule, .. }) if rule == "broken"
+    ));
+}
+
+#[test]
+fn test_filter_from_env() {
+    let inline = "{exclude: [{name: Health checks, attributes: [{key: url.path, values: [/health]}]}]}";
+    temp_env::with_vars(
+        [
+            ("INSTANA_TRACING_FILTER", Some(inline)),
+            ("INSTANA_CONFIG_PATH", None),
+        ],
+        || {
+            let options = InstanaExporterOptions::from_env().unwrap();
+            let filter = options.span_filter.unwrap();
+            assert_eq!(filter.exclude[0].name, "Health checks");
+        },
+    );
+}
+
+#[test]
+fn test_filter_from_config_file() {
+    let mut file = tempfile();
+    writeln!(file.1, "tracing:\n  filter:").unwrap();
+    for line in FILTER.lines() {
+        writeln!(file.1, "    {line}").unwrap();
+    }
+
+    temp_env::with_vars(
+        [
+            ("INSTANA_TRACING_FILTER", None),
+            ("INSTANA_CONFIG_PATH", Some(file.0.to_str().unwrap())),
+        ],
+        || {
+            let options = InstanaExporterOptions::from_env().unwrap();
+            assert_eq!(options.span_filter, Some(SpanFilter::from_yaml(FILTER).unwrap()));
+        },
+    );
+
+    std::fs::remove_file(&file.0).unwrap();
+}
+
+#[test]
+fn test_config_file_without_filter() {
+    let mut file = tempfile();
+    writeln!(file.1, "tracing:\n  disable: false").unwrap();
+
+    assert_eq!(SpanFilter::from_config_file(&file.0).unwrap(), None);
+
+    std::fs::remove_file(&file.0).unwrap();
+}
+
+/// Create an empty file in the temporary directory
+fn tempfile() -> (std::path::PathBuf, std::fs::File) {
+    let path = std::env::temp_dir().join(format!(
+        "instana-filter-{}-{:?}.yaml",
+        std::process::id(),
+        std::thread::current().id()
+    ));
+    let file = std::fs::File::create(&path).unwrap();
+    (path, file)
+}
+
+#[tokio::test]
+async fn test_exporter_drops_filtered_spans() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(RAWTRACE_PATH))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+
+    let metric_exporter = InMemoryMetricExporter::default();
+    let provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(metric_exporter.clone()).build())
+        .build();
+    let options = InstanaExporterOptions {
+        span_filter: Some(SpanFilter::from_yaml(FILTER).unwrap()),
+        ..InstanaExporterOptions::with_endpoint(&format!("{}{}", server.uri(), RAWTRACE_PATH))
+            .unwrap()
+    };
+    let exporter = InstanaExporter::builder()
+        .with_options(options)
+        .with_meter_provider(&provider)
+        .with_async_http_client()
+        .build()
+        .expect("failed to build instana exporter");
+
+    exporter
+        .export(vec![
+            health_check(200),
+            health_check(200),
+            create_test_span_data("GET /orders", SpanKind::Server, Vec::new()),
+        ])
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let spans: Vec<serde_json::Value> = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["data"]["sdk"]["name"], "GET /orders");
+
+    assert_eq!(
+        exporter.get_filtered_span_counts(),
+        vec![
+            ("Health checks".to_string(), 2),
+            ("Redis PING".to_string(), 0),
+            ("Internal polling".to_string(), 0),
+        ]
+    );
+
+    provider.force_flush().unwrap();
+    let metrics = metric_exporter.get_finished_metrics().unwrap();
+    let filtered = metrics
+        .iter()
+        .flat_map(|rm| rm.scope_metrics())
+        .flat_map(|sm| sm.metrics())
+        .find(|metric| metric.name() == SPAN_FILTERED)
+        .expect("filtered spans not recorded");
+    match filtered.data() {
+        AggregatedMetrics::U64(MetricData::Sum(sum)) => {
+            let point = sum.data_points().next().unwrap();
+            assert_eq!(point.value(), 2);
+            assert!(point
+                .attributes()
+                .any(|kv| kv.key.as_str() == "rule" && kv.value.as_str() == "Health checks"));
+        }
+        other => panic!("unexpected data {other:?}"),
+    }
+}
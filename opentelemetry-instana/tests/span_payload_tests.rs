@@ -0,0 +1,307 @@
+use opentelemetry::trace::{
+    Event, Link, SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState,
+};
+use opentelemetry::{InstrumentationScope, KeyValue, Value};
+use opentelemetry_instana::exporter::deserialize_span::{decode_batch, DecodeError};
+use opentelemetry_instana::exporter::instana_span::InstanaKind;
+use opentelemetry_instana::exporter::serialize_span::serialize_batch;
+use opentelemetry_instana::exporter::span_schema::{validate_payload, validate_value};
+use opentelemetry_instana::InstanaExporter;
+use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanLinks};
+use proptest::prelude::*;
+use serde_json::json;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn create_test_span_data(kind: SpanKind, events: Vec<Event>) -> SpanData {
+    let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+
+    SpanData {
+        span_context: SpanContext::new(
+            TraceId::from_hex("0102030405060708090a0b0c0d0e0f10").unwrap(),
+            SpanId::from_hex("0102030405060708").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        ),
+        parent_span_id: SpanId::from_hex("0807060504030201").unwrap(),
+        span_kind: kind,
+        name: std::borrow::Cow::Borrowed("test-span"),
+        start_time: time,
+        end_time: time + Duration::from_millis(100),
+        attributes: vec![KeyValue::new("http.request.method", "GET")],
+        dropped_attributes_count: 0,
+        events: span_events(events, 0),
+        links: SpanLinks::default(),
+        status: Status::Ok,
+        instrumentation_scope: InstrumentationScope::builder("test-instrumentation").build(),
+    }
+}
+
+fn span_events(events: Vec<Event>, dropped_count: u32) -> SpanEvents {
+    let mut span_events = SpanEvents::default();
+    span_events.events = events;
+    span_events.dropped_count = dropped_count;
+    span_events
+}
+
+fn span_links(links: Vec<Link>, dropped_count: u32) -> SpanLinks {
+    let mut span_links = SpanLinks::default();
+    span_links.links = links;
+    span_links.dropped_count = dropped_count;
+    span_links
+}
+
+fn serialize(spans: &[SpanData]) -> Vec<u8> {
+    serialize_batch(&InstanaExporter::default(), spans)
+        .unwrap()
+        .to_vec()
+}
+
+fn violation_paths(payload: serde_json::Value) -> Vec<String> {
+    validate_value(&payload)
+        .unwrap_err()
+        .into_iter()
+        .map(|violation| violation.path)
+        .collect()
+}
+
+#[test]
+fn test_decode_serialized_span() {
+    let span = create_test_span_data(SpanKind::Server, Vec::new());
+
+    let decoded = decode_batch(&serialize(std::slice::from_ref(&span))).unwrap();
+
+    assert_eq!(decoded.len(), 1);
+    assert_eq!(decoded[0].kind, InstanaKind::Entry);
+    assert_eq!(decoded[0].trace_id, span.span_context.trace_id());
+    assert_eq!(decoded[0].duration, Duration::from_millis(100));
+    assert_eq!(decoded[0].attributes["http.request.method"], "GET");
+    assert!(decoded[0].mismatches(&span).is_empty());
+}
+
+#[test]
+fn test_decode_exit_span_keeps_lower_trace_id() {
+    let span = create_test_span_data(SpanKind::Client, Vec::new());
+
+    let decoded = decode_batch(&serialize(&[span])).unwrap();
+
+    assert_eq!(decoded[0].kind, InstanaKind::Exit);
+    assert_eq!(decoded[0].trace_id, TraceId::from_hex("090a0b0c0d0e0f10").unwrap());
+}
+
+#[test]
+fn test_decode_invalid_payload() {
+    assert!(matches!(decode_batch(b"{}"), Err(DecodeError::Json(_))));
+
+    let mut payload: serde_json::Value =
+        serde_json::from_slice(&serialize(&[create_test_span_data(SpanKind::Internal, Vec::new())]))
+            .unwrap();
+    payload[0]["k"] = json!(7);
+    assert!(matches!(
+        decode_batch(payload.to_string().as_bytes()),
+        Err(DecodeError::InvalidField { field, .. }) if field == "k"
+    ));
+}
+
+#[test]
+fn test_instana_kind() {
+    assert_eq!(InstanaKind::of(&SpanKind::Server), InstanaKind::Entry);
+    assert_eq!(InstanaKind::of(&SpanKind::Producer), InstanaKind::Entry);
+    assert_eq!(InstanaKind::of(&SpanKind::Client), InstanaKind::Exit);
+    assert_eq!(InstanaKind::of(&SpanKind::Consumer), InstanaKind::Exit);
+    assert_eq!(InstanaKind::of(&SpanKind::Internal), InstanaKind::Intermediate);
+    assert_eq!(InstanaKind::from_code(3), Some(InstanaKind::Intermediate));
+    assert_eq!(InstanaKind::from_code(0), None);
+}
+
+#[test]
+fn test_duplicate_event_names_are_kept() {
+    let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_200);
+    let events = vec![
+        Event::new("retry", time, vec![KeyValue::new("attempt", 1)], 0),
+        Event::new("retry", time, vec![KeyValue::new("attempt", 2)], 0),
+        Event::new("retry#1", time, Vec::new(), 0),
+    ];
+    let span = create_test_span_data(SpanKind::Client, events);
+
+    let payload = serialize(std::slice::from_ref(&span));
+    let decoded = decode_batch(&payload).unwrap();
+
+    let keys: Vec<&str> = decoded[0].events.iter().map(|event| event.key.as_str()).collect();
+    assert_eq!(keys, vec!["retry", "retry#1", "retry#1#1"]);
+    assert_eq!(decoded[0].events[0].attributes["attempt"], 1);
+    assert_eq!(decoded[0].events[1].attributes["attempt"], 2);
+    assert!(decoded[0].mismatches(&span).is_empty());
+}
+
+#[test]
+fn test_serialized_payload_is_valid() {
+    let spans: Vec<SpanData> = [SpanKind::Server, SpanKind::Client, SpanKind::Internal]
+        .into_iter()
+        .map(|kind| create_test_span_data(kind, Vec::new()))
+        .collect();
+
+    assert_eq!(validate_payload(&serialize(&spans)), Ok(()));
+}
+
+#[test]
+fn test_schema_violations() {
+    let valid: serde_json::Value =
+        serde_json::from_slice(&serialize(&[create_test_span_data(SpanKind::Client, Vec::new())]))
+            .unwrap();
+
+    let mut unknown_field = valid.clone();
+    unknown_field[0]["trace"] = json!("x");
+    assert_eq!(violation_paths(unknown_field), vec!["$[0].trace"]);
+
+    let mut short_id = valid.clone();
+    short_id[0]["s"] = json!("0102");
+    assert_eq!(violation_paths(short_id), vec!["$[0].s"]);
+
+    let mut long_trace_on_exit = valid.clone();
+    long_trace_on_exit[0]["lt"] = json!("0102030405060708090a0b0c0d0e0f10");
+    assert_eq!(violation_paths(long_trace_on_exit), vec!["$[0].lt"]);
+
+    let mut wrong_type = valid.clone();
+    wrong_type[0]["d"] = json!("100");
+    assert_eq!(violation_paths(wrong_type), vec!["$[0].d"]);
+
+    assert!(validate_payload(b"{}").is_err());
+    assert!(validate_payload(b"not json").is_err());
+}
+
+fn attribute_value() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        any::<bool>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        // Quarters round-trip through JSON exactly
+        (-1_000_000i64..1_000_000).prop_map(|n| Value::from(n as f64 / 4.0)),
+        "[ -~]{0,12}".prop_map(Value::from),
+    ]
+}
+
+fn attributes(max: usize) -> impl Strategy<Value = Vec<KeyValue>> {
+    prop::collection::btree_map("[a-z]{1,6}(\\.[a-z]{1,6})?", attribute_value(), 0..max)
+        .prop_map(|attributes| {
+            attributes
+                .into_iter()
+                .map(|(key, value)| KeyValue::new(key, value))
+                .collect()
+        })
+}
+
+fn span_kind() -> impl Strategy<Value = SpanKind> {
+    prop_oneof![
+        Just(SpanKind::Server),
+        Just(SpanKind::Client),
+        Just(SpanKind::Producer),
+        Just(SpanKind::Consumer),
+        Just(SpanKind::Internal),
+    ]
+}
+
+fn status() -> impl Strategy<Value = Status> {
+    prop_oneof![
+        Just(Status::Unset),
+        Just(Status::Ok),
+        "[ -~]{0,16}".prop_map(Status::error),
+    ]
+}
+
+fn span_context(trace_id: u128, span_id: u64) -> SpanContext {
+    SpanContext::new(
+        TraceId::from(trace_id),
+        SpanId::from(span_id),
+        TraceFlags::SAMPLED,
+        false,
+        TraceState::default(),
+    )
+}
+
+prop_compose! {
+    fn event()(
+        // Few names, so that spans often have events with the same name
+        name in "[ab]{1,2}",
+        offset in 0u64..10_000_000,
+        attributes in attributes(3),
+    ) -> Event {
+        let start = UNIX_EPOCH + Duration::from_millis(1_600_000_000_000);
+        Event::new(name, start + Duration::from_micros(offset), attributes, 0)
+    }
+}
+
+prop_compose! {
+    fn link()(
+        trace_id in 1u128..,
+        span_id in 1u64..,
+        attributes in attributes(3),
+    ) -> Link {
+        Link::new(span_context(trace_id, span_id), attributes, 0)
+    }
+}
+
+prop_compose! {
+    fn span_data()(
+        trace_id in 1u128..,
+        span_id in 1u64..,
+        parent_span_id in prop::option::of(1u64..),
+        kind in span_kind(),
+        name in "[a-zA-Z0-9 /_-]{1,20}",
+        start_millis in 1_600_000_000_000u64..1_900_000_000_000,
+        start_nanos in 0u32..1_000_000,
+        duration in 0u64..10_000_000_000,
+        attributes in attributes(6),
+        events in prop::collection::vec(event(), 0..5),
+        dropped_events in 0u32..5,
+        links in prop::collection::vec(link(), 0..3),
+        dropped_links in 0u32..5,
+        status in status(),
+        scope_name in "[a-z]{1,10}",
+        scope_version in prop::option::of("[0-9]\\.[0-9]"),
+    ) -> SpanData {
+        let start_time =
+            UNIX_EPOCH + Duration::from_millis(start_millis) + Duration::from_nanos(start_nanos.into());
+        let mut scope = InstrumentationScope::builder(scope_name);
+        if let Some(version) = scope_version {
+            scope = scope.with_version(version);
+        }
+
+        SpanData {
+            span_context: span_context(trace_id, span_id),
+            parent_span_id: parent_span_id.map(SpanId::from).unwrap_or(SpanId::INVALID),
+            span_kind: kind,
+            name: name.into(),
+            start_time,
+            end_time: start_time + Duration::from_nanos(duration),
+            attributes,
+            dropped_attributes_count: 0,
+            events: span_events(events, dropped_events),
+            links: span_links(links, dropped_links),
+            status,
+            instrumentation_scope: scope.build(),
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_serialized_spans_match_schema(spans in prop::collection::vec(span_data(), 1..4)) {
+        let payload = serialize(&spans);
+
+        if let Err(violations) = validate_payload(&payload) {
+            let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+            prop_assert!(false, "schema violations: {:?}", violations);
+        }
+    }
+
+    #[test]
+    fn prop_serialized_spans_decode_to_original(spans in prop::collection::vec(span_data(), 1..4)) {
+        let decoded = decode_batch(&serialize(&spans)).unwrap();
+
+        prop_assert_eq!(decoded.len(), spans.len());
+        for (decoded, span) in decoded.iter().zip(&spans) {
+            let mismatches = decoded.mismatches(span);
+            prop_assert!(mismatches.is_empty(), "mismatches: {:?}", mismatches);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use opentelemetry::trace::{Span, SpanKind, Status, Tracer, TracerProvider};
+use opentelemetry::Key;
+use opentelemetry_instana::exporter::serialize_span;
+use opentelemetry_instana::{
+    InstanaExporter, StackTraceConfig, StackTraceMode, StackTraceSpanProcessor,
+};
+use opentelemetry_sdk::trace::{
+    InMemorySpanExporter, SdkTracerProvider, SimpleSpanProcessor, SpanData, TracerProviderBuilder,
+};
+use opentelemetry_sdk::Resource;
+use serde_json::Value;
+
+fn record_span(config: StackTraceConfig, kind: SpanKind, status: Status) -> SpanData {
+    record_span_with(SdkTracerProvider::builder(), config, kind, status)
+}
+
+fn record_span_with(
+    builder: TracerProviderBuilder,
+    config: StackTraceConfig,
+    kind: SpanKind,
+    status: Status,
+) -> SpanData {
+    let exporter = InMemorySpanExporter::default();
+    let processor =
+        StackTraceSpanProcessor::with_config(SimpleSpanProcessor::new(exporter.clone()), config);
+    let provider = builder.with_span_processor(processor).build();
+
+    let tracer = provider.tracer("stack-trace-test");
+    let mut span = tracer.span_builder("db-query").with_kind(kind).start(&tracer);
+    span.set_status(status);
+    span.end();
+
+    let mut spans = exporter.get_finished_spans().expect("failed to get spans");
+    assert_eq!(spans.len(), 1);
+    spans.remove(0)
+}
+
+fn stack_of(span: &SpanData) -> Option<Value> {
+    let exporter = InstanaExporter::builder()
+        .with_service(Resource::builder().with_service_name("test-service").build())
+        .build()
+        .expect("failed to build instana exporter");
+
+    let instana_span =
+        serialize_span::convert_to_instana_span(&exporter, span).expect("Failed to convert span");
+    let json_value = serde_json::to_value(&instana_span).expect("Failed to serialize span");
+
+    // The stack must never leak into the custom tags
+    let tags = &json_value["data"]["sdk"]["custom"]["tags"];
+    assert!(tags["attributes"]["INTERNAL_TAG_STACK"].is_null());
+
+    json_value.get("stack").cloned()
+}
+
+#[test]
+fn test_stack_trace_config_default_is_disabled() {
+    let config = StackTraceConfig::default();
+    assert_eq!(config.mode, StackTraceMode::None);
+    assert_eq!(config.length, 10);
+}
+
+#[test]
+fn test_stack_trace_config_from_env() {
+    temp_env::with_vars(
+        [
+            ("INSTANA_STACK_TRACE", Some("ALL")),
+            ("INSTANA_STACK_TRACE_LENGTH", Some("5")),
+        ],
+        || {
+            let config = StackTraceConfig::from_env();
+            assert_eq!(config.mode, StackTraceMode::All);
+            assert_eq!(config.length, 5);
+        },
+    );
+
+    temp_env::with_vars(
+        [
+            ("INSTANA_STACK_TRACE", Some("error")),
+            ("INSTANA_STACK_TRACE_LENGTH", Some("not-a-number")),
+        ],
+        || {
+            let config = StackTraceConfig::from_env();
+            assert_eq!(config.mode, StackTraceMode::Error);
+            assert_eq!(config.length, 10);
+        },
+    );
+
+    temp_env::with_vars_unset(["INSTANA_STACK_TRACE", "INSTANA_STACK_TRACE_LENGTH"], || {
+        assert_eq!(StackTraceConfig::from_env(), StackTraceConfig::default());
+    });
+}
+
+#[test]
+fn test_exit_span_has_stack_when_all() {
+    let config = StackTraceConfig {
+        mode: StackTraceMode::All,
+        length: 3,
+    };
+    let span = record_span(config, SpanKind::Client, Status::Unset);
+
+    let stack = stack_of(&span).expect("expected a stack on the exit span");
+    let frames = stack.as_array().expect("stack must be an array");
+    assert!(!frames.is_empty());
+    assert!(frames.len() <= 3);
+
+    // Frames of the tracing machinery are trimmed, so the top frame is this test
+    let top = frames[0]["m"].as_str().unwrap();
+    assert!(top.contains("stack_trace_tests"), "unexpected top frame {top}");
+}
+
+#[test]
+fn test_exit_stack_is_not_limited() {
+    let config = StackTraceConfig {
+        mode: StackTraceMode::All,
+        length: 3,
+    };
+    let builder = SdkTracerProvider::builder()
+        .with_max_attribute_value_length(8)
+        .with_max_attributes_per_span(0);
+    let span = record_span_with(builder, config, SpanKind::Client, Status::Unset);
+
+    let stack = stack_of(&span).expect("expected a stack on the exit span");
+    assert!(!stack.as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_entry_span_has_no_stack_when_all() {
+    let config = StackTraceConfig {
+        mode: StackTraceMode::All,
+        length: 10,
+    };
+    let span = record_span(config, SpanKind::Server, Status::Unset);
+
+    assert!(stack_of(&span).is_none());
+}
+
+#[test]
+fn test_error_span_has_stack_when_error() {
+    let config = StackTraceConfig {
+        mode: StackTraceMode::Error,
+        length: 10,
+    };
+
+    let span = record_span(config, SpanKind::Client, Status::Unset);
+    assert!(stack_of(&span).is_none());
+
+    let span = record_span(config, SpanKind::Internal, Status::error("boom"));
+    assert!(stack_of(&span).is_some());
+}
+
+#[test]
+fn test_no_stack_when_disabled() {
+    let config = StackTraceConfig::default();
+
+    let span = record_span(config, SpanKind::Client, Status::error("boom"));
+    assert!(stack_of(&span).is_none());
+    assert!(!span
+        .attributes
+        .iter()
+        .any(|kv| kv.key == Key::new("INTERNAL_TAG_STACK")));
+}
@@ -0,0 +1,153 @@
+//! Truncation of attribute values to the maximum length configured for a signal,
+//! as specified in [attribute limits].
+//!
+//! [attribute limits]: https://opentelemetry.io/docs/specs/otel/common/#attribute-limits
+#[cfg(feature = "logs")]
+use opentelemetry::logs::AnyValue;
+use opentelemetry::StringValue;
+#[cfg(feature = "trace")]
+use opentelemetry::{Array, Value};
+use std::env;
+
+/// Maximum length of the attribute values of all signals, overridden by the
+/// signal specific variables.
+const OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT: &str = "OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT";
+
+/// The maximum attribute value length set by `signal_var`, else by
+/// `OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT`.
+pub(crate) fn max_length_from_env(signal_var: &str) -> Option<u32> {
+    [signal_var, OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT]
+        .iter()
+        .find_map(|var| env::var(var).ok().and_then(|limit| limit.parse().ok()))
+}
+
+/// Truncate the string to at most `max_length` characters, so that it is cut
+/// at a UTF-8 character boundary.
+fn truncate_string(value: &mut StringValue, max_length: usize) {
+    if let Some((end, _)) = value.as_str().char_indices().nth(max_length) {
+        *value = StringValue::from(value.as_str()[..end].to_owned());
+    }
+}
+
+/// Truncate strings and the elements of string arrays, other values are kept.
+#[cfg(feature = "trace")]
+pub(crate) fn truncate_value(value: &mut Value, max_length: usize) {
+    match value {
+        Value::String(string) => truncate_string(string, max_length),
+        Value::Array(Array::String(strings)) => {
+            for string in strings {
+                truncate_string(string, max_length);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Truncate strings and the string elements of lists, other values are kept.
+#[cfg(feature = "logs")]
+pub(crate) fn truncate_any_value(value: &mut AnyValue, max_length: usize) {
+    match value {
+        AnyValue::String(string) => truncate_string(string, max_length),
+        AnyValue::ListAny(values) => {
+            for value in values.iter_mut() {
+                if let AnyValue::String(string) = value {
+                    truncate_string(string, max_length);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_at_character_boundary() {
+        for (value, max_length, expected) in [
+            ("hello", 10, "hello"),
+            ("hello", 5, "hello"),
+            ("hello", 3, "hel"),
+            ("hello", 0, ""),
+            // 2 bytes per character
+            ("héllö", 2, "hé"),
+            // 4 bytes per character
+            ("🦀🦀🦀", 1, "🦀"),
+        ] {
+            let mut string = StringValue::from(value);
+            truncate_string(&mut string, max_length);
+            assert_eq!(string.as_str(), expected);
+        }
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn truncate_values() {
+        let mut string = Value::from("abcdef");
+        truncate_value(&mut string, 2);
+        assert_eq!(string, Value::from("ab"));
+
+        let mut strings = Value::Array(Array::String(vec!["abc".into(), "de".into(), "f".into()]));
+        truncate_value(&mut strings, 2);
+        assert_eq!(
+            strings,
+            Value::Array(Array::String(vec!["ab".into(), "de".into(), "f".into()]))
+        );
+
+        let mut number = Value::I64(123456);
+        truncate_value(&mut number, 2);
+        assert_eq!(number, Value::I64(123456));
+    }
+
+    #[cfg(feature = "logs")]
+    #[test]
+    fn truncate_any_values() {
+        let mut list = AnyValue::ListAny(Box::new(vec![
+            AnyValue::from("abc"),
+            AnyValue::Int(123),
+            AnyValue::Bytes(Box::new(vec![1, 2, 3])),
+        ]));
+        truncate_any_value(&mut list, 2);
+        assert_eq!(
+            list,
+            AnyValue::ListAny(Box::new(vec![
+                AnyValue::from("ab"),
+                AnyValue::Int(123),
+                AnyValue::Bytes(Box::new(vec![1, 2, 3])),
+            ]))
+        );
+    }
+
+    #[test]
+    fn signal_variable_takes_precedence() {
+        temp_env::with_vars(
+            [
+                ("OTEL_SPAN_ATTRIBUTE_VALUE_LENGTH_LIMIT", Some("10")),
+                ("OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT", Some("20")),
+            ],
+            || {
+                assert_eq!(
+                    max_length_from_env("OTEL_SPAN_ATTRIBUTE_VALUE_LENGTH_LIMIT"),
+                    Some(10)
+                );
+                assert_eq!(
+                    max_length_from_env("OTEL_LOGRECORD_ATTRIBUTE_VALUE_LENGTH_LIMIT"),
+                    Some(20)
+                );
+            },
+        );
+        temp_env::with_vars(
+            [
+                ("OTEL_SPAN_ATTRIBUTE_VALUE_LENGTH_LIMIT", Some("ten")),
+                ("OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT", None::<&str>),
+            ],
+            || {
+                assert_eq!(
+                    max_length_from_env("OTEL_SPAN_ATTRIBUTE_VALUE_LENGTH_LIMIT"),
+                    None
+                );
+            },
+        );
+    }
+}
@@ -106,6 +106,17 @@ impl<
                 .chain(self.overflow.as_ref().unwrap().iter())
         }
     }
+
+    /// Returns an iterator over mutable references to the elements in the `GrowableArray`, in
+    /// the same order as [`GrowableArray::iter`].
+    #[allow(dead_code)]
+    #[inline]
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.inline
+            .iter_mut()
+            .take(self.count)
+            .chain(self.overflow.iter_mut().flatten())
+    }
 }
 
 // Implement `IntoIterator` for `GrowableArray`
@@ -83,6 +83,9 @@
 //! For `trace` the following feature flags are available:
 //!
 //! * `jaeger_remote_sampler`: Enables the [Jaeger remote sampler](https://www.jaegertracing.io/docs/1.53/sampling/).
+//! * `jaeger_remote_sampler_env`: Enables selecting the Jaeger remote sampler with `OTEL_TRACES_SAMPLER`,
+//!   polling the remote with a `reqwest` client on a dedicated Tokio thread.
+//! * `xray_remote_sampler`: Enables the [AWS X-Ray remote sampler](https://docs.aws.amazon.com/xray/latest/devguide/xray-console-sampling.html).
 //!
 //! For `logs` the following feature flags are available:
 //!
@@ -116,6 +119,8 @@
 )]
 #![cfg_attr(test, deny(warnings))]
 
+#[cfg(any(feature = "trace", feature = "logs"))]
+pub(crate) mod attribute_value;
 pub(crate) mod growable_array;
 
 #[cfg(feature = "logs")]
@@ -51,6 +51,9 @@ impl opentelemetry::logs::Logger for SdkLogger {
         if record.observed_timestamp.is_none() {
             record.observed_timestamp = Some(now());
         }
+        if let Some(max_length) = provider.max_attribute_value_length() {
+            record.truncate_attribute_values(max_length as usize);
+        }
 
         for p in processors {
             p.emit(&mut record, &self.scope);
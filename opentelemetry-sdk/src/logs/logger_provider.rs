@@ -1,4 +1,5 @@
 use super::{BatchLogProcessor, LogProcessor, SdkLogger, SimpleLogProcessor};
+use crate::attribute_value::max_length_from_env;
 use crate::error::{OTelSdkError, OTelSdkResult};
 use crate::logs::LogExporter;
 use crate::Resource;
@@ -22,6 +23,7 @@ fn noop_logger_provider() -> &'static SdkLoggerProvider {
         inner: Arc::new(LoggerProviderInner {
             processors: Vec::new(),
             is_shutdown: AtomicBool::new(true),
+            max_attribute_value_length: None,
         }),
     })
 }
@@ -82,6 +84,10 @@ impl SdkLoggerProvider {
         &self.inner.processors
     }
 
+    pub(crate) fn max_attribute_value_length(&self) -> Option<u32> {
+        self.inner.max_attribute_value_length
+    }
+
     /// Force flush all remaining logs in log processors and return results.
     pub fn force_flush(&self) -> OTelSdkResult {
         let result: Vec<_> = self
@@ -135,6 +141,7 @@ impl SdkLoggerProvider {
 struct LoggerProviderInner {
     processors: Vec<Box<dyn LogProcessor>>,
     is_shutdown: AtomicBool,
+    max_attribute_value_length: Option<u32>,
 }
 
 impl LoggerProviderInner {
@@ -184,6 +191,7 @@ impl Drop for LoggerProviderInner {
 pub struct LoggerProviderBuilder {
     processors: Vec<Box<dyn LogProcessor>>,
     resource: Option<Resource>,
+    max_attribute_value_length: Option<u32>,
 }
 
 impl LoggerProviderBuilder {
@@ -259,6 +267,18 @@ impl LoggerProviderBuilder {
         LoggerProviderBuilder { resource, ..self }
     }
 
+    /// Specify the max length of the attribute values of log records, longer strings, and
+    /// strings of lists, are truncated to this number of characters.
+    ///
+    /// By default it's read from `OTEL_LOGRECORD_ATTRIBUTE_VALUE_LENGTH_LIMIT` or
+    /// `OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT`, and unlimited if neither is set.
+    pub fn with_max_attribute_value_length(self, max_length: u32) -> Self {
+        LoggerProviderBuilder {
+            max_attribute_value_length: Some(max_length),
+            ..self
+        }
+    }
+
     /// Create a new provider from this configuration.
     pub fn build(self) -> SdkLoggerProvider {
         let resource = self.resource.unwrap_or(Resource::builder().build());
@@ -271,6 +291,9 @@ impl LoggerProviderBuilder {
             inner: Arc::new(LoggerProviderInner {
                 processors,
                 is_shutdown: AtomicBool::new(false),
+                max_attribute_value_length: self
+                    .max_attribute_value_length
+                    .or_else(|| max_length_from_env("OTEL_LOGRECORD_ATTRIBUTE_VALUE_LENGTH_LIMIT")),
             }),
         };
 
@@ -749,6 +772,7 @@ mod tests {
                     flush_called.clone(),
                 ))],
                 is_shutdown: AtomicBool::new(false),
+                max_attribute_value_length: None,
             });
 
             {
@@ -789,6 +813,7 @@ mod tests {
                 flush_called.clone(),
             ))],
             is_shutdown: AtomicBool::new(false),
+            max_attribute_value_length: None,
         });
 
         // Create a scope to test behavior when providers are dropped
@@ -857,6 +882,72 @@ mod tests {
         assert_eq!(log1.instrumentation.name(), "");
     }
 
+    #[test]
+    fn exceed_attribute_value_length_limit() {
+        let exporter = InMemoryLogExporter::default();
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .with_max_attribute_value_length(3)
+            .build();
+        let logger = logger_provider.logger("test-logger");
+        let mut record = logger.create_log_record();
+        record.set_body("the body is not an attribute".into());
+        // More attributes than are stored inline
+        for i in 0..6 {
+            record.add_attribute(format!("key{i}"), format!("value{i}"));
+        }
+        record.add_attribute("crabs", "🦀🦀🦀🦀");
+        record.add_attribute("count", 123456);
+        record.add_attribute(
+            "list",
+            AnyValue::ListAny(Box::new(vec!["abcdef".into(), AnyValue::Int(7)])),
+        );
+        logger.emit(record);
+
+        let emitted_logs = exporter.get_emitted_logs().unwrap();
+        let record = &emitted_logs[0].record;
+        assert_eq!(
+            record.body,
+            Some(AnyValue::String("the body is not an attribute".into()))
+        );
+        let attributes: Vec<_> = record
+            .attributes_iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let mut expected: Vec<_> = (0..6)
+            .map(|i| (Key::from(format!("key{i}")), AnyValue::from("val")))
+            .collect();
+        expected.extend([
+            (Key::from("crabs"), AnyValue::from("🦀🦀🦀")),
+            (Key::from("count"), AnyValue::Int(123456)),
+            (
+                Key::from("list"),
+                AnyValue::ListAny(Box::new(vec!["abc".into(), AnyValue::Int(7)])),
+            ),
+        ]);
+        assert_eq!(attributes, expected);
+    }
+
+    #[test]
+    fn attribute_value_length_limit_from_env() {
+        temp_env::with_vars(
+            [
+                ("OTEL_LOGRECORD_ATTRIBUTE_VALUE_LENGTH_LIMIT", Some("2")),
+                ("OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT", Some("4")),
+            ],
+            || {
+                let logger_provider = SdkLoggerProvider::builder().build();
+                assert_eq!(logger_provider.max_attribute_value_length(), Some(2));
+
+                // The builder takes precedence over the environment
+                let logger_provider = SdkLoggerProvider::builder()
+                    .with_max_attribute_value_length(8)
+                    .build();
+                assert_eq!(logger_provider.max_attribute_value_length(), Some(8));
+            },
+        );
+    }
+
     #[test]
     fn with_resource_multiple_calls_ensure_additive() {
         let builder = SdkLoggerProvider::builder()
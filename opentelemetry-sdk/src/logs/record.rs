@@ -1,3 +1,4 @@
+use crate::attribute_value::truncate_any_value;
 use crate::growable_array::GrowableArray;
 #[cfg(feature = "trace")]
 use opentelemetry::trace::SpanContext;
@@ -188,6 +189,13 @@ impl SdkLogRecord {
         self.attributes.iter().filter_map(|opt| opt.as_ref())
     }
 
+    /// Truncates the attribute values longer than `max_length` characters.
+    pub(crate) fn truncate_attribute_values(&mut self, max_length: usize) {
+        for (_, value) in self.attributes.iter_mut().flatten() {
+            truncate_any_value(value, max_length);
+        }
+    }
+
     #[allow(dead_code)]
     /// Returns the number of attributes in the `LogRecord`.
     pub(crate) fn attributes_len(&self) -> usize {
@@ -1,6 +1,8 @@
 //! OpenTelemetry Propagators
 mod baggage;
 mod trace_context;
+mod xray;
 
 pub use baggage::BaggagePropagator;
 pub use trace_context::TraceContextPropagator;
+pub use xray::XrayPropagator;
@@ -0,0 +1,276 @@
+//! # AWS X-Ray Propagator
+//!
+
+use opentelemetry::{
+    propagation::{text_map_propagator::FieldIter, Extractor, Injector, TextMapPropagator},
+    trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+    Context,
+};
+use std::sync::OnceLock;
+
+const XRAY_HEADER: &str = "x-amzn-trace-id";
+const TRACE_ID_VERSION: &str = "1";
+const ROOT_KEY: &str = "Root";
+const PARENT_KEY: &str = "Parent";
+const SAMPLED_KEY: &str = "Sampled";
+// Sampled values besides 0, the decision is deferred to the receiver with `?`
+const SAMPLED: &str = "1";
+const NOT_SAMPLED: &str = "0";
+const DEBUG: &str = "d";
+
+// TODO Replace this with LazyLock once it is stable.
+static XRAY_HEADER_FIELDS: OnceLock<[String; 1]> = OnceLock::new();
+
+fn xray_header_fields() -> &'static [String; 1] {
+    XRAY_HEADER_FIELDS.get_or_init(|| [XRAY_HEADER.to_owned()])
+}
+
+/// Propagates `SpanContext`s in [AWS X-Ray] format under the `X-Amzn-Trace-Id` header.
+///
+/// Here's an example of a `X-Amzn-Trace-Id` header.
+///
+/// `X-Amzn-Trace-Id: Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`
+///
+/// The root is the trace id, made of the version `1`, the epoch seconds of the start of the
+/// trace as 8 hex digits and 24 random hex digits, see [`XrayIdGenerator`]. The parent is the
+/// span id of the caller. Other fields of the header, such as `Lineage`, are not propagated.
+///
+/// [AWS X-Ray]: https://docs.aws.amazon.com/xray/latest/devguide/xray-concepts.html#xray-concepts-tracingheader
+/// [`XrayIdGenerator`]: crate::trace::XrayIdGenerator
+#[derive(Clone, Debug, Default)]
+pub struct XrayPropagator {
+    _private: (),
+}
+
+impl XrayPropagator {
+    /// Create a new `XrayPropagator`.
+    pub fn new() -> Self {
+        XrayPropagator { _private: () }
+    }
+
+    /// Extract span context from the X-Ray trace header.
+    fn extract_span_context(&self, extractor: &dyn Extractor) -> Result<SpanContext, ()> {
+        let header_value = extractor.get(XRAY_HEADER).ok_or(())?;
+
+        let mut trace_id = None;
+        let mut span_id = None;
+        let mut trace_flags = TraceFlags::default();
+        for part in header_value.split(';') {
+            let (key, value) = part.split_once('=').ok_or(())?;
+            match key.trim() {
+                ROOT_KEY => trace_id = Some(parse_trace_id(value.trim())?),
+                PARENT_KEY => {
+                    let value = value.trim();
+                    if value.len() != 16 {
+                        return Err(());
+                    }
+                    span_id = Some(SpanId::from_hex(value).map_err(|_| ())?);
+                }
+                SAMPLED_KEY => {
+                    if matches!(value.trim(), SAMPLED | DEBUG) {
+                        trace_flags = TraceFlags::SAMPLED;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let span_context = SpanContext::new(
+            trace_id.ok_or(())?,
+            span_id.ok_or(())?,
+            trace_flags,
+            true,
+            TraceState::default(),
+        );
+
+        // Ensure span is valid
+        if !span_context.is_valid() {
+            return Err(());
+        }
+
+        Ok(span_context)
+    }
+}
+
+// `1-<8 hex digits>-<24 hex digits>`
+fn parse_trace_id(root: &str) -> Result<TraceId, ()> {
+    let mut parts = root.split('-');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(TRACE_ID_VERSION), Some(epoch), Some(random), None)
+            if epoch.len() == 8 && random.len() == 24 =>
+        {
+            TraceId::from_hex(&format!("{epoch}{random}")).map_err(|_| ())
+        }
+        _ => Err(()),
+    }
+}
+
+impl TextMapPropagator for XrayPropagator {
+    /// Properly encodes the values of the `SpanContext` and injects them
+    /// into the `Injector`.
+    fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if span_context.is_valid() {
+            let trace_id = span_context.trace_id().to_string();
+            let sampled = if span_context.is_sampled() {
+                SAMPLED
+            } else {
+                NOT_SAMPLED
+            };
+            injector.set(
+                XRAY_HEADER,
+                format!(
+                    "{ROOT_KEY}={TRACE_ID_VERSION}-{}-{};{PARENT_KEY}={};{SAMPLED_KEY}={sampled}",
+                    &trace_id[..8],
+                    &trace_id[8..],
+                    span_context.span_id()
+                ),
+            );
+        }
+    }
+
+    /// Retrieves encoded `SpanContext`s using the `Extractor`. It decodes
+    /// the `SpanContext` and returns it. If no `SpanContext` was retrieved
+    /// OR if the retrieved SpanContext is invalid then the current `Context`
+    /// is returned.
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        self.extract_span_context(extractor)
+            .map(|sc| cx.with_remote_span_context(sc))
+            .unwrap_or_else(|_| cx.clone())
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        FieldIter::new(xray_header_fields())
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "trace"))]
+mod tests {
+    use super::*;
+    use crate::testing::trace::TestSpan;
+    use std::collections::HashMap;
+
+    const TRACE_ID: u128 = 0x5759_e988_bd86_2e3f_e1be_46a9_9427_2793;
+    const SPAN_ID: u64 = 0x5399_5c3f_42cd_8ad8;
+
+    #[rustfmt::skip]
+    fn extract_data() -> Vec<(&'static str, SpanContext)> {
+        vec![
+            ("Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1", SpanContext::new(TraceId::from(TRACE_ID), SpanId::from(SPAN_ID), TraceFlags::SAMPLED, true, TraceState::default())),
+            ("Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=0", SpanContext::new(TraceId::from(TRACE_ID), SpanId::from(SPAN_ID), TraceFlags::default(), true, TraceState::default())),
+            ("Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=?", SpanContext::new(TraceId::from(TRACE_ID), SpanId::from(SPAN_ID), TraceFlags::default(), true, TraceState::default())),
+            ("Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=d", SpanContext::new(TraceId::from(TRACE_ID), SpanId::from(SPAN_ID), TraceFlags::SAMPLED, true, TraceState::default())),
+            ("Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8", SpanContext::new(TraceId::from(TRACE_ID), SpanId::from(SPAN_ID), TraceFlags::default(), true, TraceState::default())),
+            ("Sampled=1; Parent=53995c3f42cd8ad8; Root=1-5759e988-bd862e3fe1be46a994272793; Lineage=a87bd80c:1|68fd508a:5", SpanContext::new(TraceId::from(TRACE_ID), SpanId::from(SPAN_ID), TraceFlags::SAMPLED, true, TraceState::default())),
+            ("Root=1-5759e988-bd862e3fe1be46a994272793;Self=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1", SpanContext::new(TraceId::from(TRACE_ID), SpanId::from(SPAN_ID), TraceFlags::SAMPLED, true, TraceState::default())),
+        ]
+    }
+
+    #[rustfmt::skip]
+    fn extract_data_invalid() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("", "empty header"),
+            ("Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=1", "missing parent"),
+            ("Parent=53995c3f42cd8ad8;Sampled=1", "missing root"),
+            ("Root=2-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8", "wrong version"),
+            ("Root=1-5759e988bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8", "missing delimiter"),
+            ("Root=1-5759e98-8bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8", "wrong epoch length"),
+            ("Root=1-5759e988-bd862e3fe1be46a99427279;Parent=53995c3f42cd8ad8", "wrong trace ID length"),
+            ("Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8a", "wrong span ID length"),
+            ("Root=1-qw59e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8", "bogus trace ID"),
+            ("Root=1-5759e988-bd862e3fe1be46a994272793;Parent=qw995c3f42cd8ad8", "bogus span ID"),
+            ("Root=1-00000000-000000000000000000000000;Parent=0000000000000000", "zero trace ID and span ID"),
+            ("Root", "missing value"),
+        ]
+    }
+
+    #[test]
+    fn extract_xray() {
+        let propagator = XrayPropagator::new();
+
+        for (header, expected_context) in extract_data() {
+            let mut extractor = HashMap::new();
+            extractor.insert(XRAY_HEADER.to_string(), header.to_string());
+
+            assert_eq!(
+                propagator.extract(&extractor).span().span_context(),
+                &expected_context,
+                "{header}"
+            )
+        }
+    }
+
+    #[test]
+    fn extract_xray_reject_invalid() {
+        let propagator = XrayPropagator::new();
+
+        for (invalid_header, reason) in extract_data_invalid() {
+            let mut extractor = HashMap::new();
+            extractor.insert(XRAY_HEADER.to_string(), invalid_header.to_string());
+
+            assert_eq!(
+                propagator.extract(&extractor).span().span_context(),
+                &SpanContext::empty_context(),
+                "{reason}"
+            )
+        }
+    }
+
+    #[test]
+    fn inject_xray() {
+        let propagator = XrayPropagator::new();
+
+        for (expected, flags) in [
+            (
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1",
+                TraceFlags::SAMPLED,
+            ),
+            (
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=0",
+                TraceFlags::default(),
+            ),
+        ] {
+            let mut injector = HashMap::new();
+            propagator.inject_context(
+                &Context::current_with_span(TestSpan(SpanContext::new(
+                    TraceId::from(TRACE_ID),
+                    SpanId::from(SPAN_ID),
+                    flags,
+                    true,
+                    TraceState::default(),
+                ))),
+                &mut injector,
+            );
+
+            assert_eq!(
+                injector.get(XRAY_HEADER).map(String::as_str),
+                Some(expected)
+            );
+        }
+
+        let mut injector = HashMap::new();
+        propagator.inject_context(
+            &Context::current_with_span(TestSpan(SpanContext::empty_context())),
+            &mut injector,
+        );
+        assert!(injector.is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let propagator = XrayPropagator::new();
+        let mut headers = HashMap::new();
+        headers.insert(
+            XRAY_HEADER.to_string(),
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1"
+                .to_string(),
+        );
+
+        let cx = propagator.extract(&headers);
+        let mut injector = HashMap::new();
+        propagator.inject_context(&cx, &mut injector);
+
+        assert_eq!(injector, headers);
+    }
+}
@@ -2,7 +2,10 @@
 //!
 //! Configuration represents the global tracing configuration, overrides
 //! can be set for the default OpenTelemetry limits and Sampler.
-use crate::trace::{span_limit::SpanLimits, IdGenerator, RandomIdGenerator, Sampler, ShouldSample};
+use crate::attribute_value::max_length_from_env;
+use crate::trace::{
+    span_limit::SpanLimits, IdGenerator, RandomIdGenerator, RuleBasedSampler, Sampler, ShouldSample,
+};
 use crate::Resource;
 use opentelemetry::otel_warn;
 use std::borrow::Cow;
@@ -24,6 +27,11 @@ pub struct Config {
 
     /// Contains attributes representing an entity that produces telemetry.
     pub resource: Cow<'static, Resource>,
+
+    /// `OTEL_TRACES_SAMPLER` and `OTEL_TRACES_SAMPLER_ARG` selecting a jaeger remote sampler,
+    /// built by the provider once its resource, and so the service name, is known.
+    #[cfg(feature = "jaeger_remote_sampler_env")]
+    pub(crate) jaeger_remote_env: Option<(String, Option<String>)>,
 }
 
 impl Default for Config {
@@ -34,6 +42,8 @@ impl Default for Config {
             id_generator: Box::<RandomIdGenerator>::default(),
             span_limits: SpanLimits::default(),
             resource: Cow::Owned(Resource::builder().build()),
+            #[cfg(feature = "jaeger_remote_sampler_env")]
+            jaeger_remote_env: None,
         };
 
         if let Some(max_attributes_per_span) = env::var("OTEL_SPAN_ATTRIBUTE_COUNT_LIMIT")
@@ -57,6 +67,12 @@ impl Default for Config {
             config.span_limits.max_links_per_span = max_links_per_span;
         }
 
+        if let Some(max_attribute_value_length) =
+            max_length_from_env("OTEL_SPAN_ATTRIBUTE_VALUE_LENGTH_LIMIT")
+        {
+            config.span_limits.max_attribute_value_length = Some(max_attribute_value_length);
+        }
+
         let sampler_arg = env::var("OTEL_TRACES_SAMPLER_ARG").ok();
         if let Ok(sampler) = env::var("OTEL_TRACES_SAMPLER") {
             config.sampler = match sampler.as_str() {
@@ -98,24 +114,73 @@ impl Default for Config {
                         ))))
                     }
                 }
-                "parentbased_jaeger_remote" => {
-                    otel_warn!(
-                        name: "TracerProvider.Config.UnsupportedSampler",
-                        message = "OTEL_TRACES_SAMPLER is set to 'parentbased_jaeger_remote' which is not implemented in this SDK version. Using fallback sampler: ParentBased(AlwaysOn). Configure an alternative sampler using OTEL_TRACES_SAMPLER"
-                    );
+                "ratelimited" | "parentbased_ratelimited" => {
+                    let traces_per_second =
+                        sampler_arg.as_ref().and_then(|r| r.parse::<f64>().ok());
+                    match traces_per_second {
+                        Some(rate) if sampler == "ratelimited" => {
+                            Box::new(Sampler::rate_limited(rate))
+                        }
+                        Some(rate) => {
+                            Box::new(Sampler::ParentBased(Box::new(Sampler::rate_limited(rate))))
+                        }
+                        None => {
+                            otel_warn!(
+                                name: "TracerProvider.Config.InvalidSamplerArgument",
+                                message = format!(
+                                    "OTEL_TRACES_SAMPLER is set to '{}' but OTEL_TRACES_SAMPLER_ARG environment variable is missing or invalid. OTEL_TRACES_SAMPLER_ARG must be a valid float representing the maximum number of traces sampled per second. Using fallback sampler: ParentBased(AlwaysOn)",
+                                    sampler
+                                ),
+                                otel_traces_sampler_arg = format!("{:?}", sampler_arg)
+                            );
+                            Box::new(Sampler::ParentBased(Box::new(Sampler::AlwaysOn)))
+                        }
+                    }
+                }
+                "rule_based" | "parentbased_rule_based" => {
+                    match sampler_arg.as_deref().map(RuleBasedSampler::from_config) {
+                        Some(Ok(rule_based)) if sampler == "rule_based" => Box::new(rule_based),
+                        Some(Ok(rule_based)) => {
+                            Box::new(Sampler::ParentBased(Box::new(rule_based)))
+                        }
+                        result => {
+                            otel_warn!(
+                                name: "TracerProvider.Config.InvalidSamplerArgument",
+                                message = format!(
+                                    "OTEL_TRACES_SAMPLER is set to '{}' but OTEL_TRACES_SAMPLER_ARG environment variable is missing or invalid. OTEL_TRACES_SAMPLER_ARG must list the sampling rules, e.g. 'name=GET /health => always_off; * => traceidratio:0.1'. Using fallback sampler: ParentBased(AlwaysOn)",
+                                    sampler
+                                ),
+                                otel_traces_sampler_arg = format!("{:?}", sampler_arg),
+                                error = format!(
+                                    "{:?}",
+                                    result.as_ref().and_then(|result| result.as_ref().err())
+                                )
+                            );
+                            Box::new(Sampler::ParentBased(Box::new(Sampler::AlwaysOn)))
+                        }
+                    }
+                }
+                #[cfg(feature = "jaeger_remote_sampler_env")]
+                "jaeger_remote" | "parentbased_jaeger_remote" => {
+                    // Polling needs the service name of the resource, see `build_jaeger_remote_env_sampler`
+                    config.jaeger_remote_env = Some((sampler.clone(), sampler_arg.clone()));
                     Box::new(Sampler::ParentBased(Box::new(Sampler::AlwaysOn)))
                 }
-                "jaeger_remote" => {
+                #[cfg(not(feature = "jaeger_remote_sampler_env"))]
+                "jaeger_remote" | "parentbased_jaeger_remote" => {
                     otel_warn!(
                         name: "TracerProvider.Config.UnsupportedSampler",
-                        message = "OTEL_TRACES_SAMPLER is set to 'jaeger_remote' which is implemented in this SDK version. Using fallback sampler: ParentBased(AlwaysOn). Configure an alternative sampler using OTEL_TRACES_SAMPLER"
+                        message = format!(
+                            "OTEL_TRACES_SAMPLER is set to '{}' which requires the jaeger_remote_sampler_env feature of this SDK. Using fallback sampler: ParentBased(AlwaysOn). Configure an alternative sampler using OTEL_TRACES_SAMPLER",
+                            sampler
+                        )
                     );
                     Box::new(Sampler::ParentBased(Box::new(Sampler::AlwaysOn)))
                 }
                 "xray" => {
                     otel_warn!(
                         name: "TracerProvider.Config.UnsupportedSampler",
-                        message = "OTEL_TRACES_SAMPLER is set to 'xray'. AWS X-Ray sampler is not implemented in this SDK version. Using fallback sampler: ParentBased(AlwaysOn). Configure an alternative sampler using OTEL_TRACES_SAMPLER"
+                        message = "OTEL_TRACES_SAMPLER is set to 'xray'. The AWS X-Ray remote sampler needs an HTTP client and a runtime and cannot be created from environment variables, use Sampler::xray_remote with the xray_remote_sampler feature instead. Using fallback sampler: ParentBased(AlwaysOn). Configure an alternative sampler using OTEL_TRACES_SAMPLER"
                     );
                     Box::new(Sampler::ParentBased(Box::new(Sampler::AlwaysOn)))
                 }
@@ -123,7 +188,7 @@ impl Default for Config {
                     otel_warn!(
                         name: "TracerProvider.Config.InvalidSamplerType",
                         message = format!(
-                            "Unrecognized sampler type '{}' in OTEL_TRACES_SAMPLER environment variable. Valid values are: always_on, always_off, traceidratio, parentbased_always_on, parentbased_always_off, parentbased_traceidratio. Using fallback sampler: ParentBased(AlwaysOn)",
+                            "Unrecognized sampler type '{}' in OTEL_TRACES_SAMPLER environment variable. Valid values are: always_on, always_off, traceidratio, parentbased_always_on, parentbased_always_off, parentbased_traceidratio, ratelimited, parentbased_ratelimited, rule_based, parentbased_rule_based, jaeger_remote, parentbased_jaeger_remote. Using fallback sampler: ParentBased(AlwaysOn)",
                             s
                         ),
                     );
@@ -135,3 +200,40 @@ impl Default for Config {
         config
     }
 }
+
+#[cfg(feature = "jaeger_remote_sampler_env")]
+impl Config {
+    /// Start the jaeger remote sampler selected with `OTEL_TRACES_SAMPLER`, polling the
+    /// strategies of the service of the resource, unless another sampler was set since.
+    pub(crate) fn build_jaeger_remote_env_sampler(&mut self) {
+        let Some((sampler, sampler_arg)) = self.jaeger_remote_env.take() else {
+            return;
+        };
+        let service_name = self
+            .resource
+            .get(&opentelemetry::Key::from_static_str(
+                crate::resource::SERVICE_NAME,
+            ))
+            .map(|service_name| service_name.to_string())
+            .unwrap_or_default();
+        self.sampler = match crate::trace::sampler::jaeger_remote_from_env(
+            sampler_arg.as_deref(),
+            &service_name,
+        ) {
+            Ok(remote) if sampler == "jaeger_remote" => Box::new(remote),
+            Ok(remote) => Box::new(Sampler::ParentBased(Box::new(remote))),
+            Err(reason) => {
+                otel_warn!(
+                    name: "TracerProvider.Config.InvalidSamplerArgument",
+                    message = format!(
+                        "OTEL_TRACES_SAMPLER is set to '{}' but OTEL_TRACES_SAMPLER_ARG environment variable is invalid. OTEL_TRACES_SAMPLER_ARG must be a comma separated list of endpoint=<url>, pollingIntervalMs=<milliseconds> and initialSamplingRate=<float between 0 and 1>. Using fallback sampler: ParentBased(AlwaysOn)",
+                        sampler
+                    ),
+                    otel_traces_sampler_arg = format!("{:?}", sampler_arg),
+                    reason = format!("{}", reason)
+                );
+                Box::new(Sampler::ParentBased(Box::new(Sampler::AlwaysOn)))
+            }
+        };
+    }
+}
@@ -3,6 +3,10 @@ use rand::{rngs, Rng, SeedableRng};
 use std::cell::RefCell;
 use std::fmt;
 
+mod xray;
+
+pub use xray::XrayIdGenerator;
+
 /// Interface for generating IDs
 pub trait IdGenerator: Send + Sync + fmt::Debug {
     /// Generate a new `TraceId`
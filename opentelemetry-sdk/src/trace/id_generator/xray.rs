@@ -0,0 +1,58 @@
+use super::{IdGenerator, RandomIdGenerator, CURRENT_RNG};
+use opentelemetry::time::now;
+use opentelemetry::trace::{SpanId, TraceId};
+use rand::Rng;
+use std::time::SystemTime;
+
+/// [`IdGenerator`] generating trace ids accepted by AWS X-Ray.
+///
+/// X-Ray trace ids start with the epoch seconds of the start of the trace, and X-Ray
+/// rejects the traces started more than 30 days ago. This generator puts the epoch
+/// seconds in the first 4 bytes of the trace id, followed by 12 random bytes. Span ids
+/// are random.
+#[derive(Clone, Debug, Default)]
+pub struct XrayIdGenerator {
+    _private: (),
+}
+
+impl IdGenerator for XrayIdGenerator {
+    fn new_trace_id(&self) -> TraceId {
+        let epoch_seconds = now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as u32)
+            .unwrap_or_default();
+        let random = CURRENT_RNG.with(|rng| rng.borrow_mut().random::<u128>());
+        TraceId::from(((epoch_seconds as u128) << 96) | (random >> 32))
+    }
+
+    fn new_span_id(&self) -> SpanId {
+        RandomIdGenerator::default().new_span_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_id_starts_with_the_epoch_seconds() {
+        let before = now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let trace_id = XrayIdGenerator::default().new_trace_id();
+        let after = now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let bytes = trace_id.to_bytes();
+        let epoch_seconds = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as u64;
+        assert!((before..=after).contains(&epoch_seconds));
+        assert_ne!(
+            XrayIdGenerator::default().new_trace_id().to_bytes()[4..],
+            bytes[4..]
+        );
+        assert_ne!(XrayIdGenerator::default().new_span_id(), SpanId::INVALID);
+    }
+}
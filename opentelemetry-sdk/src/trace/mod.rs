@@ -20,6 +20,7 @@ mod span_processor;
 #[cfg(feature = "experimental_trace_batch_span_processor_with_async_runtime")]
 /// Experimental feature to use async runtime with batch span processor.
 pub mod span_processor_with_async_runtime;
+mod tail_sampling;
 mod tracer;
 
 pub use config::Config;
@@ -35,22 +36,30 @@ pub mod in_memory_exporter;
 #[cfg_attr(docsrs, doc(cfg(any(feature = "testing", test))))]
 pub use in_memory_exporter::{InMemorySpanExporter, InMemorySpanExporterBuilder};
 
-pub use id_generator::{IdGenerator, RandomIdGenerator};
+pub use id_generator::{IdGenerator, RandomIdGenerator, XrayIdGenerator};
 pub use links::SpanLinks;
 pub use provider::{SdkTracerProvider, TracerProviderBuilder};
-pub use sampler::{Sampler, ShouldSample};
+pub use sampler::{
+    NameMatcher, RateLimitingSampler, RuleBasedSampler, RuleConfigError, Sampler, SamplingRule,
+    ShouldSample,
+};
 pub use span::Span;
 pub use span_limit::SpanLimits;
 pub use span_processor::{
     BatchConfig, BatchConfigBuilder, BatchSpanProcessor, BatchSpanProcessorBuilder,
     SimpleSpanProcessor, SpanProcessor,
 };
+pub use tail_sampling::{
+    TailSamplingPolicy, TailSamplingSpanProcessor, TailSamplingSpanProcessorBuilder,
+};
 
 pub use tracer::SdkTracer;
 pub use tracer::SdkTracer as Tracer; // for back-compat else tracing-opentelemetry won't build
 
 #[cfg(feature = "jaeger_remote_sampler")]
 pub use sampler::{JaegerRemoteSampler, JaegerRemoteSamplerBuilder};
+#[cfg(feature = "xray_remote_sampler")]
+pub use sampler::{XrayRemoteSampler, XrayRemoteSamplerBuilder};
 
 #[cfg(feature = "experimental_trace_batch_span_processor_with_async_runtime")]
 #[cfg(test)]
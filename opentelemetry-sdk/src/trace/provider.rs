@@ -94,6 +94,8 @@ fn noop_tracer_provider() -> &'static SdkTracerProvider {
                     id_generator: Box::<RandomIdGenerator>::default(),
                     span_limits: SpanLimits::default(),
                     resource: Cow::Owned(Resource::empty()),
+                    #[cfg(feature = "jaeger_remote_sampler_env")]
+                    jaeger_remote_env: None,
                 },
                 is_shutdown: AtomicBool::new(true),
             }),
@@ -357,6 +359,10 @@ impl TracerProviderBuilder {
     /// Specify the sampler to be used.
     pub fn with_sampler<T: crate::trace::ShouldSample + 'static>(mut self, sampler: T) -> Self {
         self.config.sampler = Box::new(sampler);
+        #[cfg(feature = "jaeger_remote_sampler_env")]
+        {
+            self.config.jaeger_remote_env = None;
+        }
         self
     }
 
@@ -396,6 +402,12 @@ impl TracerProviderBuilder {
         self
     }
 
+    /// Specify the max length of attribute values, longer values are truncated.
+    pub fn with_max_attribute_value_length(mut self, max_length: u32) -> Self {
+        self.config.span_limits.max_attribute_value_length = Some(max_length);
+        self
+    }
+
     /// Specify all limit via the span_limits
     pub fn with_span_limits(mut self, span_limits: SpanLimits) -> Self {
         self.config.span_limits = span_limits;
@@ -431,6 +443,11 @@ impl TracerProviderBuilder {
             config.resource = Cow::Owned(resource);
         };
 
+        // The jaeger remote sampler of `OTEL_TRACES_SAMPLER` polls the strategies of the service
+        // of the final resource.
+        #[cfg(feature = "jaeger_remote_sampler_env")]
+        config.build_jaeger_remote_env_sampler();
+
         // Standard config will contain an owned [`Resource`] (either sdk default or use supplied)
         // we can optimize the common case with a static ref to avoid cloning the underlying
         // resource data for each span.
@@ -5,13 +5,24 @@ use opentelemetry::{
     Context, KeyValue,
 };
 
+mod consistent;
 #[cfg(feature = "jaeger_remote_sampler")]
 mod jaeger_remote;
+mod rate_limit;
+mod rule_based;
+#[cfg(feature = "xray_remote_sampler")]
+mod xray_remote;
 
+#[cfg(feature = "jaeger_remote_sampler_env")]
+pub(crate) use jaeger_remote::sampler_from_env as jaeger_remote_from_env;
 #[cfg(feature = "jaeger_remote_sampler")]
 pub use jaeger_remote::{JaegerRemoteSampler, JaegerRemoteSamplerBuilder};
-#[cfg(feature = "jaeger_remote_sampler")]
+#[cfg(any(feature = "jaeger_remote_sampler", feature = "xray_remote_sampler"))]
 use opentelemetry_http::HttpClient;
+pub use rate_limit::RateLimitingSampler;
+pub use rule_based::{NameMatcher, RuleBasedSampler, RuleConfigError, SamplingRule};
+#[cfg(feature = "xray_remote_sampler")]
+pub use xray_remote::{XrayRemoteSampler, XrayRemoteSamplerBuilder};
 
 /// The [`ShouldSample`] interface allows implementations to provide samplers
 /// which will return a sampling [`SamplingResult`] based on information that
@@ -123,6 +134,18 @@ pub enum Sampler {
     /// *Note:* If this is used then all Spans in a trace will become sampled assuming that the
     /// first span is sampled as it is based on the `trace_id` not the `span_id`
     TraceIdRatioBased(f64),
+    /// Sample at most a given number of traces per second, see [`Sampler::rate_limited`].
+    /// Typically used inside [`Sampler::ParentBased`], so that it only limits the
+    /// traces started by this process.
+    RateLimited(RateLimitingSampler),
+    /// Sample a given fraction of traces consistently across services, as specified by
+    /// [OTEP 235](https://github.com/open-telemetry/oteps/blob/main/text/trace/0235-sampling-threshold-in-trace-state.md).
+    /// The decision compares the rejection threshold of the probability with the randomness
+    /// of the trace, the `rv` value of the `ot` trace state entry if present, else the low
+    /// 56 bits of the trace id. Sampled spans record the threshold as `ot=th:<threshold>` in
+    /// their trace state, which backends use to compute adjusted counts. Use it inside
+    /// [`Sampler::ParentBased`] so that child spans keep the threshold of their root.
+    ConsistentProbabilityBased(f64),
     /// Jaeger remote sampler supports any remote service that implemented the jaeger remote sampler protocol.
     /// The proto definition can be found [here](https://github.com/jaegertracing/jaeger-idl/blob/main/proto/api_v2/sampling.proto)
     ///
@@ -133,9 +156,22 @@ pub enum Sampler {
     /// given service (a.k.a per operation).
     #[cfg(feature = "jaeger_remote_sampler")]
     JaegerRemote(JaegerRemoteSampler),
+    /// AWS X-Ray remote sampler applies the sampling rules configured in X-Ray, polled from
+    /// the X-Ray daemon or a collector proxying the X-Ray sampling API.
+    ///
+    /// Each rule samples a number of requests per second, shared between the instances of the
+    /// service by X-Ray, and a fixed fraction of the rest.
+    #[cfg(feature = "xray_remote_sampler")]
+    XrayRemote(XrayRemoteSampler),
 }
 
 impl Sampler {
+    /// Create a sampler sampling at most `traces_per_second` traces per second.
+    /// A rate of zero or less samples nothing.
+    pub fn rate_limited(traces_per_second: f64) -> Self {
+        Sampler::RateLimited(RateLimitingSampler::new(traces_per_second))
+    }
+
     /// Create a jaeger remote sampler builder.
     ///
     /// ### Arguments
@@ -160,6 +196,29 @@ impl Sampler {
     {
         JaegerRemoteSamplerBuilder::new(runtime, http_client, default_sampler, service_name)
     }
+
+    /// Create an AWS X-Ray remote sampler builder.
+    ///
+    /// ### Arguments
+    /// * `runtime` - A runtime to run the HTTP client.
+    /// * `http_client` - An HTTP client to query the X-Ray sampling API.
+    /// * `default_sampler` - A default sampler to make a sampling decision before the SDK receives the first rules, or when no rule matches.
+    /// * `service_name` - The name of the service, matched by the `ServiceName` of the rules.
+    #[cfg(feature = "xray_remote_sampler")]
+    pub fn xray_remote<C, Sampler, R, Svc>(
+        runtime: R,
+        http_client: C,
+        default_sampler: Sampler,
+        service_name: Svc,
+    ) -> XrayRemoteSamplerBuilder<C, Sampler, R>
+    where
+        C: HttpClient + 'static,
+        Sampler: ShouldSample,
+        R: crate::runtime::RuntimeChannel,
+        Svc: Into<String>,
+    {
+        XrayRemoteSamplerBuilder::new(runtime, http_client, default_sampler, service_name)
+    }
 }
 
 impl ShouldSample for Sampler {
@@ -178,22 +237,9 @@ impl ShouldSample for Sampler {
             // Never sample the trace
             Sampler::AlwaysOff => SamplingDecision::Drop,
             // The parent decision if sampled; otherwise the decision of delegate_sampler
-            Sampler::ParentBased(delegate_sampler) => parent_context
-                .filter(|cx| cx.has_active_span())
-                .map_or_else(
-                    || {
-                        delegate_sampler
-                            .should_sample(
-                                parent_context,
-                                trace_id,
-                                name,
-                                span_kind,
-                                attributes,
-                                links,
-                            )
-                            .decision
-                    },
-                    |ctx| {
+            Sampler::ParentBased(delegate_sampler) => {
+                match parent_context.filter(|cx| cx.has_active_span()) {
+                    Some(ctx) => {
                         let span = ctx.span();
                         let parent_span_context = span.span_context();
                         if parent_span_context.is_sampled() {
@@ -201,22 +247,52 @@ impl ShouldSample for Sampler {
                         } else {
                             SamplingDecision::Drop
                         }
-                    },
-                ),
+                    }
+                    None => {
+                        let result = delegate_sampler.should_sample(
+                            parent_context,
+                            trace_id,
+                            name,
+                            span_kind,
+                            attributes,
+                            links,
+                        );
+                        // Keep the trace state of the delegate, where consistent samplers
+                        // record their threshold
+                        return SamplingResult {
+                            decision: result.decision,
+                            attributes: Vec::new(),
+                            trace_state: result.trace_state,
+                        };
+                    }
+                }
+            }
             // Probabilistically sample the trace.
             Sampler::TraceIdRatioBased(prob) => sample_based_on_probability(prob, trace_id),
+            // Sample while the budget of traces per second allows it
+            Sampler::RateLimited(rate_limited) => rate_limited.decide(),
+            // Consistently sample the trace, recording the threshold in the trace state
+            Sampler::ConsistentProbabilityBased(probability) => {
+                return consistent::should_sample(*probability, parent_context, trace_id);
+            }
             #[cfg(feature = "jaeger_remote_sampler")]
             Sampler::JaegerRemote(remote_sampler) => {
                 remote_sampler
                     .should_sample(parent_context, trace_id, name, span_kind, attributes, links)
                     .decision
             }
+            #[cfg(feature = "xray_remote_sampler")]
+            Sampler::XrayRemote(remote_sampler) => {
+                remote_sampler
+                    .should_sample(parent_context, trace_id, name, span_kind, attributes, links)
+                    .decision
+            }
         };
         SamplingResult {
             decision,
             // No extra attributes ever set by the SDK samplers.
             attributes: Vec::new(),
-            // all sampler in SDK will not modify trace state.
+            // the other samplers in SDK will not modify trace state.
             trace_state: match parent_context {
                 Some(ctx) => ctx.span().span_context().trace_state().clone(),
                 None => TraceState::default(),
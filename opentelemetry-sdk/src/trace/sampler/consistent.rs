@@ -0,0 +1,342 @@
+//! Consistent probability sampling, as specified by [OTEP 235].
+//!
+//! The decision compares a 56 bit randomness value, taken from the `rv` entry
+//! of the `ot` trace state or else from the low 56 bits of the trace id, with
+//! a rejection threshold: spans are sampled if the randomness is at least the
+//! threshold. Every service sampling the same trace with the same probability
+//! therefore makes the same decision. The threshold of sampled spans is
+//! recorded as `th` in the `ot` trace state, so that backends can compute the
+//! adjusted count of each span, `2^56 / (2^56 - threshold)`.
+//!
+//! [OTEP 235]: https://github.com/open-telemetry/oteps/blob/main/text/trace/0235-sampling-threshold-in-trace-state.md
+
+use opentelemetry::trace::{
+    SamplingDecision, SamplingResult, TraceContextExt, TraceId, TraceState,
+};
+use opentelemetry::{otel_debug, Context};
+
+/// The trace state key of the OpenTelemetry entry.
+const OT_KEY: &str = "ot";
+/// The sub-key of the rejection threshold in the OpenTelemetry entry.
+const THRESHOLD_KEY: &str = "th";
+/// The sub-key of the explicit randomness value in the OpenTelemetry entry.
+const RANDOMNESS_KEY: &str = "rv";
+/// Number of hex digits of thresholds and randomness values.
+const HEX_DIGITS: usize = 14;
+/// Thresholds are in `[0, 2^56)`, a threshold of 2^56 would reject everything.
+const MAX_THRESHOLD: u64 = 1 << 56;
+/// Number of hex digits kept when converting a probability to a threshold.
+const THRESHOLD_PRECISION: u32 = 4;
+
+/// The rejection threshold sampling with the probability, `None` if it never
+/// samples.
+///
+/// The threshold is rounded to 4 hex digits to keep the trace state short, or
+/// kept exact if rounding would make a small probability zero.
+pub(crate) fn threshold(probability: f64) -> Option<u64> {
+    if probability.is_nan() || probability <= 0.0 {
+        return None;
+    }
+    if probability >= 1.0 {
+        return Some(0);
+    }
+    let scale = (1u64 << (4 * THRESHOLD_PRECISION)) as f64;
+    let rounded = ((1.0 - probability) * scale).round() as u64;
+    let threshold = rounded << (4 * (HEX_DIGITS as u32 - THRESHOLD_PRECISION));
+    if threshold < MAX_THRESHOLD {
+        return Some(threshold);
+    }
+    let exact = ((1.0 - probability) * MAX_THRESHOLD as f64).round() as u64;
+    Some(exact.min(MAX_THRESHOLD - 1))
+}
+
+/// Encode the threshold without its trailing zeros, as `0` if it is zero.
+pub(crate) fn encode_threshold(threshold: u64) -> String {
+    let encoded = format!("{:0width$x}", threshold, width = HEX_DIGITS);
+    match encoded.trim_end_matches('0') {
+        "" => "0".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+/// The value of a sub-key of the OpenTelemetry trace state entry.
+fn ot_value<'a>(trace_state: &'a TraceState, key: &str) -> Option<&'a str> {
+    trace_state.get(OT_KEY)?.split(';').find_map(|entry| {
+        entry
+            .split_once(':')
+            .filter(|(entry_key, _)| *entry_key == key)
+            .map(|(_, value)| value)
+    })
+}
+
+/// The randomness of the trace, from the `rv` trace state value or the trace id.
+pub(crate) fn randomness(trace_state: &TraceState, trace_id: TraceId) -> u64 {
+    ot_value(trace_state, RANDOMNESS_KEY)
+        .filter(|value| value.len() == HEX_DIGITS)
+        .and_then(|value| u64::from_str_radix(value, 16).ok())
+        .unwrap_or_else(|| {
+            let bytes = trace_id.to_bytes();
+            u64::from_be_bytes(bytes[8..16].try_into().unwrap()) & (MAX_THRESHOLD - 1)
+        })
+}
+
+/// Set or remove the threshold, keeping the other OpenTelemetry sub-keys.
+fn with_threshold(trace_state: &TraceState, threshold: Option<u64>) -> TraceState {
+    let mut entries: Vec<String> = trace_state
+        .get(OT_KEY)
+        .map(|value| {
+            value
+                .split(';')
+                .filter(|entry| !entry.is_empty() && entry.split(':').next() != Some(THRESHOLD_KEY))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(threshold) = threshold {
+        entries.insert(
+            0,
+            format!("{}:{}", THRESHOLD_KEY, encode_threshold(threshold)),
+        );
+    }
+
+    let updated = if entries.is_empty() {
+        trace_state.delete(OT_KEY)
+    } else {
+        trace_state.insert(OT_KEY, entries.join(";"))
+    };
+    updated.unwrap_or_else(|err| {
+        otel_debug!(
+            name: "Sampler.ConsistentProbability.InvalidTraceState",
+            reason = format!("{:?}", err),
+        );
+        trace_state.clone()
+    })
+}
+
+/// Sample consistently with the probability, recording the threshold in the
+/// trace state of sampled spans and removing it from the others.
+pub(crate) fn should_sample(
+    probability: f64,
+    parent_context: Option<&Context>,
+    trace_id: TraceId,
+) -> SamplingResult {
+    let trace_state = match parent_context {
+        Some(ctx) => ctx.span().span_context().trace_state().clone(),
+        None => TraceState::default(),
+    };
+    let threshold =
+        threshold(probability).filter(|threshold| randomness(&trace_state, trace_id) >= *threshold);
+    SamplingResult {
+        decision: match threshold {
+            Some(_) => SamplingDecision::RecordAndSample,
+            None => SamplingDecision::Drop,
+        },
+        attributes: Vec::new(),
+        trace_state: with_threshold(&trace_state, threshold),
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "trace"))]
+mod tests {
+    use super::{encode_threshold, ot_value, randomness, threshold, HEX_DIGITS, MAX_THRESHOLD};
+    use crate::propagation::TraceContextPropagator;
+    use crate::trace::{Sampler, ShouldSample};
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry::trace::{
+        SamplingDecision, SpanContext, SpanId, SpanKind, TraceContextExt, TraceFlags, TraceId,
+        TraceState,
+    };
+    use opentelemetry::Context;
+    use std::collections::HashMap;
+
+    /// Parse a threshold of 1 to 14 hex digits, padded with trailing zeros.
+    fn parse_threshold(value: &str) -> Option<u64> {
+        if value.is_empty() || value.len() > HEX_DIGITS {
+            return None;
+        }
+        let digits = u64::from_str_radix(value, 16).ok()?;
+        Some(digits << (4 * (HEX_DIGITS - value.len())))
+    }
+
+    fn parent(trace_id: TraceId, sampled: bool, trace_state: &str) -> Context {
+        let flags = if sampled {
+            TraceFlags::SAMPLED
+        } else {
+            TraceFlags::default()
+        };
+        let trace_state = TraceState::from_key_value(
+            trace_state
+                .split(',')
+                .filter_map(|entry| entry.split_once('=')),
+        )
+        .unwrap();
+        Context::new().with_remote_span_context(SpanContext::new(
+            trace_id,
+            SpanId::from(1),
+            flags,
+            true,
+            trace_state,
+        ))
+    }
+
+    #[test]
+    fn thresholds() {
+        assert_eq!(threshold(1.0), Some(0));
+        assert_eq!(threshold(0.0), None);
+        assert_eq!(threshold(-1.0), None);
+        assert_eq!(threshold(f64::NAN), None);
+        assert_eq!(threshold(0.5).map(encode_threshold), Some("8".to_string()));
+        assert_eq!(threshold(0.25).map(encode_threshold), Some("c".to_string()));
+        assert_eq!(
+            threshold(0.1).map(encode_threshold),
+            Some("e666".to_string())
+        );
+        // Too small to be rounded to 4 hex digits
+        let small = threshold(1e-6).unwrap();
+        assert!(small > 0xffff << 40);
+        let probability = (MAX_THRESHOLD - small) as f64 / MAX_THRESHOLD as f64;
+        assert!((probability - 1e-6).abs() < 1e-12);
+        assert_eq!(encode_threshold(0), "0");
+    }
+
+    #[test]
+    fn parse_thresholds() {
+        assert_eq!(parse_threshold("8"), Some(1 << 55));
+        assert_eq!(parse_threshold("0"), Some(0));
+        assert_eq!(parse_threshold("e666"), threshold(0.1));
+        assert_eq!(parse_threshold(""), None);
+        assert_eq!(parse_threshold("fffffffffffffff"), None);
+        assert_eq!(parse_threshold("xyz"), None);
+    }
+
+    #[test]
+    fn randomness_from_trace_id_or_rv() {
+        let trace_id = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+        assert_eq!(
+            randomness(&TraceState::default(), trace_id),
+            0xce929d0e0e4736
+        );
+
+        let trace_state = TraceState::from_key_value([("ot", "th:8;rv:00000000000001")]).unwrap();
+        assert_eq!(randomness(&trace_state, trace_id), 1);
+        assert_eq!(ot_value(&trace_state, "th"), Some("8"));
+    }
+
+    #[test]
+    fn decision_uses_low_56_bits() {
+        let sampler = Sampler::ConsistentProbabilityBased(0.5);
+        // Randomness 0x80000000000000 is at the threshold, sampled
+        let sampled = TraceId::from(0x0080_0000_0000_0000u128);
+        // The high bits are ignored
+        let dropped = TraceId::from(0xffff_ffff_ffff_ffff_ff7f_ffff_ffff_ffffu128);
+
+        let result = sampler.should_sample(None, sampled, "span", &SpanKind::Server, &[], &[]);
+        assert_eq!(result.decision, SamplingDecision::RecordAndSample);
+        assert_eq!(result.trace_state.header(), "ot=th:8");
+
+        let result = sampler.should_sample(None, dropped, "span", &SpanKind::Server, &[], &[]);
+        assert_eq!(result.decision, SamplingDecision::Drop);
+        assert_eq!(result.trace_state.header(), "");
+    }
+
+    #[test]
+    fn explicit_randomness_and_other_entries_are_kept() {
+        let sampler = Sampler::ConsistentProbabilityBased(0.25);
+        let cx = parent(
+            TraceId::from(1),
+            false,
+            "vendor=value,ot=th:8;rv:ffffffffffffff;x:y",
+        );
+
+        let result = sampler.should_sample(
+            Some(&cx),
+            TraceId::from(1),
+            "span",
+            &SpanKind::Server,
+            &[],
+            &[],
+        );
+
+        assert_eq!(result.decision, SamplingDecision::RecordAndSample);
+        assert_eq!(
+            result.trace_state.header(),
+            "ot=th:c;rv:ffffffffffffff;x:y,vendor=value"
+        );
+    }
+
+    #[test]
+    fn dropped_spans_erase_threshold() {
+        let sampler = Sampler::ConsistentProbabilityBased(0.0);
+        let cx = parent(TraceId::from(1), true, "ot=th:0;rv:ffffffffffffff");
+
+        let result = sampler.should_sample(
+            Some(&cx),
+            TraceId::from(1),
+            "span",
+            &SpanKind::Server,
+            &[],
+            &[],
+        );
+
+        assert_eq!(result.decision, SamplingDecision::Drop);
+        assert_eq!(result.trace_state.header(), "ot=rv:ffffffffffffff");
+    }
+
+    #[test]
+    fn parent_based_keeps_the_root_threshold() {
+        let sampler = Sampler::ParentBased(Box::new(Sampler::ConsistentProbabilityBased(0.5)));
+        let trace_id = TraceId::from(0x00ff_ffff_ffff_ffffu128);
+
+        // The root span records the threshold
+        let root = sampler.should_sample(None, trace_id, "root", &SpanKind::Server, &[], &[]);
+        assert_eq!(root.decision, SamplingDecision::RecordAndSample);
+        assert_eq!(root.trace_state.header(), "ot=th:8");
+
+        // It is propagated to the next service, whose child span keeps it
+        let mut headers = HashMap::new();
+        let root_cx = Context::new().with_remote_span_context(SpanContext::new(
+            trace_id,
+            SpanId::from(1),
+            TraceFlags::SAMPLED,
+            false,
+            root.trace_state,
+        ));
+        TraceContextPropagator::new().inject_context(&root_cx, &mut headers);
+        assert_eq!(
+            headers.get("tracestate").map(String::as_str),
+            Some("ot=th:8")
+        );
+        let remote_cx = TraceContextPropagator::new().extract(&headers);
+
+        let child = sampler.should_sample(
+            Some(&remote_cx),
+            trace_id,
+            "child",
+            &SpanKind::Server,
+            &[],
+            &[],
+        );
+        assert_eq!(child.decision, SamplingDecision::RecordAndSample);
+        assert_eq!(child.trace_state.header(), "ot=th:8");
+    }
+
+    #[test]
+    fn consistent_across_services() {
+        // Services sampling at 50% and 25% agree: the 25% traces are a subset
+        let half = Sampler::ConsistentProbabilityBased(0.5);
+        let quarter = Sampler::ConsistentProbabilityBased(0.25);
+        for trace_id in
+            (0..1000u128).map(|i| TraceId::from(i.wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c)))
+        {
+            let in_half = half
+                .should_sample(None, trace_id, "span", &SpanKind::Server, &[], &[])
+                .decision;
+            let in_quarter = quarter
+                .should_sample(None, trace_id, "span", &SpanKind::Server, &[], &[])
+                .decision;
+            if in_quarter == SamplingDecision::RecordAndSample {
+                assert_eq!(in_half, SamplingDecision::RecordAndSample);
+            }
+        }
+    }
+}
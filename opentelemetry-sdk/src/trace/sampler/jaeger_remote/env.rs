@@ -0,0 +1,230 @@
+use std::time::Duration;
+
+const ENDPOINT_KEY: &str = "endpoint";
+const POLLING_INTERVAL_KEY: &str = "pollingIntervalMs";
+const INITIAL_SAMPLING_RATE_KEY: &str = "initialSamplingRate";
+
+// Same default as the other OpenTelemetry SDKs
+const DEFAULT_INITIAL_SAMPLING_RATE: f64 = 0.001;
+
+/// The configuration of a [`JaegerRemoteSampler`] read from `OTEL_TRACES_SAMPLER_ARG`, e.g.
+/// `endpoint=http://localhost:5778/sampling,pollingIntervalMs=5000,initialSamplingRate=0.25`.
+/// Missing keys keep the defaults of [`JaegerRemoteSamplerBuilder`], the initial sampling
+/// rate defaults to 0.001.
+///
+/// [`JaegerRemoteSampler`]: super::JaegerRemoteSampler
+/// [`JaegerRemoteSamplerBuilder`]: super::JaegerRemoteSamplerBuilder
+#[derive(Debug, PartialEq)]
+pub(crate) struct SamplerArgs {
+    pub(crate) endpoint: Option<String>,
+    pub(crate) polling_interval: Option<Duration>,
+    /// Rate of the `TraceIdRatioBased` sampler used until the first strategy is fetched.
+    pub(crate) initial_sampling_rate: f64,
+}
+
+impl SamplerArgs {
+    pub(crate) fn parse(arg: Option<&str>) -> Result<Self, String> {
+        let mut args = SamplerArgs {
+            endpoint: None,
+            polling_interval: None,
+            initial_sampling_rate: DEFAULT_INITIAL_SAMPLING_RATE,
+        };
+        for pair in arg.unwrap_or_default().split(',') {
+            let pair = pair.trim();
+            if pair.is_empty() {
+                continue;
+            }
+            let (key, value) = pair
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| format!("'{pair}' is not a key=value pair"))?;
+            match key {
+                ENDPOINT_KEY if !value.is_empty() => args.endpoint = Some(value.to_string()),
+                POLLING_INTERVAL_KEY => {
+                    let millis = value
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|millis| *millis > 0)
+                        .ok_or_else(|| {
+                            format!("{POLLING_INTERVAL_KEY} must be a positive integer")
+                        })?;
+                    args.polling_interval = Some(Duration::from_millis(millis));
+                }
+                INITIAL_SAMPLING_RATE_KEY => {
+                    args.initial_sampling_rate = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|rate| (0.0..=1.0).contains(rate))
+                        .ok_or_else(|| {
+                            format!("{INITIAL_SAMPLING_RATE_KEY} must be a number between 0 and 1")
+                        })?;
+                }
+                _ => return Err(format!("unknown or empty argument '{pair}'")),
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Build the sampler polling the remote with a `reqwest` client, on its own Tokio
+/// thread so that it doesn't depend on the runtime of the application, if any.
+pub(crate) fn sampler_from_env(
+    arg: Option<&str>,
+    service_name: &str,
+) -> Result<crate::trace::Sampler, String> {
+    use crate::trace::Sampler;
+
+    let args = SamplerArgs::parse(arg)?;
+    let mut builder = Sampler::jaeger_remote(
+        crate::runtime::TokioCurrentThread,
+        reqwest::Client::new(),
+        Sampler::TraceIdRatioBased(args.initial_sampling_rate),
+        service_name,
+    );
+    if let Some(endpoint) = args.endpoint {
+        builder = builder.with_endpoint(endpoint);
+    }
+    if let Some(interval) = args.polling_interval {
+        builder = builder.with_update_interval(interval);
+    }
+    builder.build().map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args() {
+        assert_eq!(
+            SamplerArgs::parse(Some(
+                "endpoint=http://jaeger:5778/sampling, pollingIntervalMs=5000,initialSamplingRate=0.25"
+            )),
+            Ok(SamplerArgs {
+                endpoint: Some("http://jaeger:5778/sampling".to_string()),
+                polling_interval: Some(Duration::from_secs(5)),
+                initial_sampling_rate: 0.25,
+            })
+        );
+        for arg in [None, Some(""), Some(" , ")] {
+            assert_eq!(
+                SamplerArgs::parse(arg),
+                Ok(SamplerArgs {
+                    endpoint: None,
+                    polling_interval: None,
+                    initial_sampling_rate: DEFAULT_INITIAL_SAMPLING_RATE,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn parse_invalid_args() {
+        for arg in [
+            "endpoint",
+            "endpoint=",
+            "pollingIntervalMs=0",
+            "pollingIntervalMs=5s",
+            "initialSamplingRate=2",
+            "initialSamplingRate=half",
+            "samplingRate=0.5",
+        ] {
+            assert!(SamplerArgs::parse(Some(arg)).is_err(), "{arg}");
+        }
+    }
+
+    #[test]
+    fn env_config() {
+        use crate::trace::{Config, SdkTracerProvider, ShouldSample};
+        use opentelemetry::trace::{SamplingDecision, SpanKind, TraceId};
+
+        let decide = |sampler: &dyn ShouldSample| {
+            sampler
+                .should_sample(None, TraceId::from(1), "span", &SpanKind::Server, &[], &[])
+                .decision
+        };
+        let sampler = |provider: &SdkTracerProvider| format!("{:?}", provider.config().sampler);
+
+        // The initial sampler decides until a strategy is fetched
+        temp_env::with_vars(
+            [
+                ("OTEL_TRACES_SAMPLER", Some("parentbased_jaeger_remote")),
+                (
+                    "OTEL_TRACES_SAMPLER_ARG",
+                    Some("endpoint=http://localhost:1/sampling,initialSamplingRate=0"),
+                ),
+            ],
+            || {
+                // Nothing is polled until the provider is built
+                assert!(!format!("{:?}", Config::default().sampler).contains("JaegerRemote"));
+
+                let provider = SdkTracerProvider::builder().build();
+                assert!(sampler(&provider).contains("JaegerRemote"));
+                assert_eq!(
+                    decide(provider.config().sampler.as_ref()),
+                    SamplingDecision::Drop
+                );
+
+                // A sampler set on the builder replaces it
+                let provider = SdkTracerProvider::builder()
+                    .with_sampler(crate::trace::Sampler::AlwaysOn)
+                    .build();
+                assert!(!sampler(&provider).contains("JaegerRemote"));
+            },
+        );
+
+        // An invalid configuration falls back to the default sampler
+        temp_env::with_vars(
+            [
+                ("OTEL_TRACES_SAMPLER", Some("jaeger_remote")),
+                ("OTEL_TRACES_SAMPLER_ARG", Some("initialSamplingRate=2")),
+            ],
+            || {
+                let provider = SdkTracerProvider::builder().build();
+                assert!(!sampler(&provider).contains("JaegerRemote"));
+                assert_eq!(
+                    decide(provider.config().sampler.as_ref()),
+                    SamplingDecision::RecordAndSample
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn env_sampler_polls_service_of_resource() {
+        use crate::trace::SdkTracerProvider;
+        use crate::Resource;
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let arg = format!(
+            "endpoint=http://{}/sampling,pollingIntervalMs=10",
+            listener.local_addr().unwrap()
+        );
+        temp_env::with_vars(
+            [
+                ("OTEL_TRACES_SAMPLER", Some("jaeger_remote")),
+                ("OTEL_TRACES_SAMPLER_ARG", Some(arg.as_str())),
+                ("OTEL_SERVICE_NAME", None),
+            ],
+            || {
+                let _provider = SdkTracerProvider::builder()
+                    .with_resource(Resource::builder().with_service_name("checkout").build())
+                    .build();
+
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 1024];
+                let read = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..read]).to_string();
+                stream
+                    .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+                    .unwrap();
+                assert!(
+                    request.starts_with("GET /sampling?service=checkout "),
+                    "{request}"
+                );
+            },
+        );
+    }
+}
@@ -1,9 +1,12 @@
-mod rate_limit;
+#[cfg(feature = "jaeger_remote_sampler_env")]
+mod env;
 #[allow(dead_code)]
 mod remote;
 mod sampler;
 mod sampling_strategy;
 
+#[cfg(feature = "jaeger_remote_sampler_env")]
+pub(crate) use env::sampler_from_env;
 pub use sampler::{JaegerRemoteSampler, JaegerRemoteSamplerBuilder};
 
 #[cfg(test)]
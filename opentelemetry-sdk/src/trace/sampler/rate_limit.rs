@@ -0,0 +1,280 @@
+use crate::trace::ShouldSample;
+use opentelemetry::otel_debug;
+use opentelemetry::time::now;
+use opentelemetry::trace::{
+    Link, SamplingDecision, SamplingResult, SpanKind, TraceContextExt, TraceId, TraceState,
+};
+use opentelemetry::{Context, KeyValue};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// A sampler sampling at most `traces_per_second` spans per second, with bursts
+/// of up to one second worth of spans.
+///
+/// Used as the root sampler of [`Sampler::ParentBased`], it caps the number of
+/// traces started by the process, while their child spans follow the parent
+/// decision. Clones share the same budget.
+///
+/// [`Sampler::ParentBased`]: crate::trace::Sampler::ParentBased
+#[derive(Clone, Debug)]
+pub struct RateLimitingSampler {
+    traces_per_second: f64,
+    bucket: Arc<Mutex<LeakyBucket>>,
+}
+
+impl RateLimitingSampler {
+    /// Create a sampler allowing `traces_per_second` sampling decisions per second.
+    pub fn new(traces_per_second: f64) -> Self {
+        let traces_per_second = traces_per_second.max(0.0);
+        RateLimitingSampler {
+            traces_per_second,
+            // Same bucket size as the Jaeger remote rate limiting strategy
+            bucket: Arc::new(Mutex::new(LeakyBucket::new(
+                traces_per_second.max(1.0),
+                traces_per_second,
+            ))),
+        }
+    }
+
+    /// The maximum number of traces sampled per second.
+    pub fn traces_per_second(&self) -> f64 {
+        self.traces_per_second
+    }
+
+    pub(crate) fn decide(&self) -> SamplingDecision {
+        if self.traces_per_second > 0.0
+            && self
+                .bucket
+                .lock()
+                .map(|mut bucket| bucket.should_sample())
+                .unwrap_or(false)
+        {
+            SamplingDecision::RecordAndSample
+        } else {
+            SamplingDecision::Drop
+        }
+    }
+}
+
+impl ShouldSample for RateLimitingSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        _trace_id: TraceId,
+        _name: &str,
+        _span_kind: &SpanKind,
+        _attributes: &[KeyValue],
+        _links: &[Link],
+    ) -> SamplingResult {
+        SamplingResult {
+            decision: self.decide(),
+            attributes: Vec::new(),
+            trace_state: match parent_context {
+                Some(ctx) => ctx.span().span_context().trace_state().clone(),
+                None => TraceState::default(),
+            },
+        }
+    }
+}
+
+// leaky bucket based rate limit
+// should be Send+Sync
+#[derive(Debug)]
+pub(crate) struct LeakyBucket {
+    span_per_sec: f64,
+    available: f64,
+    bucket_size: f64,
+    last_time: SystemTime,
+}
+
+impl LeakyBucket {
+    pub(crate) fn new(bucket_size: f64, span_per_sec: f64) -> LeakyBucket {
+        LeakyBucket {
+            span_per_sec,
+            available: bucket_size,
+            bucket_size,
+            last_time: now(),
+        }
+    }
+
+    #[cfg(feature = "jaeger_remote_sampler")]
+    pub(crate) fn update(&mut self, span_per_sec: f64) {
+        self.span_per_sec = span_per_sec;
+    }
+
+    pub(crate) fn should_sample(&mut self) -> bool {
+        self.check_availability(now)
+    }
+
+    fn check_availability<F>(&mut self, now: F) -> bool
+    where
+        F: Fn() -> SystemTime,
+    {
+        if self.available >= 1.0 {
+            self.available -= 1.0;
+            true
+        } else {
+            let cur_time = now();
+            let elapsed = cur_time.duration_since(self.last_time);
+            match elapsed {
+                Ok(dur) => {
+                    self.last_time = cur_time;
+                    self.available = f64::min(
+                        dur.as_secs_f64() * self.span_per_sec + self.available,
+                        self.bucket_size,
+                    );
+
+                    if self.available >= 1.0 {
+                        self.available -= 1.0;
+                        true
+                    } else {
+                        false
+                    }
+                }
+                Err(err) => {
+                    otel_debug!(
+                        name: "Sampler.LeakyBucket.ClockAdjustment",
+                        message = "Rate limiting sampler detected a rewind in system clock",
+                        reason = format!("{:?}", err),
+                    );
+                    true
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LeakyBucket, RateLimitingSampler};
+    use crate::trace::{Sampler, ShouldSample};
+    use opentelemetry::time::now;
+    use opentelemetry::trace::{
+        SamplingDecision, SpanContext, SpanId, SpanKind, TraceContextExt, TraceFlags, TraceId,
+        TraceState,
+    };
+    use opentelemetry::Context;
+    use std::ops::{Add, Sub};
+    use std::time::Duration;
+
+    #[test]
+    fn test_leaky_bucket() {
+        // maximum bucket size 2, add 1 allowance every 10 seconds
+        let mut leaky_bucket = LeakyBucket::new(2.0, 0.1);
+        let current_time = now();
+        leaky_bucket.last_time = current_time;
+
+        let test_cases = vec![
+            (0, vec![true, true, false]),
+            (1, vec![false]),
+            (5, vec![false]),
+            (10, vec![true, false]),
+            (60, vec![true, true, false]), // maximum allowance is 2
+        ];
+
+        for (elapsed_sec, cases) in test_cases.into_iter() {
+            for should_pass in cases {
+                assert_eq!(
+                    should_pass,
+                    leaky_bucket.check_availability(|| {
+                        current_time.add(Duration::from_secs(elapsed_sec))
+                    })
+                )
+            }
+        }
+    }
+
+    #[test]
+    fn test_rewind_clock_should_pass() {
+        let mut leaky_bucket = LeakyBucket::new(2.0, 0.1);
+        let current_time = now();
+        leaky_bucket.last_time = current_time;
+
+        assert!(leaky_bucket.check_availability(|| { current_time.sub(Duration::from_secs(10)) }))
+    }
+
+    #[test]
+    fn test_leaky_bucket_refills_with_fractional_seconds() {
+        // add 1 allowance every 100 milliseconds
+        let mut leaky_bucket = LeakyBucket::new(1.0, 10.0);
+        let current_time = now();
+        leaky_bucket.last_time = current_time;
+
+        assert!(leaky_bucket.check_availability(|| current_time));
+        assert!(!leaky_bucket.check_availability(|| current_time));
+        assert!(!leaky_bucket.check_availability(|| current_time.add(Duration::from_millis(50))));
+        assert!(leaky_bucket.check_availability(|| current_time.add(Duration::from_millis(150))));
+    }
+
+    fn decide(sampler: &dyn ShouldSample, parent: Option<&Context>) -> SamplingDecision {
+        sampler
+            .should_sample(
+                parent,
+                TraceId::from(1),
+                "span",
+                &SpanKind::Server,
+                &[],
+                &[],
+            )
+            .decision
+    }
+
+    #[test]
+    fn test_rate_limited_sampler() {
+        let sampler = Sampler::rate_limited(2.0);
+        let clone = sampler.clone();
+
+        assert_eq!(decide(&sampler, None), SamplingDecision::RecordAndSample);
+        // Clones share the budget
+        assert_eq!(decide(&clone, None), SamplingDecision::RecordAndSample);
+        assert_eq!(decide(&sampler, None), SamplingDecision::Drop);
+    }
+
+    #[test]
+    fn test_rate_limited_sampler_zero_rate() {
+        let sampler = RateLimitingSampler::new(0.0);
+
+        assert_eq!(decide(&sampler, None), SamplingDecision::Drop);
+    }
+
+    #[test]
+    fn test_rate_limited_sampler_in_parent_based() {
+        let sampler = Sampler::ParentBased(Box::new(Sampler::rate_limited(1.0)));
+        let parent = Context::new().with_remote_span_context(SpanContext::new(
+            TraceId::from(1),
+            SpanId::from(1),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        ));
+
+        assert_eq!(decide(&sampler, None), SamplingDecision::RecordAndSample);
+        assert_eq!(decide(&sampler, None), SamplingDecision::Drop);
+        // Child spans of sampled traces don't use the budget
+        assert_eq!(
+            decide(&sampler, Some(&parent)),
+            SamplingDecision::RecordAndSample
+        );
+    }
+
+    #[test]
+    fn test_rate_limited_env_config() {
+        temp_env::with_vars(
+            [
+                ("OTEL_TRACES_SAMPLER", Some("parentbased_ratelimited")),
+                ("OTEL_TRACES_SAMPLER_ARG", Some("1")),
+            ],
+            || {
+                let config = crate::trace::Config::default();
+                assert_eq!(
+                    decide(config.sampler.as_ref(), None),
+                    SamplingDecision::RecordAndSample
+                );
+                assert_eq!(
+                    decide(config.sampler.as_ref(), None),
+                    SamplingDecision::Drop
+                );
+            },
+        );
+    }
+}
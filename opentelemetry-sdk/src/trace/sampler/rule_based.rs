@@ -0,0 +1,688 @@
+use crate::trace::{Sampler, ShouldSample};
+use opentelemetry::{
+    trace::{Link, SamplingResult, SpanKind, TraceId},
+    Context, Key, KeyValue, Value,
+};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Errors returned when parsing a [`RuleBasedSampler`] configuration.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum RuleConfigError {
+    /// The rule is not of the form `<conditions> => <sampler>`.
+    #[error("invalid sampling rule '{0}', expected '<conditions> => <sampler>'")]
+    InvalidRule(String),
+
+    /// The condition is not of the form `name=`, `name~=`, `kind=` or `attr.<key>=`.
+    #[error("invalid sampling rule condition '{0}'")]
+    InvalidCondition(String),
+
+    /// The span kind is not one of `client`, `server`, `producer`, `consumer` or `internal`.
+    #[error("invalid span kind '{0}'")]
+    InvalidSpanKind(String),
+
+    /// The sampler name or its argument is invalid.
+    #[error("invalid sampler '{0}'")]
+    InvalidSampler(String),
+
+    /// The span name pattern is not a valid regular expression, or regular
+    /// expressions are not enabled.
+    #[error("invalid span name pattern '{pattern}': {reason}")]
+    InvalidPattern {
+        /// The pattern.
+        pattern: String,
+        /// Why the pattern is invalid.
+        reason: String,
+    },
+}
+
+/// How a [`SamplingRule`] matches the span name.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum NameMatcher {
+    /// The name equals the string.
+    Exact(String),
+    /// The name matches the glob pattern, where `*` matches any sequence of
+    /// characters and `?` any single character.
+    Glob(String),
+    /// The name matches the regular expression.
+    #[cfg(feature = "rule_based_sampler_regex")]
+    Regex(regex::Regex),
+}
+
+impl NameMatcher {
+    /// Create a matcher for the regular expression.
+    #[cfg(feature = "rule_based_sampler_regex")]
+    pub fn regex(pattern: &str) -> Result<Self, RuleConfigError> {
+        regex::Regex::new(pattern)
+            .map(NameMatcher::Regex)
+            .map_err(|err| RuleConfigError::InvalidPattern {
+                pattern: pattern.to_string(),
+                reason: err.to_string(),
+            })
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            NameMatcher::Exact(expected) => name == expected,
+            NameMatcher::Glob(pattern) => glob_match(pattern, name),
+            #[cfg(feature = "rule_based_sampler_regex")]
+            NameMatcher::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// Match `*` and `?` wildcards, backtracking to the last `*` on mismatch.
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` consume one more character
+                Some((star, consumed)) => {
+                    backtrack = Some((star, consumed + 1));
+                    p = star + 1;
+                    n = consumed + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// A rule of a [`RuleBasedSampler`], delegating the decision for the spans it
+/// matches to its sampler.
+///
+/// A rule without conditions matches every span.
+#[derive(Clone, Debug)]
+pub struct SamplingRule {
+    name: Option<NameMatcher>,
+    span_kind: Option<SpanKind>,
+    attributes: Vec<(Key, Value)>,
+    sampler: Box<dyn ShouldSample>,
+}
+
+impl SamplingRule {
+    /// Create a rule matching every span, sampled by `sampler`.
+    pub fn new<S: ShouldSample + 'static>(sampler: S) -> Self {
+        SamplingRule::boxed(Box::new(sampler))
+    }
+
+    fn boxed(sampler: Box<dyn ShouldSample>) -> Self {
+        SamplingRule {
+            name: None,
+            span_kind: None,
+            attributes: Vec::new(),
+            sampler,
+        }
+    }
+
+    /// Only match spans whose name matches.
+    pub fn with_name(mut self, matcher: NameMatcher) -> Self {
+        self.name = Some(matcher);
+        self
+    }
+
+    /// Only match spans of this kind.
+    pub fn with_span_kind(mut self, span_kind: SpanKind) -> Self {
+        self.span_kind = Some(span_kind);
+        self
+    }
+
+    /// Only match spans started with the attribute. Values are compared by
+    /// their string representation, so `"200"` matches the integer `200`.
+    pub fn with_attribute(mut self, key: impl Into<Key>, value: impl Into<Value>) -> Self {
+        self.attributes.push((key.into(), value.into()));
+        self
+    }
+
+    fn matches(&self, name: &str, span_kind: &SpanKind, attributes: &[KeyValue]) -> bool {
+        self.name
+            .as_ref()
+            .map_or(true, |matcher| matcher.matches(name))
+            && self
+                .span_kind
+                .as_ref()
+                .map_or(true, |kind| kind == span_kind)
+            && self.attributes.iter().all(|(key, value)| {
+                attributes.iter().any(|attribute| {
+                    attribute.key == *key && attribute.value.as_str() == value.as_str()
+                })
+            })
+    }
+}
+
+/// A sampler delegating to the sampler of the first [`SamplingRule`] matching
+/// the span name, kind and initial attributes, or to the fallback sampler if
+/// no rule matches.
+///
+/// # Example
+///
+/// ```
+/// use opentelemetry::trace::SpanKind;
+/// use opentelemetry_sdk::trace::{NameMatcher, RuleBasedSampler, Sampler, SamplingRule};
+///
+/// // Drop health checks, keep all payments and 10% of the other traces
+/// let sampler = RuleBasedSampler::new(Sampler::TraceIdRatioBased(0.1))
+///     .with_rule(
+///         SamplingRule::new(Sampler::AlwaysOff)
+///             .with_name(NameMatcher::Exact("GET /health".into())),
+///     )
+///     .with_rule(
+///         SamplingRule::new(Sampler::AlwaysOn)
+///             .with_name(NameMatcher::Glob("POST /payments*".into()))
+///             .with_span_kind(SpanKind::Server),
+///     );
+///
+/// // The same rules as a configuration string
+/// let sampler: RuleBasedSampler =
+///     "name=GET /health => always_off; name=POST /payments*, kind=server => always_on; * => traceidratio:0.1"
+///         .parse()
+///         .unwrap();
+/// ```
+///
+/// # Configuration string
+///
+/// Rules are separated by `;` and written `<conditions> => <sampler>`, where
+/// the conditions are separated by `,` and all have to match:
+///
+/// * `name=<glob>`: the span name matches the glob pattern.
+/// * `name~=<regex>`: the span name matches the regular expression, requires
+///   the `rule_based_sampler_regex` feature.
+/// * `kind=<kind>`: the span kind is `client`, `server`, `producer`,
+///   `consumer` or `internal`.
+/// * `attr.<key>=<value>`: the span was started with the attribute.
+///
+/// Values containing `;`, `,` or `=>`, such as the regular expression
+/// `name~="^GET /v{1,2}$"`, have to be written in double quotes, in which
+/// `\"` and `\\` stand for `"` and `\`.
+///
+/// A rule whose conditions are `*` sets the fallback sampler, which is
+/// `always_on` by default. The samplers are `always_on`, `always_off`,
+/// `traceidratio:<ratio>`, `ratelimited:<traces per second>`,
+/// `parentbased_always_on`, `parentbased_always_off`,
+/// `parentbased_traceidratio:<ratio>` and
+/// `parentbased_ratelimited:<traces per second>`.
+///
+/// The configuration can be set with the `OTEL_TRACES_SAMPLER_ARG` environment
+/// variable when `OTEL_TRACES_SAMPLER` is `rule_based` or
+/// `parentbased_rule_based`.
+#[derive(Clone, Debug)]
+pub struct RuleBasedSampler {
+    rules: Vec<SamplingRule>,
+    fallback: Box<dyn ShouldSample>,
+}
+
+impl RuleBasedSampler {
+    /// Create a sampler without rules, delegating to `fallback`.
+    pub fn new<S: ShouldSample + 'static>(fallback: S) -> Self {
+        RuleBasedSampler {
+            rules: Vec::new(),
+            fallback: Box::new(fallback),
+        }
+    }
+
+    /// Add a rule, checked after the rules already added.
+    pub fn with_rule(mut self, rule: SamplingRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Parse the rules from a configuration string, see the
+    /// [type documentation](RuleBasedSampler#configuration-string).
+    pub fn from_config(config: &str) -> Result<Self, RuleConfigError> {
+        let mut sampler = RuleBasedSampler::new(Sampler::AlwaysOn);
+        for rule in split_unquoted(config, ";")
+            .into_iter()
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+        {
+            let (conditions, delegate) = match split_unquoted(rule, "=>")[..] {
+                [conditions, delegate] => (conditions, delegate),
+                _ => return Err(RuleConfigError::InvalidRule(rule.to_string())),
+            };
+            let delegate = parse_sampler(delegate.trim())?;
+
+            let conditions = conditions.trim();
+            if conditions == "*" {
+                sampler.fallback = delegate;
+                continue;
+            }
+            let mut parsed = SamplingRule::boxed(delegate);
+            for condition in split_unquoted(conditions, ",").into_iter().map(str::trim) {
+                parsed = parse_condition(parsed, condition)?;
+            }
+            sampler.rules.push(parsed);
+        }
+        Ok(sampler)
+    }
+}
+
+impl FromStr for RuleBasedSampler {
+    type Err = RuleConfigError;
+
+    fn from_str(config: &str) -> Result<Self, Self::Err> {
+        RuleBasedSampler::from_config(config)
+    }
+}
+
+fn parse_condition(rule: SamplingRule, condition: &str) -> Result<SamplingRule, RuleConfigError> {
+    let invalid = || RuleConfigError::InvalidCondition(condition.to_string());
+    let (key, value) = condition.split_once('=').ok_or_else(invalid)?;
+    let value = unquote(value.trim()).ok_or_else(invalid)?;
+    let value = value.as_str();
+    match key.trim() {
+        "name" => Ok(rule.with_name(NameMatcher::Glob(value.to_string()))),
+        "name~" => {
+            #[cfg(feature = "rule_based_sampler_regex")]
+            {
+                Ok(rule.with_name(NameMatcher::regex(value)?))
+            }
+            #[cfg(not(feature = "rule_based_sampler_regex"))]
+            {
+                Err(RuleConfigError::InvalidPattern {
+                    pattern: value.to_string(),
+                    reason: "regular expressions require the `rule_based_sampler_regex` feature"
+                        .to_string(),
+                })
+            }
+        }
+        "kind" => {
+            let span_kind = match value.to_ascii_lowercase().as_str() {
+                "client" => SpanKind::Client,
+                "server" => SpanKind::Server,
+                "producer" => SpanKind::Producer,
+                "consumer" => SpanKind::Consumer,
+                "internal" => SpanKind::Internal,
+                _ => return Err(RuleConfigError::InvalidSpanKind(value.to_string())),
+            };
+            Ok(rule.with_span_kind(span_kind))
+        }
+        key => match key.strip_prefix("attr.") {
+            Some(attribute) if !attribute.is_empty() => {
+                Ok(rule.with_attribute(attribute.to_string(), value.to_string()))
+            }
+            _ => Err(invalid()),
+        },
+    }
+}
+
+/// Split `value` at each `separator` outside of double quotes.
+fn split_unquoted<'a>(value: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if !quoted && index >= start && value[index..].starts_with(separator) {
+            parts.push(&value[start..index]);
+            start = index + separator.len();
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Remove the double quotes around `value`, if any, and resolve the `\"` and
+/// `\\` escapes within. Returns `None` if the quotes are not closed.
+fn unquote(value: &str) -> Option<String> {
+    let Some(quoted) = value.strip_prefix('"') else {
+        return Some(value.to_string());
+    };
+    let mut unquoted = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return chars.next().is_none().then_some(unquoted),
+            '\\' => match chars.next_if(|next| matches!(next, '"' | '\\')) {
+                Some(escaped) => unquoted.push(escaped),
+                None => unquoted.push(c),
+            },
+            c => unquoted.push(c),
+        }
+    }
+    None
+}
+
+fn parse_sampler(sampler: &str) -> Result<Box<dyn ShouldSample>, RuleConfigError> {
+    let (name, arg) = match sampler.split_once(':') {
+        Some((name, arg)) => (name.trim(), Some(arg.trim())),
+        None => (sampler, None),
+    };
+    let number = || {
+        arg.and_then(|number| number.parse::<f64>().ok())
+            .ok_or_else(|| RuleConfigError::InvalidSampler(sampler.to_string()))
+    };
+    let parsed = match (name, arg) {
+        ("always_on", None) => Sampler::AlwaysOn,
+        ("always_off", None) => Sampler::AlwaysOff,
+        ("traceidratio", Some(_)) => Sampler::TraceIdRatioBased(number()?),
+        ("ratelimited", Some(_)) => Sampler::rate_limited(number()?),
+        ("parentbased_always_on", None) => Sampler::ParentBased(Box::new(Sampler::AlwaysOn)),
+        ("parentbased_always_off", None) => Sampler::ParentBased(Box::new(Sampler::AlwaysOff)),
+        ("parentbased_traceidratio", Some(_)) => {
+            Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(number()?)))
+        }
+        ("parentbased_ratelimited", Some(_)) => {
+            Sampler::ParentBased(Box::new(Sampler::rate_limited(number()?)))
+        }
+        _ => return Err(RuleConfigError::InvalidSampler(sampler.to_string())),
+    };
+    Ok(Box::new(parsed))
+}
+
+impl ShouldSample for RuleBasedSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(name, span_kind, attributes))
+            .map_or(&self.fallback, |rule| &rule.sampler)
+            .should_sample(parent_context, trace_id, name, span_kind, attributes, links)
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "trace"))]
+mod tests {
+    use super::{glob_match, NameMatcher, RuleBasedSampler, RuleConfigError, SamplingRule};
+    use crate::trace::{Config, Sampler, ShouldSample};
+    use opentelemetry::trace::{SamplingDecision, SpanKind, TraceId};
+    use opentelemetry::KeyValue;
+
+    fn decide(
+        sampler: &dyn ShouldSample,
+        name: &str,
+        span_kind: SpanKind,
+        attributes: &[KeyValue],
+    ) -> SamplingDecision {
+        sampler
+            .should_sample(None, TraceId::from(1), name, &span_kind, attributes, &[])
+            .decision
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("GET /health", "GET /health"));
+        assert!(glob_match("GET /*", "GET /users/42"));
+        assert!(glob_match("*/health", "GET /health"));
+        assert!(glob_match("GET /users/?", "GET /users/7"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("GET /users/?", "GET /users/42"));
+        assert!(!glob_match("*a*b", "xxaxxbxx"));
+        assert!(!glob_match("GET", "GET /health"));
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let sampler = RuleBasedSampler::new(Sampler::AlwaysOn)
+            .with_rule(
+                SamplingRule::new(Sampler::AlwaysOff)
+                    .with_name(NameMatcher::Exact("GET /health".into())),
+            )
+            .with_rule(
+                SamplingRule::new(Sampler::AlwaysOn).with_name(NameMatcher::Glob("GET *".into())),
+            )
+            .with_rule(
+                SamplingRule::new(Sampler::AlwaysOff).with_name(NameMatcher::Glob("*".into())),
+            );
+
+        assert_eq!(
+            decide(&sampler, "GET /health", SpanKind::Server, &[]),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decide(&sampler, "GET /users", SpanKind::Server, &[]),
+            SamplingDecision::RecordAndSample
+        );
+        assert_eq!(
+            decide(&sampler, "POST /users", SpanKind::Server, &[]),
+            SamplingDecision::Drop
+        );
+    }
+
+    #[test]
+    fn span_kind_and_attributes() {
+        let sampler = RuleBasedSampler::new(Sampler::AlwaysOff).with_rule(
+            SamplingRule::new(Sampler::AlwaysOn)
+                .with_span_kind(SpanKind::Client)
+                .with_attribute("http.response.status_code", "500"),
+        );
+
+        let error = [KeyValue::new("http.response.status_code", 500)];
+        let ok = [KeyValue::new("http.response.status_code", 200)];
+        assert_eq!(
+            decide(&sampler, "call", SpanKind::Client, &error),
+            SamplingDecision::RecordAndSample
+        );
+        assert_eq!(
+            decide(&sampler, "call", SpanKind::Client, &ok),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decide(&sampler, "call", SpanKind::Server, &error),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decide(&sampler, "call", SpanKind::Client, &[]),
+            SamplingDecision::Drop
+        );
+    }
+
+    #[test]
+    fn from_config() {
+        let sampler: RuleBasedSampler = "name=GET /health => always_off; \
+            name=POST /payments*, kind=server => always_on; \
+            attr.tenant=acme => parentbased_always_on; \
+            name=batch * => ratelimited:100; \
+            * => traceidratio:0.0"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            decide(&sampler, "GET /health", SpanKind::Server, &[]),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decide(&sampler, "POST /payments/42", SpanKind::Server, &[]),
+            SamplingDecision::RecordAndSample
+        );
+        assert_eq!(
+            decide(&sampler, "POST /payments/42", SpanKind::Client, &[]),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decide(
+                &sampler,
+                "work",
+                SpanKind::Internal,
+                &[KeyValue::new("tenant", "acme")]
+            ),
+            SamplingDecision::RecordAndSample
+        );
+        assert_eq!(
+            decide(&sampler, "work", SpanKind::Internal, &[]),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decide(&sampler, "batch import", SpanKind::Internal, &[]),
+            SamplingDecision::RecordAndSample
+        );
+    }
+
+    #[test]
+    fn from_config_defaults_to_always_on() {
+        let sampler = RuleBasedSampler::from_config("name=GET /health => always_off").unwrap();
+
+        assert_eq!(
+            decide(&sampler, "GET /users", SpanKind::Server, &[]),
+            SamplingDecision::RecordAndSample
+        );
+    }
+
+    #[test]
+    fn from_config_quoted_values() {
+        let sampler = RuleBasedSampler::from_config(
+            r#"name="GET /a;b, c => d" => always_off; attr.note="say \"hi\"" => always_off; * => always_on"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            decide(&sampler, "GET /a;b, c => d", SpanKind::Server, &[]),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decide(
+                &sampler,
+                "work",
+                SpanKind::Internal,
+                &[KeyValue::new("note", r#"say "hi""#)]
+            ),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decide(&sampler, "GET /a", SpanKind::Server, &[]),
+            SamplingDecision::RecordAndSample
+        );
+    }
+
+    #[test]
+    fn from_config_errors() {
+        assert_eq!(
+            RuleBasedSampler::from_config("name=GET /health").unwrap_err(),
+            RuleConfigError::InvalidRule("name=GET /health".into())
+        );
+        assert_eq!(
+            RuleBasedSampler::from_config("path=/health => always_off").unwrap_err(),
+            RuleConfigError::InvalidCondition("path=/health".into())
+        );
+        assert_eq!(
+            RuleBasedSampler::from_config("kind=remote => always_off").unwrap_err(),
+            RuleConfigError::InvalidSpanKind("remote".into())
+        );
+        assert_eq!(
+            RuleBasedSampler::from_config("* => traceidratio").unwrap_err(),
+            RuleConfigError::InvalidSampler("traceidratio".into())
+        );
+        assert_eq!(
+            RuleBasedSampler::from_config("* => sometimes").unwrap_err(),
+            RuleConfigError::InvalidSampler("sometimes".into())
+        );
+        assert_eq!(
+            RuleBasedSampler::from_config(r#"name="GET /health => always_off"#).unwrap_err(),
+            RuleConfigError::InvalidRule(r#"name="GET /health => always_off"#.into())
+        );
+        assert_eq!(
+            RuleBasedSampler::from_config(r#"name="GET" /health => always_off"#).unwrap_err(),
+            RuleConfigError::InvalidCondition(r#"name="GET" /health"#.into())
+        );
+    }
+
+    #[cfg(feature = "rule_based_sampler_regex")]
+    #[test]
+    fn regex_names() {
+        let sampler =
+            RuleBasedSampler::from_config(r"name~=^GET /users/\d+$ => always_off").unwrap();
+
+        assert_eq!(
+            decide(&sampler, "GET /users/42", SpanKind::Server, &[]),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decide(&sampler, "GET /users/me", SpanKind::Server, &[]),
+            SamplingDecision::RecordAndSample
+        );
+
+        let sampler =
+            RuleBasedSampler::from_config(r#"name~="^GET /v\d{1,2}$", kind=server => always_off"#)
+                .unwrap();
+        assert_eq!(
+            decide(&sampler, "GET /v12", SpanKind::Server, &[]),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decide(&sampler, "GET /v123", SpanKind::Server, &[]),
+            SamplingDecision::RecordAndSample
+        );
+        assert!(matches!(
+            RuleBasedSampler::from_config("name~=( => always_off"),
+            Err(RuleConfigError::InvalidPattern { .. })
+        ));
+    }
+
+    #[test]
+    fn env_config() {
+        temp_env::with_vars(
+            [
+                ("OTEL_TRACES_SAMPLER", Some("rule_based")),
+                (
+                    "OTEL_TRACES_SAMPLER_ARG",
+                    Some("name=GET /health => always_off"),
+                ),
+            ],
+            || {
+                let config = Config::default();
+                assert_eq!(
+                    decide(
+                        config.sampler.as_ref(),
+                        "GET /health",
+                        SpanKind::Server,
+                        &[]
+                    ),
+                    SamplingDecision::Drop
+                );
+                assert_eq!(
+                    decide(config.sampler.as_ref(), "GET /users", SpanKind::Server, &[]),
+                    SamplingDecision::RecordAndSample
+                );
+            },
+        );
+
+        // An invalid configuration falls back to the default sampler
+        temp_env::with_vars(
+            [
+                ("OTEL_TRACES_SAMPLER", Some("parentbased_rule_based")),
+                ("OTEL_TRACES_SAMPLER_ARG", Some("name=GET /health")),
+            ],
+            || {
+                let config = Config::default();
+                assert_eq!(
+                    decide(
+                        config.sampler.as_ref(),
+                        "GET /health",
+                        SpanKind::Server,
+                        &[]
+                    ),
+                    SamplingDecision::RecordAndSample
+                );
+            },
+        );
+    }
+}
@@ -0,0 +1,5 @@
+mod remote;
+mod sampler;
+mod sampling_rules;
+
+pub use sampler::{XrayRemoteSampler, XrayRemoteSamplerBuilder};
@@ -0,0 +1,95 @@
+//! Types of the X-Ray sampling API, as served by the X-Ray daemon or the collector's
+//! `awsproxy` extension.
+//! See the [API reference](https://docs.aws.amazon.com/xray/latest/api/API_GetSamplingRules.html)
+use std::collections::HashMap;
+
+/// GetSamplingRules request, fetching one page of rules.
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct GetSamplingRulesRequest {
+    pub(crate) next_token: Option<String>,
+}
+
+/// GetSamplingRules response.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct GetSamplingRulesResponse {
+    #[serde(default)]
+    pub(crate) sampling_rule_records: Vec<SamplingRuleRecord>,
+    pub(crate) next_token: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct SamplingRuleRecord {
+    pub(crate) sampling_rule: SamplingRule,
+}
+
+/// A sampling rule, matching a span when all of its properties match. String properties are
+/// patterns with `*` and `?` wildcards.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct SamplingRule {
+    pub(crate) rule_name: String,
+    /// Lower values are evaluated first.
+    pub(crate) priority: i32,
+    /// Fraction of the matching requests to sample once the reservoir is exhausted.
+    pub(crate) fixed_rate: f64,
+    /// Number of matching requests to sample per second, across all instances of the service.
+    pub(crate) reservoir_size: u64,
+    pub(crate) service_name: String,
+    pub(crate) service_type: String,
+    pub(crate) host: String,
+    #[serde(rename = "HTTPMethod")]
+    pub(crate) http_method: String,
+    #[serde(rename = "URLPath")]
+    pub(crate) url_path: String,
+    #[serde(rename = "ResourceARN")]
+    pub(crate) resource_arn: String,
+    #[serde(default)]
+    pub(crate) attributes: HashMap<String, String>,
+    pub(crate) version: i32,
+}
+
+/// GetSamplingTargets request, reporting the statistics of each rule since the last report.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct GetSamplingTargetsRequest {
+    pub(crate) sampling_statistics_documents: Vec<SamplingStatisticsDocument>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct SamplingStatisticsDocument {
+    pub(crate) rule_name: String,
+    #[serde(rename = "ClientID")]
+    pub(crate) client_id: String,
+    /// Epoch seconds of the report.
+    pub(crate) timestamp: i64,
+    pub(crate) request_count: u64,
+    pub(crate) sampled_count: u64,
+    pub(crate) borrow_count: u64,
+}
+
+/// GetSamplingTargets response.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct GetSamplingTargetsResponse {
+    #[serde(default)]
+    pub(crate) sampling_target_documents: Vec<SamplingTargetDocument>,
+    /// Epoch seconds of the last change of the rules, which are then fetched again.
+    pub(crate) last_rule_modification: Option<f64>,
+}
+
+/// The share of the reservoir of a rule assigned to this instance.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct SamplingTargetDocument {
+    pub(crate) rule_name: String,
+    pub(crate) fixed_rate: f64,
+    /// Number of requests to sample per second, none to keep the current quota.
+    pub(crate) reservoir_quota: Option<u64>,
+    /// Epoch seconds after which the quota expires.
+    #[serde(rename = "ReservoirQuotaTTL")]
+    pub(crate) reservoir_quota_ttl: Option<f64>,
+}
@@ -0,0 +1,601 @@
+use crate::runtime::{to_interval_stream, RuntimeChannel};
+use crate::trace::error::TraceError;
+use crate::trace::sampler::xray_remote::remote::{
+    GetSamplingRulesRequest, GetSamplingRulesResponse, GetSamplingTargetsRequest,
+    GetSamplingTargetsResponse, SamplingRule,
+};
+use crate::trace::sampler::xray_remote::sampling_rules::{Inner, Request};
+use crate::trace::{Sampler, ShouldSample};
+use futures_util::{stream, StreamExt as _};
+use http::Uri;
+use opentelemetry::time::now;
+use opentelemetry::trace::{Link, SamplingResult, SpanKind, TraceContextExt, TraceId, TraceState};
+use opentelemetry::{otel_warn, Context, KeyValue};
+use opentelemetry_http::HttpClient;
+use rand::Rng;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const DEFAULT_PROXY_ENDPOINT: &str = "http://localhost:2000";
+const SAMPLING_RULES_PATH: &str = "/GetSamplingRules";
+// The GetSamplingTargets API
+const SAMPLING_TARGETS_PATH: &str = "/SamplingTargets";
+
+#[derive(Clone, Debug)]
+struct Endpoints {
+    rules: Uri,
+    targets: Uri,
+}
+
+/// Builder for [`XrayRemoteSampler`].
+/// See [Sampler::xray_remote] for details.
+#[derive(Debug)]
+pub struct XrayRemoteSamplerBuilder<C, S, R>
+where
+    R: RuntimeChannel,
+    C: HttpClient + 'static,
+    S: ShouldSample + 'static,
+{
+    pub(crate) rules_interval: Duration,
+    pub(crate) targets_interval: Duration,
+    pub(crate) client: C,
+    pub(crate) endpoint: String,
+    pub(crate) default_sampler: S,
+    pub(crate) runtime: R,
+    pub(crate) service_name: String,
+    pub(crate) service_type: String,
+    pub(crate) resource_arn: String,
+}
+
+impl<C, S, R> XrayRemoteSamplerBuilder<C, S, R>
+where
+    C: HttpClient + 'static,
+    S: ShouldSample + 'static,
+    R: RuntimeChannel,
+{
+    pub(crate) fn new<Svc>(
+        runtime: R,
+        http_client: C,
+        default_sampler: S,
+        service_name: Svc,
+    ) -> Self
+    where
+        Svc: Into<String>,
+    {
+        XrayRemoteSamplerBuilder {
+            runtime,
+            rules_interval: Duration::from_secs(60 * 5),
+            targets_interval: Duration::from_secs(10),
+            client: http_client,
+            endpoint: DEFAULT_PROXY_ENDPOINT.to_string(),
+            default_sampler,
+            service_name: service_name.into(),
+            service_type: String::new(),
+            resource_arn: String::new(),
+        }
+    }
+
+    /// Change how often the SDK should fetch the sampling rules.
+    ///
+    /// By default it fetches every 5 minutes. The rules are also fetched again when
+    /// X-Ray reports that they changed.
+    pub fn with_rules_interval(self, interval: Duration) -> Self {
+        Self {
+            rules_interval: interval,
+            ..self
+        }
+    }
+
+    /// Change how often the SDK should report its statistics and fetch the reservoir
+    /// quotas assigned to it.
+    ///
+    /// By default it reports every 10 seconds.
+    pub fn with_targets_interval(self, interval: Duration) -> Self {
+        Self {
+            targets_interval: interval,
+            ..self
+        }
+    }
+
+    /// The endpoint of the X-Ray daemon or collector proxying the X-Ray sampling API.
+    ///
+    /// By default it's `http://localhost:2000`.
+    pub fn with_endpoint<Str: Into<String>>(self, endpoint: Str) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            ..self
+        }
+    }
+
+    /// The service type matched by the `ServiceType` of the rules, e.g. `AWS::EC2::Instance`.
+    ///
+    /// By default it's empty, only matched by `*`.
+    pub fn with_service_type<Str: Into<String>>(self, service_type: Str) -> Self {
+        Self {
+            service_type: service_type.into(),
+            ..self
+        }
+    }
+
+    /// The ARN of the resource running the service, matched by the `ResourceARN` of the rules.
+    ///
+    /// By default it's empty, only matched by `*`.
+    pub fn with_resource_arn<Str: Into<String>>(self, resource_arn: Str) -> Self {
+        Self {
+            resource_arn: resource_arn.into(),
+            ..self
+        }
+    }
+
+    /// Build a [XrayRemoteSampler] using provided configuration.
+    ///
+    /// Return errors if:
+    ///
+    /// - the endpoint provided is empty or invalid.
+    /// - the service name provided is empty.
+    pub fn build(self) -> Result<Sampler, TraceError> {
+        let endpoints = Self::get_endpoints(&self.endpoint, &self.service_name)
+            .map_err(|err_str| TraceError::Other(err_str.into()))?;
+
+        Ok(Sampler::XrayRemote(XrayRemoteSampler::new(
+            self.runtime,
+            self.rules_interval,
+            self.targets_interval,
+            self.client,
+            endpoints,
+            self.default_sampler,
+            ServiceInfo {
+                name: self.service_name,
+                service_type: self.service_type,
+                resource_arn: self.resource_arn,
+            },
+        )))
+    }
+
+    fn get_endpoints(endpoint: &str, service_name: &str) -> Result<Endpoints, String> {
+        if endpoint.is_empty() || service_name.is_empty() {
+            return Err("endpoint and service name cannot be empty".to_string());
+        }
+        let endpoint = endpoint.trim_end_matches('/');
+        let uri = |path: &str| {
+            Uri::from_str(&format!("{endpoint}{path}"))
+                .map_err(|err| format!("invalid endpoint {endpoint}, {err}"))
+        };
+        Ok(Endpoints {
+            rules: uri(SAMPLING_RULES_PATH)?,
+            targets: uri(SAMPLING_TARGETS_PATH)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ServiceInfo {
+    name: String,
+    service_type: String,
+    resource_arn: String,
+}
+
+/// Sampler that applies the [AWS X-Ray sampling rules](https://docs.aws.amazon.com/xray/latest/devguide/xray-console-sampling.html)
+/// of the service.
+///
+/// The rules are polled from the X-Ray sampling API, proxied by the X-Ray daemon or the
+/// collector's `awsproxy` extension. A span is sampled by the first rule, in priority order,
+/// matching the service and the request attributes (`http.request.method`, `url.path`,
+/// `server.address`, or their legacy names, and the attributes of the rule):
+/// - **Reservoir**, each second the rule samples up to the quota of requests assigned to this
+///   instance by X-Ray. Until a quota is assigned, it borrows one request per second.
+/// - **Fixed rate**, once the reservoir is exhausted it samples a fraction of the requests.
+///
+/// The sampler reports how many requests each rule sampled, from which X-Ray computes the
+/// quotas of the instances of the service.
+///
+/// User can build a [`XrayRemoteSampler`] by getting a [`XrayRemoteSamplerBuilder`] from [`Sampler::xray_remote`].
+#[derive(Clone, Debug)]
+pub struct XrayRemoteSampler {
+    inner: Arc<Inner>,
+    service: Arc<ServiceInfo>,
+    default_sampler: Arc<dyn ShouldSample + 'static>,
+}
+
+impl XrayRemoteSampler {
+    fn new<C, R, S>(
+        runtime: R,
+        rules_interval: Duration,
+        targets_interval: Duration,
+        client: C,
+        endpoints: Endpoints,
+        default_sampler: S,
+        service: ServiceInfo,
+    ) -> Self
+    where
+        R: RuntimeChannel,
+        C: HttpClient + 'static,
+        S: ShouldSample + 'static,
+    {
+        let (shutdown_tx, shutdown_rx) = futures_channel::mpsc::channel(1);
+        let client_id = format!("{:024x}", rand::rng().random::<u128>() >> 32);
+        let inner = Arc::new(Inner::new(client_id, shutdown_tx));
+        let sampler = XrayRemoteSampler {
+            inner,
+            service: Arc::new(service),
+            default_sampler: Arc::new(default_sampler),
+        };
+        Self::run_update_task(
+            runtime,
+            sampler.inner.clone(),
+            rules_interval,
+            targets_interval,
+            client,
+            shutdown_rx,
+            endpoints,
+        );
+        sampler
+    }
+
+    // start a updating thread/task
+    fn run_update_task<C, R>(
+        runtime: R,
+        rules: Arc<Inner>,
+        rules_interval: Duration,
+        targets_interval: Duration,
+        client: C,
+        shutdown: futures_channel::mpsc::Receiver<()>,
+        endpoints: Endpoints,
+    ) where
+        R: RuntimeChannel,
+        C: HttpClient + 'static,
+    {
+        enum Update {
+            Rules,
+            Targets,
+            Shutdown,
+        }
+
+        // the rules are fetched right away, the targets once there are statistics to report
+        let rules_updates = stream::once(async {})
+            .chain(to_interval_stream(runtime.clone(), rules_interval))
+            .map(|_| Update::Rules);
+        let targets_updates =
+            to_interval_stream(runtime.clone(), targets_interval).map(|_| Update::Targets);
+
+        runtime.spawn(async move {
+            // either update or shutdown
+            let mut updates = Box::pin(stream::select(
+                shutdown.map(|_| Update::Shutdown),
+                stream::select(rules_updates, targets_updates),
+            ));
+            let mut rules_fetched_at = 0.0;
+
+            while let Some(update) = updates.next().await {
+                let fetch_rules = match update {
+                    Update::Rules => true,
+                    Update::Targets => {
+                        let statistics = rules.take_statistics(epoch_seconds(now()));
+                        if statistics.is_empty() {
+                            continue;
+                        }
+                        let request = GetSamplingTargetsRequest {
+                            sampling_statistics_documents: statistics,
+                        };
+                        match Self::post::<_, _, GetSamplingTargetsResponse>(
+                            &client,
+                            endpoints.targets.clone(),
+                            &request,
+                        )
+                        .await
+                        {
+                            Ok(response) => {
+                                rules.update_targets(response.sampling_target_documents);
+                                response
+                                    .last_rule_modification
+                                    .is_some_and(|modified| modified > rules_fetched_at)
+                            }
+                            Err(err_msg) => {
+                                otel_warn!(
+                                    name: "XrayRemoteSampler.FailedToFetchTargets",
+                                    message = "Failed to fetch the sampling targets from the X-Ray sampling API. The current reservoir quotas will be used until they expire.",
+                                    reason = format!("{}", err_msg),
+                                );
+                                false
+                            }
+                        }
+                    }
+                    Update::Shutdown => break,
+                };
+                if fetch_rules {
+                    let fetched_at = epoch_seconds(now());
+                    match Self::request_rules(&client, &endpoints.rules).await {
+                        Ok(definitions) => {
+                            rules.update_rules(definitions);
+                            rules_fetched_at = fetched_at;
+                        }
+                        Err(err_msg) => {
+                            otel_warn!(
+                                name: "XrayRemoteSampler.FailedToFetchRules",
+                                message = "Failed to fetch the sampling rules from the X-Ray sampling API. The last successfully fetched rules will be used if available; otherwise, the default sampler will be applied until a successful fetch.",
+                                reason = format!("{}", err_msg),
+                            );
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    async fn request_rules<C>(client: &C, endpoint: &Uri) -> Result<Vec<SamplingRule>, String>
+    where
+        C: HttpClient,
+    {
+        let mut definitions = Vec::new();
+        let mut request = GetSamplingRulesRequest::default();
+        loop {
+            let response: GetSamplingRulesResponse =
+                Self::post(client, endpoint.clone(), &request).await?;
+            definitions.extend(
+                response
+                    .sampling_rule_records
+                    .into_iter()
+                    .map(|record| record.sampling_rule),
+            );
+            match response.next_token {
+                Some(next_token) if !next_token.is_empty() => request.next_token = Some(next_token),
+                _ => return Ok(definitions),
+            }
+        }
+    }
+
+    async fn post<C, Req, Resp>(client: &C, endpoint: Uri, body: &Req) -> Result<Resp, String>
+    where
+        C: HttpClient,
+        Req: serde::Serialize,
+        Resp: serde::de::DeserializeOwned,
+    {
+        let body = serde_json::to_vec(body)
+            .map_err(|err| format!("cannot serialize the request, {err}"))?;
+        let request = http::Request::post(endpoint)
+            .header("Content-Type", "application/json")
+            .body(body.into())
+            .unwrap();
+
+        let resp = client
+            .send_bytes(request)
+            .await
+            .map_err(|err| format!("the request is failed to send {err}"))?;
+
+        // process failures
+        if resp.status() != http::StatusCode::OK {
+            return Err(format!(
+                "the http response code is not 200 but {}",
+                resp.status()
+            ));
+        }
+
+        // deserialize the response
+        serde_json::from_slice(&resp.body()[..])
+            .map_err(|err| format!("cannot deserialize the response, {err}"))
+    }
+}
+
+fn epoch_seconds(time: SystemTime) -> f64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs_f64())
+        .unwrap_or_default()
+}
+
+impl ShouldSample for XrayRemoteSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        let request = Request {
+            service_name: &self.service.name,
+            service_type: &self.service.service_type,
+            resource_arn: &self.service.resource_arn,
+            attributes,
+        };
+        match self
+            .inner
+            .should_sample(&request, epoch_seconds(now()), trace_id)
+        {
+            Some(decision) => SamplingResult {
+                decision,
+                attributes: Vec::new(),
+                trace_state: match parent_context {
+                    Some(ctx) => ctx.span().span_context().trace_state().clone(),
+                    None => TraceState::default(),
+                },
+            },
+            None => self.default_sampler.should_sample(
+                parent_context,
+                trace_id,
+                name,
+                span_kind,
+                attributes,
+                links,
+            ),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "rt-tokio"))]
+mod tests {
+    use super::*;
+    use crate::runtime;
+    use async_trait::async_trait;
+    use opentelemetry::trace::SamplingDecision;
+    use opentelemetry_http::{Bytes, HttpError};
+    use std::sync::Mutex;
+
+    // Serves the X-Ray sampling API like the X-Ray daemon, recording the requests
+    #[derive(Debug, Clone, Default)]
+    struct MockDaemon {
+        rules: Arc<Mutex<Vec<&'static str>>>,
+        targets: Arc<Mutex<&'static str>>,
+        requests: Arc<Mutex<Vec<(String, serde_json::Value)>>>,
+    }
+
+    #[async_trait]
+    impl HttpClient for MockDaemon {
+        async fn send_bytes(
+            &self,
+            request: http::Request<Bytes>,
+        ) -> Result<http::Response<Bytes>, HttpError> {
+            let path = request.uri().path().to_string();
+            self.requests.lock().unwrap().push((
+                path.clone(),
+                serde_json::from_slice(request.body()).unwrap(),
+            ));
+            let body = match path.as_str() {
+                SAMPLING_RULES_PATH => {
+                    let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                    // one page per rule
+                    let page = body["NextToken"]
+                        .as_str()
+                        .map_or(0, |token| token.parse::<usize>().unwrap());
+                    let rules = self.rules.lock().unwrap();
+                    let next_token = if page + 1 < rules.len() {
+                        format!("\"{}\"", page + 1)
+                    } else {
+                        "null".to_string()
+                    };
+                    format!(
+                        r#"{{"SamplingRuleRecords": [{{"SamplingRule": {}}}], "NextToken": {}}}"#,
+                        rules[page], next_token
+                    )
+                }
+                SAMPLING_TARGETS_PATH => self.targets.lock().unwrap().to_string(),
+                _ => {
+                    return Ok(http::Response::builder()
+                        .status(404)
+                        .body(Bytes::new())
+                        .unwrap())
+                }
+            };
+            Ok(http::Response::builder()
+                .status(200)
+                .body(Bytes::from(body))
+                .unwrap())
+        }
+    }
+
+    impl MockDaemon {
+        fn requests_to(&self, path: &str) -> Vec<serde_json::Value> {
+            self.requests
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(request_path, _)| request_path == path)
+                .map(|(_, body)| body.clone())
+                .collect()
+        }
+    }
+
+    const HEALTH_RULE: &str = r#"{"RuleName": "health", "RuleARN": "arn:aws:xray:us-east-1:123456789012:sampling-rule/health", "Priority": 1, "FixedRate": 0.0, "ReservoirSize": 0, "ServiceName": "checkout", "ServiceType": "*", "Host": "*", "HTTPMethod": "GET", "URLPath": "/health", "ResourceARN": "*", "Attributes": {}, "Version": 1}"#;
+    const DEFAULT_RULE: &str = r#"{"RuleName": "Default", "RuleARN": "arn:aws:xray:us-east-1:123456789012:sampling-rule/Default", "Priority": 10000, "FixedRate": 1.0, "ReservoirSize": 1, "ServiceName": "*", "ServiceType": "*", "Host": "*", "HTTPMethod": "*", "URLPath": "*", "ResourceARN": "*", "Version": 1}"#;
+
+    fn sample(sampler: &Sampler, attributes: &[KeyValue]) -> SamplingDecision {
+        sampler
+            .should_sample(
+                None,
+                TraceId::from(1),
+                "span",
+                &SpanKind::Server,
+                attributes,
+                &[],
+            )
+            .decision
+    }
+
+    async fn wait_for(condition: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    #[test]
+    fn invalid_configuration() {
+        for (endpoint, service_name) in [
+            ("", "checkout"),
+            ("http://localhost:2000", ""),
+            ("not a uri", "checkout"),
+        ] {
+            let result = Sampler::xray_remote(
+                runtime::Tokio,
+                MockDaemon::default(),
+                Sampler::AlwaysOff,
+                service_name,
+            )
+            .with_endpoint(endpoint)
+            .build();
+            assert!(result.is_err(), "{endpoint} {service_name}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rules_are_polled_from_the_daemon() {
+        let daemon = MockDaemon::default();
+        *daemon.rules.lock().unwrap() = vec![DEFAULT_RULE, HEALTH_RULE];
+        *daemon.targets.lock().unwrap() = r#"{"SamplingTargetDocuments": []}"#;
+        let sampler = Sampler::xray_remote(
+            runtime::Tokio,
+            daemon.clone(),
+            Sampler::AlwaysOff,
+            "checkout",
+        )
+        .with_endpoint("http://localhost:2000/")
+        .with_targets_interval(Duration::from_millis(20))
+        .build()
+        .unwrap();
+
+        // the default sampler decides until the rules are fetched
+        let health_check = [
+            KeyValue::new("http.request.method", "GET"),
+            KeyValue::new("url.path", "/health"),
+        ];
+        wait_for(|| sample(&sampler, &[]) == SamplingDecision::RecordAndSample).await;
+
+        assert_eq!(daemon.requests_to(SAMPLING_RULES_PATH).len(), 2);
+        assert_eq!(sample(&sampler, &health_check), SamplingDecision::Drop);
+
+        // the statistics of both rules are reported
+        wait_for(|| !daemon.requests_to(SAMPLING_TARGETS_PATH).is_empty()).await;
+        let report = &daemon.requests_to(SAMPLING_TARGETS_PATH)[0];
+        let documents = report["SamplingStatisticsDocuments"].as_array().unwrap();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0]["RuleName"], "health");
+        assert_eq!(documents[0]["ClientID"].as_str().unwrap().len(), 24);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rules_are_fetched_again_when_modified() {
+        let daemon = MockDaemon::default();
+        *daemon.rules.lock().unwrap() = vec![DEFAULT_RULE];
+        *daemon.targets.lock().unwrap() = r#"{"SamplingTargetDocuments": [{"RuleName": "Default", "FixedRate": 0.0, "ReservoirQuota": 0, "ReservoirQuotaTTL": 4102444800}], "LastRuleModification": 4102444800}"#;
+        let sampler = Sampler::xray_remote(
+            runtime::Tokio,
+            daemon.clone(),
+            Sampler::AlwaysOn,
+            "checkout",
+        )
+        .with_targets_interval(Duration::from_millis(20))
+        .build()
+        .unwrap();
+
+        // the target drops everything once applied
+        wait_for(|| sample(&sampler, &[]) == SamplingDecision::Drop).await;
+        *daemon.rules.lock().unwrap() = vec![HEALTH_RULE];
+        wait_for(|| daemon.requests_to(SAMPLING_RULES_PATH).len() >= 2).await;
+        // the new rules don't apply to other requests, left to the default sampler
+        wait_for(|| sample(&sampler, &[]) == SamplingDecision::RecordAndSample).await;
+    }
+}
@@ -0,0 +1,456 @@
+use crate::trace::sampler::rule_based::glob_match;
+use crate::trace::sampler::sample_based_on_probability;
+use crate::trace::sampler::xray_remote::remote::{
+    SamplingRule, SamplingStatisticsDocument, SamplingTargetDocument,
+};
+use opentelemetry::trace::{SamplingDecision, TraceId};
+use opentelemetry::{otel_warn, KeyValue};
+use std::fmt::{Debug, Formatter};
+use std::sync::Mutex;
+
+// Only version 1 of the rules is defined by X-Ray
+const SUPPORTED_RULE_VERSION: i32 = 1;
+
+// Attributes holding the request properties matched by the rules, current semantic
+// conventions first
+const HTTP_METHOD_KEYS: [&str; 2] = ["http.request.method", "http.method"];
+const URL_PATH_KEYS: [&str; 2] = ["url.path", "http.target"];
+const HOST_KEYS: [&str; 2] = ["server.address", "http.host"];
+
+/// Request properties a span is matched on.
+pub(crate) struct Request<'a> {
+    pub(crate) service_name: &'a str,
+    pub(crate) service_type: &'a str,
+    pub(crate) resource_arn: &'a str,
+    pub(crate) attributes: &'a [KeyValue],
+}
+
+impl Request<'_> {
+    fn attribute(&self, keys: &[&str]) -> String {
+        keys.iter()
+            .find_map(|key| {
+                self.attributes
+                    .iter()
+                    .find(|kv| kv.key.as_str() == *key)
+                    .map(|kv| kv.value.as_str().into_owned())
+            })
+            .unwrap_or_default()
+    }
+}
+
+// X-Ray patterns are case insensitive
+fn matches(pattern: &str, value: &str) -> bool {
+    glob_match(&pattern.to_lowercase(), &value.to_lowercase())
+}
+
+// The reservoir of a rule, sampling up to the quota assigned to this instance each
+// second. Until a quota is assigned, or once it expires, one request per second is
+// borrowed.
+#[derive(Default)]
+struct Reservoir {
+    quota: Option<u64>,
+    // epoch seconds
+    quota_expires_at: f64,
+    second: u64,
+    taken: u64,
+}
+
+enum Taken {
+    Quota,
+    Borrowed,
+}
+
+impl Reservoir {
+    fn take(&mut self, now: f64, can_borrow: bool) -> Option<Taken> {
+        let second = now as u64;
+        if second != self.second {
+            self.second = second;
+            self.taken = 0;
+        }
+        let (limit, taken) = match self.quota.filter(|_| now < self.quota_expires_at) {
+            Some(quota) => (quota, Taken::Quota),
+            None if can_borrow => (1, Taken::Borrowed),
+            None => return None,
+        };
+        if self.taken < limit {
+            self.taken += 1;
+            Some(taken)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Default)]
+struct Statistics {
+    request_count: u64,
+    sampled_count: u64,
+    borrow_count: u64,
+}
+
+struct Rule {
+    definition: SamplingRule,
+    // assigned by the last target, kept when the rules are fetched again
+    target_fixed_rate: Option<f64>,
+    reservoir: Reservoir,
+    statistics: Statistics,
+}
+
+impl Rule {
+    fn applies_to(&self, request: &Request<'_>) -> bool {
+        let rule = &self.definition;
+        matches(&rule.service_name, request.service_name)
+            && matches(&rule.service_type, request.service_type)
+            && matches(&rule.resource_arn, request.resource_arn)
+            && matches(&rule.http_method, &request.attribute(&HTTP_METHOD_KEYS))
+            && matches(&rule.url_path, &request.attribute(&URL_PATH_KEYS))
+            && matches(&rule.host, &request.attribute(&HOST_KEYS))
+            && rule.attributes.iter().all(|(key, pattern)| {
+                request
+                    .attributes
+                    .iter()
+                    .find(|kv| kv.key.as_str() == key)
+                    .is_some_and(|kv| matches(pattern, &kv.value.as_str()))
+            })
+    }
+
+    fn sample(&mut self, now: f64, trace_id: TraceId) -> SamplingDecision {
+        self.statistics.request_count += 1;
+        let can_borrow = self.definition.reservoir_size > 0;
+        match self.reservoir.take(now, can_borrow) {
+            Some(Taken::Quota) => {
+                self.statistics.sampled_count += 1;
+                SamplingDecision::RecordAndSample
+            }
+            Some(Taken::Borrowed) => {
+                self.statistics.borrow_count += 1;
+                SamplingDecision::RecordAndSample
+            }
+            None => {
+                let fixed_rate = self.target_fixed_rate.unwrap_or(self.definition.fixed_rate);
+                let decision = sample_based_on_probability(&fixed_rate, trace_id);
+                if decision == SamplingDecision::RecordAndSample {
+                    self.statistics.sampled_count += 1;
+                }
+                decision
+            }
+        }
+    }
+}
+
+/// The rules fetched from the X-Ray sampling API, in the order they are evaluated.
+pub(crate) struct Inner {
+    rules: Mutex<Vec<Rule>>,
+    // identifies this instance in the statistics reported to X-Ray
+    client_id: String,
+    shut_down: futures_channel::mpsc::Sender<()>,
+}
+
+impl Debug for Inner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("XrayRemoteSamplerInner")
+            .field("client_id", &self.client_id)
+            .finish()
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let _ = self.shut_down.try_send(());
+    }
+}
+
+impl Inner {
+    pub(crate) fn new(client_id: String, shut_down: futures_channel::mpsc::Sender<()>) -> Self {
+        Inner {
+            rules: Mutex::new(Vec::new()),
+            client_id,
+            shut_down,
+        }
+    }
+
+    /// Replace the rules, keeping the targets and statistics of the rules with an
+    /// unchanged name.
+    pub(crate) fn update_rules(&self, definitions: Vec<SamplingRule>) {
+        let Ok(mut rules) = self.rules.lock() else {
+            otel_warn!(
+                name: "XrayRemoteSampler.MutexPoisoned",
+                message = "Unable to update the X-Ray sampling rules: the sampler's internal mutex is poisoned. The last known rules will continue to be used.",
+            );
+            return;
+        };
+        let mut previous = std::mem::take(&mut *rules);
+        *rules = definitions
+            .into_iter()
+            .filter(|definition| definition.version == SUPPORTED_RULE_VERSION)
+            .map(|definition| {
+                match previous
+                    .iter()
+                    .position(|rule| rule.definition.rule_name == definition.rule_name)
+                {
+                    Some(index) => Rule {
+                        definition,
+                        ..previous.swap_remove(index)
+                    },
+                    None => Rule {
+                        definition,
+                        target_fixed_rate: None,
+                        reservoir: Reservoir::default(),
+                        statistics: Statistics::default(),
+                    },
+                }
+            })
+            .collect();
+        rules.sort_by(|a, b| {
+            (a.definition.priority, &a.definition.rule_name)
+                .cmp(&(b.definition.priority, &b.definition.rule_name))
+        });
+    }
+
+    /// Apply the targets assigned to this instance.
+    pub(crate) fn update_targets(&self, targets: Vec<SamplingTargetDocument>) {
+        let Ok(mut rules) = self.rules.lock() else {
+            return;
+        };
+        for target in targets {
+            if let Some(rule) = rules
+                .iter_mut()
+                .find(|rule| rule.definition.rule_name == target.rule_name)
+            {
+                rule.target_fixed_rate = Some(target.fixed_rate);
+                if let Some(quota) = target.reservoir_quota {
+                    rule.reservoir.quota = Some(quota);
+                    rule.reservoir.quota_expires_at =
+                        target.reservoir_quota_ttl.unwrap_or(f64::INFINITY);
+                }
+            }
+        }
+    }
+
+    /// Take the statistics of the rules since the last report.
+    pub(crate) fn take_statistics(&self, now: f64) -> Vec<SamplingStatisticsDocument> {
+        let Ok(mut rules) = self.rules.lock() else {
+            return Vec::new();
+        };
+        rules
+            .iter_mut()
+            .map(|rule| {
+                let statistics = std::mem::take(&mut rule.statistics);
+                SamplingStatisticsDocument {
+                    rule_name: rule.definition.rule_name.clone(),
+                    client_id: self.client_id.clone(),
+                    timestamp: now as i64,
+                    request_count: statistics.request_count,
+                    sampled_count: statistics.sampled_count,
+                    borrow_count: statistics.borrow_count,
+                }
+            })
+            .collect()
+    }
+
+    /// The decision of the first rule applying to the request, none before the rules
+    /// are fetched or if no rule applies.
+    pub(crate) fn should_sample(
+        &self,
+        request: &Request<'_>,
+        now: f64,
+        trace_id: TraceId,
+    ) -> Option<SamplingDecision> {
+        let mut rules = self.rules.lock().ok()?;
+        rules
+            .iter_mut()
+            .find(|rule| rule.applies_to(request))
+            .map(|rule| rule.sample(now, trace_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn rule(name: &str, priority: i32, fixed_rate: f64, reservoir_size: u64) -> SamplingRule {
+        SamplingRule {
+            rule_name: name.to_string(),
+            priority,
+            fixed_rate,
+            reservoir_size,
+            service_name: "*".to_string(),
+            service_type: "*".to_string(),
+            host: "*".to_string(),
+            http_method: "*".to_string(),
+            url_path: "*".to_string(),
+            resource_arn: "*".to_string(),
+            attributes: HashMap::new(),
+            version: 1,
+        }
+    }
+
+    fn inner() -> Inner {
+        let (shutdown_tx, _) = futures_channel::mpsc::channel(1);
+        Inner::new("client".to_string(), shutdown_tx)
+    }
+
+    fn request(attributes: &[KeyValue]) -> Request<'_> {
+        Request {
+            service_name: "checkout",
+            service_type: "",
+            resource_arn: "",
+            attributes,
+        }
+    }
+
+    const NOW: f64 = 1_700_000_000.0;
+
+    #[test]
+    fn no_rules_no_decision() {
+        assert_eq!(
+            inner().should_sample(&request(&[]), NOW, TraceId::from(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn rules_are_matched_by_priority() {
+        let inner = inner();
+        let mut health = rule("health", 1, 0.0, 0);
+        health.url_path = "/HEALTH*".to_string();
+        health.http_method = "GET".to_string();
+        inner.update_rules(vec![rule("Default", 10000, 1.0, 0), health]);
+
+        let health_check = [
+            KeyValue::new("http.request.method", "get"),
+            KeyValue::new("url.path", "/health/live"),
+        ];
+        let order = [
+            KeyValue::new("http.method", "POST"),
+            KeyValue::new("http.target", "/health"),
+        ];
+        assert_eq!(
+            inner.should_sample(&request(&health_check), NOW, TraceId::from(1)),
+            Some(SamplingDecision::Drop)
+        );
+        assert_eq!(
+            inner.should_sample(&request(&order), NOW, TraceId::from(1)),
+            Some(SamplingDecision::RecordAndSample)
+        );
+    }
+
+    #[test]
+    fn rule_attributes_must_be_present() {
+        let inner = inner();
+        let mut tenant = rule("tenant", 1, 1.0, 0);
+        tenant.attributes = HashMap::from([("tenant".to_string(), "acme-*".to_string())]);
+        tenant.service_name = "other".to_string();
+        let mut default = rule("Default", 10000, 0.0, 0);
+        default.service_name = "check*".to_string();
+        inner.update_rules(vec![tenant.clone(), default]);
+
+        let attributes = [KeyValue::new("tenant", "acme-eu")];
+        assert_eq!(
+            inner.should_sample(&request(&attributes), NOW, TraceId::from(1)),
+            Some(SamplingDecision::Drop)
+        );
+
+        tenant.service_name = "*".to_string();
+        inner.update_rules(vec![tenant]);
+        assert_eq!(
+            inner.should_sample(&request(&attributes), NOW, TraceId::from(1)),
+            Some(SamplingDecision::RecordAndSample)
+        );
+        assert_eq!(
+            inner.should_sample(&request(&[]), NOW, TraceId::from(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn unsupported_versions_are_skipped() {
+        let inner = inner();
+        let mut future = rule("future", 1, 1.0, 0);
+        future.version = 2;
+        inner.update_rules(vec![future]);
+
+        assert_eq!(
+            inner.should_sample(&request(&[]), NOW, TraceId::from(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn borrows_one_request_per_second_until_a_quota_is_assigned() {
+        let inner = inner();
+        inner.update_rules(vec![rule("Default", 10000, 0.0, 5)]);
+
+        let decisions = (0..3)
+            .map(|_| inner.should_sample(&request(&[]), NOW, TraceId::from(1)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decisions,
+            vec![
+                Some(SamplingDecision::RecordAndSample),
+                Some(SamplingDecision::Drop),
+                Some(SamplingDecision::Drop)
+            ]
+        );
+        assert_eq!(
+            inner.should_sample(&request(&[]), NOW + 1.0, TraceId::from(1)),
+            Some(SamplingDecision::RecordAndSample)
+        );
+
+        let statistics = inner.take_statistics(NOW + 1.0);
+        assert_eq!(
+            statistics,
+            vec![SamplingStatisticsDocument {
+                rule_name: "Default".to_string(),
+                client_id: "client".to_string(),
+                timestamp: NOW as i64 + 1,
+                request_count: 4,
+                sampled_count: 0,
+                borrow_count: 2,
+            }]
+        );
+        assert_eq!(inner.take_statistics(NOW + 2.0)[0].request_count, 0);
+    }
+
+    #[test]
+    fn targets_assign_the_quota_and_fixed_rate() {
+        let inner = inner();
+        inner.update_rules(vec![rule("Default", 10000, 0.0, 5)]);
+        inner.update_targets(vec![SamplingTargetDocument {
+            rule_name: "Default".to_string(),
+            fixed_rate: 1.0,
+            reservoir_quota: Some(2),
+            reservoir_quota_ttl: Some(NOW + 10.0),
+        }]);
+
+        for _ in 0..3 {
+            assert_eq!(
+                inner.should_sample(&request(&[]), NOW, TraceId::from(1)),
+                Some(SamplingDecision::RecordAndSample)
+            );
+        }
+        let statistics = inner.take_statistics(NOW);
+        assert_eq!(statistics[0].request_count, 3);
+        assert_eq!(statistics[0].sampled_count, 3);
+        assert_eq!(statistics[0].borrow_count, 0);
+
+        // The quota expired, borrowing until the next target
+        inner.update_rules(vec![rule("Default", 10000, 1.0, 5)]);
+        inner.update_targets(vec![SamplingTargetDocument {
+            rule_name: "Default".to_string(),
+            fixed_rate: 0.0,
+            reservoir_quota: None,
+            reservoir_quota_ttl: None,
+        }]);
+        assert_eq!(
+            inner.should_sample(&request(&[]), NOW + 20.0, TraceId::from(1)),
+            Some(SamplingDecision::RecordAndSample)
+        );
+        assert_eq!(
+            inner.should_sample(&request(&[]), NOW + 20.0, TraceId::from(1)),
+            Some(SamplingDecision::Drop)
+        );
+        assert_eq!(inner.take_statistics(NOW + 20.0)[0].borrow_count, 1);
+    }
+}
@@ -71,6 +71,11 @@ impl Span {
         self.data.as_mut().map(f)
     }
 
+    /// The kind of this span, `None` if it is not recording.
+    pub fn span_kind(&self) -> Option<&SpanKind> {
+        self.data.as_ref().map(|data| &data.span_kind)
+    }
+
     /// Convert information in this span into `exporter::trace::SpanData`.
     /// This function copies all data from the current span, which will create a
     /// overhead.
@@ -97,13 +102,15 @@ impl opentelemetry::trace::Span for Span {
     ) where
         T: Into<Cow<'static, str>>,
     {
-        let span_events_limit = self.span_limits.max_events_per_span as usize;
-        let event_attributes_limit = self.span_limits.max_attributes_per_event as usize;
+        let span_limits = self.span_limits;
+        let span_events_limit = span_limits.max_events_per_span as usize;
+        let event_attributes_limit = span_limits.max_attributes_per_event as usize;
         self.with_data(|data| {
             if data.events.len() < span_events_limit {
                 let dropped_attributes_count =
                     attributes.len().saturating_sub(event_attributes_limit);
                 attributes.truncate(event_attributes_limit);
+                span_limits.truncate_values(&mut attributes);
 
                 data.events.add_event(Event::new(
                     name,
@@ -134,10 +141,12 @@ impl opentelemetry::trace::Span for Span {
     /// Note that the OpenTelemetry project documents certain ["standard
     /// attributes"](https://github.com/open-telemetry/opentelemetry-specification/tree/v0.5.0/specification/trace/semantic_conventions/README.md)
     /// that have prescribed semantic meanings.
-    fn set_attribute(&mut self, attribute: KeyValue) {
-        let span_attribute_limit = self.span_limits.max_attributes_per_span as usize;
+    fn set_attribute(&mut self, mut attribute: KeyValue) {
+        let span_limits = self.span_limits;
+        let span_attribute_limit = span_limits.max_attributes_per_span as usize;
         self.with_data(|data| {
             if data.attributes.len() < span_attribute_limit {
+                span_limits.truncate_values(std::slice::from_mut(&mut attribute));
                 data.attributes.push(attribute);
             } else {
                 data.dropped_attributes_count += 1;
@@ -171,14 +180,16 @@ impl opentelemetry::trace::Span for Span {
     /// Add `Link` to this `Span`
     ///
     fn add_link(&mut self, span_context: SpanContext, attributes: Vec<KeyValue>) {
-        let span_links_limit = self.span_limits.max_links_per_span as usize;
-        let link_attributes_limit = self.span_limits.max_attributes_per_link as usize;
+        let span_limits = self.span_limits;
+        let span_links_limit = span_limits.max_links_per_span as usize;
+        let link_attributes_limit = span_limits.max_attributes_per_link as usize;
         self.with_data(|data| {
             if data.links.links.len() < span_links_limit {
                 let dropped_attributes_count =
                     attributes.len().saturating_sub(link_attributes_limit);
                 let mut attributes = attributes;
                 attributes.truncate(link_attributes_limit);
+                span_limits.truncate_values(&mut attributes);
                 data.links.add_link(Link::new(
                     span_context,
                     attributes,
@@ -705,6 +716,108 @@ mod tests {
         assert_eq!(event_vec.len(), DEFAULT_MAX_EVENT_PER_SPAN as usize);
     }
 
+    #[test]
+    fn exceed_attribute_value_length_limit() {
+        let provider = crate::trace::SdkTracerProvider::builder()
+            .with_simple_exporter(NoopSpanExporter::new())
+            .with_max_attribute_value_length(3)
+            .build();
+        let tracer = provider.tracer("opentelemetry-test");
+        let link_context = SpanContext::new(
+            TraceId::from(12),
+            SpanId::from(12),
+            TraceFlags::default(),
+            false,
+            Default::default(),
+        );
+
+        // add attributes, events and links when build
+        let span_builder = tracer
+            .span_builder("test")
+            .with_attributes(vec![KeyValue::new("statement", "SELECT 1")])
+            .with_events(vec![Event::new(
+                "event",
+                SystemTime::now(),
+                vec![KeyValue::new("body", "héllo")],
+                0,
+            )])
+            .with_links(vec![Link::new(
+                link_context.clone(),
+                vec![KeyValue::new(
+                    "ids",
+                    opentelemetry::Value::Array(opentelemetry::Array::String(vec![
+                        "12345".into(),
+                        "6".into(),
+                    ])),
+                )],
+                0,
+            )]);
+        let mut span = tracer.build(span_builder);
+
+        // add them after build
+        span.set_attribute(KeyValue::new("body", "🦀🦀🦀🦀"));
+        span.set_attribute(KeyValue::new("count", 123456));
+        span.add_event("another event", vec![KeyValue::new("body", "abcdef")]);
+        span.add_link(link_context, vec![KeyValue::new("name", "abcdef")]);
+
+        let data = span.data.clone().expect("span data should not be empty");
+        assert_eq!(
+            data.attributes,
+            vec![
+                KeyValue::new("statement", "SEL"),
+                KeyValue::new("body", "🦀🦀🦀"),
+                KeyValue::new("count", 123456),
+            ]
+        );
+        let event_attributes: Vec<_> = data
+            .events
+            .iter()
+            .map(|event| event.attributes[0].clone())
+            .collect();
+        assert_eq!(
+            event_attributes,
+            vec![KeyValue::new("body", "hél"), KeyValue::new("body", "abc")]
+        );
+        let link_attributes: Vec<_> = data
+            .links
+            .iter()
+            .map(|link| link.attributes[0].clone())
+            .collect();
+        assert_eq!(
+            link_attributes,
+            vec![
+                KeyValue::new(
+                    "ids",
+                    opentelemetry::Value::Array(opentelemetry::Array::String(vec![
+                        "123".into(),
+                        "6".into()
+                    ]))
+                ),
+                KeyValue::new("name", "abc"),
+            ]
+        );
+    }
+
+    #[test]
+    fn attribute_value_length_limit_from_env() {
+        temp_env::with_vars(
+            [
+                ("OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT", Some("2")),
+                ("OTEL_SPAN_ATTRIBUTE_VALUE_LENGTH_LIMIT", None::<&str>),
+            ],
+            || {
+                let provider = crate::trace::SdkTracerProvider::builder()
+                    .with_simple_exporter(NoopSpanExporter::new())
+                    .build();
+                let mut span = provider.tracer("opentelemetry-test").start("test");
+                span.set_attribute(KeyValue::new("key", "value"));
+
+                let data = span.data.clone().expect("span data should not be empty");
+                assert_eq!(data.attributes, vec![KeyValue::new("key", "va")]);
+            },
+        );
+    }
+
     #[test]
     fn test_span_exported_data() {
         let provider = crate::trace::SdkTracerProvider::builder()
@@ -718,11 +831,13 @@ mod tests {
 
         let exported_data = span.exported_data();
         assert!(exported_data.is_some());
+        assert_eq!(span.span_kind(), Some(&SpanKind::Internal));
         let res = provider.shutdown();
         println!("{res:?}");
         assert!(res.is_ok());
         let dropped_span = tracer.start("span_with_dropped_provider");
         // return none if the provider has already been dropped
         assert!(dropped_span.exported_data().is_none());
+        assert!(dropped_span.span_kind().is_none());
     }
 }
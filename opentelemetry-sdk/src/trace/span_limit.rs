@@ -1,3 +1,6 @@
+use crate::attribute_value::truncate_value;
+use opentelemetry::KeyValue;
+
 /// # Span limit
 /// Erroneous code can add unintended attributes, events, and links to a span. If these collections
 /// are unbounded, they can quickly exhaust available memory, resulting in crashes that are
@@ -9,9 +12,11 @@
 ///  - Maximum allowed span link count
 ///  - Maximum allowed attribute per span event count
 ///  - Maximum allowed attribute per span link count
+///  - Maximum allowed attribute value length
 ///
 /// If the limit has been breached. The attributes, events or links will be dropped based on their
-/// index in the collection. The one added to collections later will be dropped first.
+/// index in the collection. The one added to collections later will be dropped first. Attribute
+/// values longer than the limit are truncated.
 pub(crate) const DEFAULT_MAX_EVENT_PER_SPAN: u32 = 128;
 pub(crate) const DEFAULT_MAX_ATTRIBUTES_PER_SPAN: u32 = 128;
 pub(crate) const DEFAULT_MAX_LINKS_PER_SPAN: u32 = 128;
@@ -31,6 +36,10 @@ pub struct SpanLimits {
     pub max_attributes_per_event: u32,
     /// The max attributes that can be added into a `Link`
     pub max_attributes_per_link: u32,
+    /// The max length of the attribute values of a `Span`, its `Event`s and `Link`s. Longer
+    /// strings, and strings of string arrays, are truncated to this number of characters.
+    /// Unlimited if `None`.
+    pub max_attribute_value_length: Option<u32>,
 }
 
 impl Default for SpanLimits {
@@ -41,6 +50,18 @@ impl Default for SpanLimits {
             max_links_per_span: DEFAULT_MAX_LINKS_PER_SPAN,
             max_attributes_per_link: DEFAULT_MAX_ATTRIBUTES_PER_LINK,
             max_attributes_per_event: DEFAULT_MAX_ATTRIBUTES_PER_EVENT,
+            max_attribute_value_length: None,
+        }
+    }
+}
+
+impl SpanLimits {
+    /// Truncate the attribute values longer than `max_attribute_value_length`.
+    pub(crate) fn truncate_values(&self, attributes: &mut [KeyValue]) {
+        if let Some(max_length) = self.max_attribute_value_length {
+            for attribute in attributes {
+                truncate_value(&mut attribute.value, max_length as usize);
+            }
         }
     }
 }
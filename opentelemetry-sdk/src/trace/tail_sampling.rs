@@ -0,0 +1,906 @@
+//! # Tail Sampling Span Processor
+//!
+//! Head samplers decide when a trace starts, before it is known whether the
+//! trace will be slow or fail. The [`TailSamplingSpanProcessor`] instead
+//! buffers the finished spans of each trace and decides once the trace had
+//! time to complete, keeping the traces matching one of its
+//! [`TailSamplingPolicy`]s. Kept traces are forwarded as a whole to the wrapped
+//! `SpanExporter` or `SpanProcessor`.
+//!
+//! Memory is bounded by the number of buffered traces and of spans per trace.
+//! When the trace limit is reached, the oldest trace is decided early with the
+//! spans it has so far. Spans ending after their trace was decided follow that
+//! decision. Traces are forwarded from a background thread, never from the
+//! thread ending the span.
+
+use crate::error::{OTelSdkError, OTelSdkResult};
+use crate::resource::Resource;
+use crate::trace::sampler::sample_based_on_probability;
+use crate::trace::{Span, SpanData, SpanExporter, SpanProcessor};
+use opentelemetry::trace::{SamplingDecision, SpanId, Status, TraceId};
+use opentelemetry::{otel_debug, otel_warn, Context, Key, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Default time waited for the spans of a trace before deciding on it.
+const DEFAULT_DECISION_WAIT: Duration = Duration::from_secs(10);
+/// Default maximum number of traces buffered at once.
+const DEFAULT_MAX_TRACES: usize = 10_000;
+/// Default maximum number of spans buffered per trace.
+const DEFAULT_MAX_SPANS_PER_TRACE: usize = 1_000;
+/// Maximum number of traces and late spans queued to the background thread.
+const MAX_QUEUE_SIZE: usize = 2_048;
+
+/// A rule keeping the traces it matches. A trace is kept if any policy of the
+/// [`TailSamplingSpanProcessor`] matches it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TailSamplingPolicy {
+    /// Keep traces with at least one span with an error status.
+    Error,
+    /// Keep traces whose root span lasted at least the threshold. If the root
+    /// span has not ended when the trace is decided, the time from the first
+    /// span start to the last span end is used instead.
+    Latency(Duration),
+    /// Keep traces with at least one span having the attribute, with the given
+    /// value if any.
+    Attribute {
+        /// The attribute key.
+        key: Key,
+        /// The value to match, any value if `None`.
+        value: Option<Value>,
+    },
+    /// Keep the given ratio of traces, chosen by their trace id in the same way
+    /// as [`Sampler::TraceIdRatioBased`](crate::trace::Sampler::TraceIdRatioBased).
+    Probabilistic(f64),
+}
+
+impl TailSamplingPolicy {
+    /// Whether the policy keeps the trace made of the spans.
+    fn matches(&self, trace_id: TraceId, spans: &[SpanData]) -> bool {
+        match self {
+            TailSamplingPolicy::Error => spans
+                .iter()
+                .any(|span| matches!(span.status, Status::Error { .. })),
+            TailSamplingPolicy::Latency(threshold) => {
+                trace_latency(spans).is_some_and(|latency| latency >= *threshold)
+            }
+            TailSamplingPolicy::Attribute { key, value } => spans.iter().any(|span| {
+                span.attributes.iter().any(|attribute| {
+                    attribute.key == *key
+                        && value
+                            .as_ref()
+                            .map_or(true, |value| attribute.value == *value)
+                })
+            }),
+            TailSamplingPolicy::Probabilistic(ratio) => {
+                sample_based_on_probability(ratio, trace_id) == SamplingDecision::RecordAndSample
+            }
+        }
+    }
+}
+
+/// Duration of the root span, or of the whole trace if the root span is missing.
+fn trace_latency(spans: &[SpanData]) -> Option<Duration> {
+    let (start, end) = match spans
+        .iter()
+        .find(|span| span.parent_span_id == SpanId::INVALID)
+    {
+        Some(root) => (root.start_time, root.end_time),
+        None => (
+            spans.iter().map(|span| span.start_time).min()?,
+            spans.iter().map(|span| span.end_time).max()?,
+        ),
+    };
+    end.duration_since(start).ok()
+}
+
+/// Where the kept traces go.
+trait TraceSink: Send + Sync + fmt::Debug {
+    fn forward(&self, trace: Vec<SpanData>) -> OTelSdkResult;
+    fn force_flush(&self) -> OTelSdkResult;
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult;
+    fn set_resource(&mut self, resource: &Resource);
+}
+
+#[derive(Debug)]
+struct ExporterSink<E: SpanExporter>(Mutex<E>);
+
+impl<E: SpanExporter> TraceSink for ExporterSink<E> {
+    fn forward(&self, trace: Vec<SpanData>) -> OTelSdkResult {
+        let exporter = self.0.lock().map_err(|_| {
+            OTelSdkError::InternalFailure("TailSamplingSpanProcessor mutex poison".into())
+        })?;
+        futures_executor::block_on(exporter.export(trace))
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        match self.0.lock() {
+            Ok(mut exporter) => exporter.force_flush(),
+            Err(_) => Err(OTelSdkError::InternalFailure(
+                "TailSamplingSpanProcessor mutex poison at force flush".into(),
+            )),
+        }
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        match self.0.lock() {
+            Ok(mut exporter) => exporter.shutdown_with_timeout(timeout),
+            Err(_) => Err(OTelSdkError::InternalFailure(
+                "TailSamplingSpanProcessor mutex poison at shutdown".into(),
+            )),
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        if let Ok(exporter) = self.0.get_mut() {
+            exporter.set_resource(resource);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ProcessorSink<P: SpanProcessor>(P);
+
+impl<P: SpanProcessor> TraceSink for ProcessorSink<P> {
+    fn forward(&self, trace: Vec<SpanData>) -> OTelSdkResult {
+        for span in trace {
+            self.0.on_end(span);
+        }
+        Ok(())
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.0.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.0.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.0.set_resource(resource);
+    }
+}
+
+#[derive(Debug)]
+struct PendingTrace {
+    spans: Vec<SpanData>,
+    first_seen: Instant,
+    dropped_spans: usize,
+}
+
+/// Buffered traces, and the recent decisions applied to late spans.
+#[derive(Debug, Default)]
+struct TraceBuffer {
+    pending: HashMap<TraceId, PendingTrace>,
+    arrival: VecDeque<TraceId>, // pending traces, oldest first
+    decided: HashMap<TraceId, bool>,
+    decision_order: VecDeque<TraceId>, // decided traces, oldest first
+}
+
+#[derive(Debug)]
+struct Inner {
+    buffer: Mutex<TraceBuffer>,
+    sink: RwLock<Box<dyn TraceSink>>,
+    policies: Vec<TailSamplingPolicy>,
+    decision_wait: Duration,
+    max_traces: usize,
+    max_spans_per_trace: usize,
+}
+
+impl Inner {
+    fn should_keep(&self, trace_id: TraceId, spans: &[SpanData]) -> bool {
+        self.policies.is_empty()
+            || self
+                .policies
+                .iter()
+                .any(|policy| policy.matches(trace_id, spans))
+    }
+
+    /// Decide on the trace and remember the decision for its late spans.
+    /// Returns the spans to forward if the trace is kept.
+    fn decide(
+        &self,
+        buffer: &mut TraceBuffer,
+        trace_id: TraceId,
+        trace: PendingTrace,
+    ) -> Option<Vec<SpanData>> {
+        let keep = self.should_keep(trace_id, &trace.spans);
+        if buffer.decided.len() >= self.max_traces {
+            if let Some(oldest) = buffer.decision_order.pop_front() {
+                buffer.decided.remove(&oldest);
+            }
+        }
+        buffer.decided.insert(trace_id, keep);
+        buffer.decision_order.push_back(trace_id);
+        keep.then_some(trace.spans)
+    }
+
+    /// Decide on the traces buffered for at least the decision wait, or on all
+    /// of them if `all` is set.
+    fn decide_pending(&self, all: bool) -> Vec<Vec<SpanData>> {
+        let mut kept = Vec::new();
+        let Ok(mut buffer) = self.buffer.lock() else {
+            return kept;
+        };
+        while let Some(trace_id) = buffer.arrival.front().copied() {
+            let expired = buffer.pending.get(&trace_id).map_or(true, |trace| {
+                trace.first_seen.elapsed() >= self.decision_wait
+            });
+            if !all && !expired {
+                break;
+            }
+            buffer.arrival.pop_front();
+            if let Some(trace) = buffer.pending.remove(&trace_id) {
+                kept.extend(self.decide(&mut buffer, trace_id, trace));
+            }
+        }
+        kept
+    }
+
+    /// Decide on all pending traces and flush the sink.
+    fn flush(&self) -> OTelSdkResult {
+        let kept = self.decide_pending(true);
+        self.forward(kept);
+        match self.sink.read() {
+            Ok(sink) => sink.force_flush(),
+            Err(_) => Err(OTelSdkError::InternalFailure(
+                "TailSamplingSpanProcessor lock poison at force flush".into(),
+            )),
+        }
+    }
+
+    fn forward(&self, traces: Vec<Vec<SpanData>>) {
+        if traces.is_empty() {
+            return;
+        }
+        let Ok(sink) = self.sink.read() else {
+            return;
+        };
+        for trace in traces {
+            if let Err(err) = sink.forward(trace) {
+                otel_debug!(
+                    name: "TailSamplingSpanProcessor.ForwardError",
+                    reason = format!("{:?}", err)
+                );
+            }
+        }
+    }
+}
+
+/// Messages handled by the background thread of the processor.
+#[derive(Debug)]
+enum TailSamplingMessage {
+    /// Forward a trace decided on the thread ending a span.
+    Forward(Vec<SpanData>),
+    ForceFlush(SyncSender<OTelSdkResult>),
+    Shutdown,
+}
+
+/// A [`SpanProcessor`] deciding which traces to keep once they are complete.
+///
+/// Finished spans are buffered per trace. A trace is decided once its first
+/// span has been buffered for the decision wait, on `force_flush` and on
+/// `shutdown`, or early when the buffer is full. Traces matching any of the
+/// policies, or all traces if there are none, are forwarded as a whole. Spans
+/// that are not sampled are ignored, so the head sampler should keep every
+/// trace the policies may want, e.g. `Sampler::AlwaysOn`.
+///
+/// A **dedicated background thread** decides the traces whose wait is over and
+/// forwards all kept traces, so the wrapped exporter must work without an
+/// async runtime, as for the
+/// [`BatchSpanProcessor`](crate::trace::BatchSpanProcessor). Traces decided
+/// early and late spans of kept traces are queued to that thread, up to 2,048
+/// of them; further ones are dropped while the queue is full.
+///
+/// # Example
+///
+/// ```rust
+/// use opentelemetry_sdk::{
+///     testing::trace::NoopSpanExporter,
+///     trace::{SdkTracerProvider, TailSamplingPolicy, TailSamplingSpanProcessor},
+/// };
+/// use std::time::Duration;
+///
+/// let processor = TailSamplingSpanProcessor::builder(NoopSpanExporter::new())
+///     .with_policy(TailSamplingPolicy::Error)
+///     .with_policy(TailSamplingPolicy::Latency(Duration::from_millis(500)))
+///     .with_policy(TailSamplingPolicy::Probabilistic(0.01))
+///     .with_decision_wait(Duration::from_secs(5))
+///     .build();
+///
+/// let provider = SdkTracerProvider::builder()
+///     .with_span_processor(processor)
+///     .build();
+/// # let _ = provider.shutdown();
+/// ```
+#[derive(Debug)]
+pub struct TailSamplingSpanProcessor {
+    inner: Arc<Inner>,
+    message_sender: SyncSender<TailSamplingMessage>,
+    handle: Mutex<Option<thread::JoinHandle<()>>>,
+    is_shutdown: AtomicBool,
+    dropped_traces_count: AtomicUsize,
+}
+
+impl TailSamplingSpanProcessor {
+    /// Create a builder forwarding the kept traces to the exporter, one export
+    /// per trace.
+    pub fn builder<E>(exporter: E) -> TailSamplingSpanProcessorBuilder
+    where
+        E: SpanExporter + 'static,
+    {
+        TailSamplingSpanProcessorBuilder::new(Box::new(ExporterSink(Mutex::new(exporter))))
+    }
+
+    /// Create a builder forwarding the spans of the kept traces to the processor,
+    /// e.g. a [`BatchSpanProcessor`](crate::trace::BatchSpanProcessor).
+    pub fn builder_with_processor<P>(processor: P) -> TailSamplingSpanProcessorBuilder
+    where
+        P: SpanProcessor + 'static,
+    {
+        TailSamplingSpanProcessorBuilder::new(Box::new(ProcessorSink(processor)))
+    }
+
+    fn new(inner: Inner) -> Self {
+        let inner = Arc::new(inner);
+        let (message_sender, message_receiver) =
+            sync_channel::<TailSamplingMessage>(MAX_QUEUE_SIZE);
+        // Check several times per wait, so traces are decided close to their deadline
+        let tick =
+            (inner.decision_wait / 10).clamp(Duration::from_millis(1), Duration::from_secs(1));
+
+        let worker = Arc::clone(&inner);
+        let handle = thread::Builder::new()
+            .name("OpenTelemetry.Traces.TailSamplingProcessor".to_string())
+            .spawn(move || {
+                otel_debug!(
+                    name: "TailSamplingSpanProcessor.ThreadStarted",
+                    decision_wait_in_millisecs = worker.decision_wait.as_millis(),
+                    max_traces = worker.max_traces,
+                    max_spans_per_trace = worker.max_spans_per_trace,
+                );
+                loop {
+                    match message_receiver.recv_timeout(tick) {
+                        Ok(TailSamplingMessage::Forward(trace)) => worker.forward(vec![trace]),
+                        Ok(TailSamplingMessage::ForceFlush(sender)) => {
+                            let _ = sender.send(worker.flush());
+                        }
+                        // Stop on shutdown, or once the processor is dropped
+                        Ok(TailSamplingMessage::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                            break
+                        }
+                        Err(RecvTimeoutError::Timeout) => {}
+                    }
+                    let kept = worker.decide_pending(false);
+                    worker.forward(kept);
+                }
+                otel_debug!(name: "TailSamplingSpanProcessor.ThreadStopped");
+            })
+            .expect("Failed to spawn thread"); //TODO: Handle thread spawn failure
+
+        TailSamplingSpanProcessor {
+            inner,
+            message_sender,
+            handle: Mutex::new(Some(handle)),
+            is_shutdown: AtomicBool::new(false),
+            dropped_traces_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Queue the trace to the background thread, without waiting for the sink.
+    fn queue(&self, trace: Vec<SpanData>) {
+        match self
+            .message_sender
+            .try_send(TailSamplingMessage::Forward(trace))
+        {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                if self.dropped_traces_count.fetch_add(1, Ordering::Relaxed) == 0 {
+                    otel_warn!(
+                        name: "TailSamplingSpanProcessor.TraceDroppingStarted",
+                        message = "TailSamplingSpanProcessor dropped a kept trace due to queue full. No further log will be emitted for further drops until Shutdown.",
+                    );
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                // The background thread stopped, the processor was shut down
+            }
+        }
+    }
+}
+
+impl SpanProcessor for TailSamplingSpanProcessor {
+    fn on_start(&self, _span: &mut Span, _cx: &Context) {
+        // Ignored
+    }
+
+    fn on_end(&self, span: SpanData) {
+        if !span.span_context.is_sampled() || self.is_shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        let inner = &self.inner;
+        let trace_id = span.span_context.trace_id();
+        let mut kept = None;
+        {
+            let Ok(mut buffer) = inner.buffer.lock() else {
+                return;
+            };
+            if let Some(&keep) = buffer.decided.get(&trace_id) {
+                // The trace was already decided, the late span follows the decision
+                if keep {
+                    kept = Some(vec![span]);
+                }
+            } else if let Some(trace) = buffer.pending.get_mut(&trace_id) {
+                if trace.spans.len() < inner.max_spans_per_trace {
+                    trace.spans.push(span);
+                } else {
+                    trace.dropped_spans += 1;
+                    if trace.dropped_spans == 1 {
+                        otel_warn!(
+                            name: "TailSamplingSpanProcessor.SpanDroppingStarted",
+                            message = "Trace has reached the maximum number of buffered spans, further spans of the trace are dropped",
+                            trace_id = format!("{}", trace_id),
+                            max_spans_per_trace = inner.max_spans_per_trace,
+                        );
+                    }
+                }
+            } else {
+                if buffer.pending.len() >= inner.max_traces {
+                    if let Some(oldest) = buffer.arrival.pop_front() {
+                        if let Some(trace) = buffer.pending.remove(&oldest) {
+                            let spans = trace.spans.len();
+                            let evicted = inner.decide(&mut buffer, oldest, trace);
+                            otel_warn!(
+                                name: "TailSamplingSpanProcessor.TraceEvicted",
+                                message = "Trace buffer is full, the oldest trace was decided before its decision wait elapsed",
+                                trace_id = format!("{}", oldest),
+                                spans = spans,
+                                kept = evicted.is_some(),
+                            );
+                            kept = evicted;
+                        }
+                    }
+                }
+                buffer.pending.insert(
+                    trace_id,
+                    PendingTrace {
+                        spans: vec![span],
+                        first_seen: Instant::now(),
+                        dropped_spans: 0,
+                    },
+                );
+                buffer.arrival.push_back(trace_id);
+            }
+        }
+        if let Some(trace) = kept {
+            self.queue(trace);
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        if self.is_shutdown.load(Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        // Flush on the background thread, after the traces queued before
+        let (sender, receiver) = sync_channel(1);
+        if self
+            .message_sender
+            .send(TailSamplingMessage::ForceFlush(sender))
+            .is_err()
+        {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        receiver
+            .recv()
+            .map_err(|err| OTelSdkError::InternalFailure(format!("{err}")))?
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        if self.is_shutdown.swap(true, Ordering::Relaxed) {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+        let dropped_traces = self.dropped_traces_count.load(Ordering::Relaxed);
+        if dropped_traces > 0 {
+            otel_warn!(
+                name: "TailSamplingSpanProcessor.TracesDropped",
+                dropped_trace_count = dropped_traces,
+                message = "Kept traces were dropped due to the queue to the background thread being full. The wrapped exporter or processor is too slow for the traces decided early or the late spans.",
+            );
+        }
+        // The background thread forwards the queued traces before stopping
+        let _ = self.message_sender.send(TailSamplingMessage::Shutdown);
+        if let Some(handle) = self.handle.lock().ok().and_then(|mut handle| handle.take()) {
+            let _ = handle.join();
+        }
+
+        let deadline = SystemTime::now() + timeout;
+        let kept = self.inner.decide_pending(true);
+        self.inner.forward(kept);
+        let remaining = deadline
+            .duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO);
+        match self.inner.sink.read() {
+            Ok(sink) => sink.shutdown_with_timeout(remaining),
+            Err(_) => Err(OTelSdkError::InternalFailure(
+                "TailSamplingSpanProcessor lock poison at shutdown".into(),
+            )),
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        if let Ok(mut sink) = self.inner.sink.write() {
+            sink.set_resource(resource);
+        }
+    }
+}
+
+/// Builder for [`TailSamplingSpanProcessor`].
+#[derive(Debug)]
+pub struct TailSamplingSpanProcessorBuilder {
+    sink: Box<dyn TraceSink>,
+    policies: Vec<TailSamplingPolicy>,
+    decision_wait: Duration,
+    max_traces: usize,
+    max_spans_per_trace: usize,
+}
+
+impl TailSamplingSpanProcessorBuilder {
+    fn new(sink: Box<dyn TraceSink>) -> Self {
+        TailSamplingSpanProcessorBuilder {
+            sink,
+            policies: Vec::new(),
+            decision_wait: DEFAULT_DECISION_WAIT,
+            max_traces: DEFAULT_MAX_TRACES,
+            max_spans_per_trace: DEFAULT_MAX_SPANS_PER_TRACE,
+        }
+    }
+
+    /// Add a policy. Traces matching any policy are kept, all traces are kept
+    /// if no policy is set.
+    pub fn with_policy(mut self, policy: TailSamplingPolicy) -> Self {
+        self.policies.push(policy);
+        self
+    }
+
+    /// Set the time to wait after the first span of a trace ends before
+    /// deciding on the trace. The default is 10 seconds.
+    pub fn with_decision_wait(mut self, decision_wait: Duration) -> Self {
+        self.decision_wait = decision_wait;
+        self
+    }
+
+    /// Set the maximum number of traces buffered at once. The default is 10,000.
+    pub fn with_max_traces(mut self, max_traces: usize) -> Self {
+        self.max_traces = max_traces.max(1);
+        self
+    }
+
+    /// Set the maximum number of spans buffered per trace, further spans of
+    /// the trace are dropped. The default is 1,000.
+    pub fn with_max_spans_per_trace(mut self, max_spans_per_trace: usize) -> Self {
+        self.max_spans_per_trace = max_spans_per_trace.max(1);
+        self
+    }
+
+    /// Build a new processor and start its background thread.
+    pub fn build(self) -> TailSamplingSpanProcessor {
+        TailSamplingSpanProcessor::new(Inner {
+            buffer: Mutex::new(TraceBuffer::default()),
+            sink: RwLock::new(self.sink),
+            policies: self.policies,
+            decision_wait: self.decision_wait,
+            max_traces: self.max_traces,
+            max_spans_per_trace: self.max_spans_per_trace,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "trace"))]
+mod tests {
+    use super::{TailSamplingPolicy, TailSamplingSpanProcessor};
+    use crate::error::OTelSdkResult;
+    use crate::testing::trace::new_test_export_span_data;
+    use crate::trace::{
+        InMemorySpanExporter, InMemorySpanExporterBuilder, SimpleSpanProcessor, SpanData,
+        SpanExporter, SpanProcessor,
+    };
+    use opentelemetry::trace::{SpanContext, SpanId, Status, TraceFlags, TraceId, TraceState};
+    use opentelemetry::KeyValue;
+    use std::sync::mpsc::{sync_channel, Receiver};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant, SystemTime};
+
+    fn span(trace_id: u128, span_id: u64, parent_span_id: u64) -> SpanData {
+        let mut span = new_test_export_span_data();
+        span.span_context = SpanContext::new(
+            TraceId::from(trace_id),
+            SpanId::from(span_id),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        );
+        span.parent_span_id = SpanId::from(parent_span_id);
+        span
+    }
+
+    fn with_duration(mut span: SpanData, duration: Duration) -> SpanData {
+        span.start_time = SystemTime::UNIX_EPOCH;
+        span.end_time = SystemTime::UNIX_EPOCH + duration;
+        span
+    }
+
+    fn exported_trace_ids(exporter: &InMemorySpanExporter) -> Vec<u128> {
+        let mut trace_ids: Vec<u128> = exporter
+            .get_finished_spans()
+            .unwrap()
+            .iter()
+            .map(|span| u128::from_be_bytes(span.span_context.trace_id().to_bytes()))
+            .collect();
+        trace_ids.dedup();
+        trace_ids
+    }
+
+    /// Wait until the exporter received spans of the trace ids, or a deadline passed.
+    fn wait_for_trace_ids(exporter: &InMemorySpanExporter, trace_ids: Vec<u128>) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while exported_trace_ids(exporter) != trace_ids && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(exported_trace_ids(exporter), trace_ids);
+    }
+
+    /// Exporter blocking until it is released.
+    #[derive(Debug)]
+    struct BlockingExporter {
+        release: Arc<Mutex<Receiver<()>>>,
+        inner: InMemorySpanExporter,
+    }
+
+    impl SpanExporter for BlockingExporter {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            let _ = self
+                .release
+                .lock()
+                .unwrap()
+                .recv_timeout(Duration::from_secs(5));
+            self.inner.export(batch).await
+        }
+    }
+
+    #[test]
+    fn keeps_traces_with_errors() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(exporter.clone())
+            .with_policy(TailSamplingPolicy::Error)
+            .build();
+
+        processor.on_end(span(1, 1, 0));
+        let mut failed = span(2, 2, 1);
+        failed.status = Status::error("timeout");
+        processor.on_end(failed);
+        processor.on_end(span(2, 1, 0));
+        processor.force_flush().unwrap();
+
+        assert_eq!(exported_trace_ids(&exporter), vec![2]);
+        assert_eq!(exporter.get_finished_spans().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn keeps_slow_traces() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(exporter.clone())
+            .with_policy(TailSamplingPolicy::Latency(Duration::from_millis(500)))
+            .build();
+
+        // The root span is fast, even though it has a slow child
+        processor.on_end(with_duration(span(1, 2, 1), Duration::from_secs(1)));
+        processor.on_end(with_duration(span(1, 1, 0), Duration::from_millis(100)));
+        processor.on_end(with_duration(span(2, 1, 0), Duration::from_secs(1)));
+        // Without root span, the trace duration is used
+        processor.on_end(with_duration(span(3, 2, 1), Duration::from_secs(1)));
+        processor.force_flush().unwrap();
+
+        assert_eq!(exported_trace_ids(&exporter), vec![2, 3]);
+    }
+
+    #[test]
+    fn keeps_traces_with_attribute() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(exporter.clone())
+            .with_policy(TailSamplingPolicy::Attribute {
+                key: "tenant".into(),
+                value: Some("acme".into()),
+            })
+            .with_policy(TailSamplingPolicy::Attribute {
+                key: "debug".into(),
+                value: None,
+            })
+            .build();
+
+        let mut other_tenant = span(1, 1, 0);
+        other_tenant.attributes = vec![KeyValue::new("tenant", "other")];
+        let mut tenant = span(2, 1, 0);
+        tenant.attributes = vec![KeyValue::new("tenant", "acme")];
+        let mut debug = span(3, 1, 0);
+        debug.attributes = vec![KeyValue::new("debug", true)];
+        for span in [other_tenant, tenant, debug] {
+            processor.on_end(span);
+        }
+        processor.force_flush().unwrap();
+
+        assert_eq!(exported_trace_ids(&exporter), vec![2, 3]);
+    }
+
+    #[test]
+    fn probabilistic_fallback() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(exporter.clone())
+            .with_policy(TailSamplingPolicy::Error)
+            .with_policy(TailSamplingPolicy::Probabilistic(0.5))
+            .build();
+
+        // The low bits of the trace id decide, as for `TraceIdRatioBased`
+        processor.on_end(span(1, 1, 0));
+        processor.on_end(span(u64::MAX as u128, 1, 0));
+        processor.force_flush().unwrap();
+
+        assert_eq!(exported_trace_ids(&exporter), vec![1]);
+    }
+
+    #[test]
+    fn keeps_all_traces_without_policies() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(exporter.clone()).build();
+
+        processor.on_end(span(1, 1, 0));
+        processor.on_end(span(2, 1, 0));
+        processor.force_flush().unwrap();
+
+        assert_eq!(exported_trace_ids(&exporter), vec![1, 2]);
+    }
+
+    #[test]
+    fn decides_after_decision_wait() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(exporter.clone())
+            .with_decision_wait(Duration::from_millis(50))
+            .build();
+
+        processor.on_end(span(1, 1, 0));
+        assert!(exporter.get_finished_spans().unwrap().is_empty());
+
+        wait_for_trace_ids(&exporter, vec![1]);
+    }
+
+    #[test]
+    fn evicts_oldest_trace_when_full() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(exporter.clone())
+            .with_policy(TailSamplingPolicy::Error)
+            .with_max_traces(1)
+            .build();
+
+        let mut failed = span(1, 1, 0);
+        failed.status = Status::error("failed");
+        processor.on_end(failed);
+        processor.on_end(span(2, 1, 0));
+
+        // The first trace was decided early to make room for the second
+        wait_for_trace_ids(&exporter, vec![1]);
+    }
+
+    #[test]
+    fn forwards_evicted_traces_in_background() {
+        let (release, released) = sync_channel(3);
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(BlockingExporter {
+            release: Arc::new(Mutex::new(released)),
+            inner: exporter.clone(),
+        })
+        .with_max_traces(1)
+        .build();
+
+        // Evicting the first trace does not wait for the blocked exporter
+        processor.on_end(span(1, 1, 0));
+        processor.on_end(span(2, 1, 0));
+        processor.on_end(span(1, 2, 1));
+        assert!(exporter.get_finished_spans().unwrap().is_empty());
+
+        for _ in 0..3 {
+            release.send(()).unwrap();
+        }
+        processor.force_flush().unwrap();
+        assert_eq!(exported_trace_ids(&exporter), vec![1, 2]);
+        assert_eq!(exporter.get_finished_spans().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn late_spans_follow_the_decision() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(exporter.clone())
+            .with_policy(TailSamplingPolicy::Error)
+            .build();
+
+        let mut failed = span(1, 2, 1);
+        failed.status = Status::error("failed");
+        processor.on_end(failed);
+        processor.on_end(span(2, 2, 1));
+        processor.force_flush().unwrap();
+
+        processor.on_end(span(1, 1, 0));
+        processor.on_end(span(2, 1, 0));
+        processor.force_flush().unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 2);
+        assert!(spans
+            .iter()
+            .all(|span| span.span_context.trace_id() == TraceId::from(1)));
+    }
+
+    #[test]
+    fn limits_spans_per_trace() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(exporter.clone())
+            .with_max_spans_per_trace(2)
+            .build();
+
+        for span_id in 1..=5 {
+            processor.on_end(span(1, span_id, 1));
+        }
+        processor.force_flush().unwrap();
+
+        assert_eq!(exporter.get_finished_spans().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn ignores_unsampled_spans() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = TailSamplingSpanProcessor::builder(exporter.clone()).build();
+
+        let mut unsampled = span(1, 1, 0);
+        unsampled.span_context = SpanContext::new(
+            TraceId::from(1),
+            SpanId::from(1),
+            TraceFlags::default(),
+            false,
+            TraceState::default(),
+        );
+        processor.on_end(unsampled);
+        processor.force_flush().unwrap();
+
+        assert!(exporter.get_finished_spans().unwrap().is_empty());
+    }
+
+    #[test]
+    fn forwards_to_processor() {
+        let exporter = InMemorySpanExporterBuilder::new()
+            .keep_records_on_shutdown()
+            .build();
+        let processor = TailSamplingSpanProcessor::builder_with_processor(
+            SimpleSpanProcessor::new(exporter.clone()),
+        )
+        .with_policy(TailSamplingPolicy::Error)
+        .build();
+
+        let mut failed = span(1, 1, 0);
+        failed.status = Status::error("failed");
+        processor.on_end(failed);
+        processor.on_end(span(1, 2, 1));
+
+        processor.shutdown().unwrap();
+        assert!(processor.shutdown().is_err());
+        processor.on_end(span(2, 1, 0));
+
+        // Shutdown decides the pending traces, later spans are ignored
+        assert_eq!(exported_trace_ids(&exporter), vec![1]);
+        assert_eq!(exporter.get_finished_spans().unwrap().len(), 2);
+        assert!(exporter.is_shutdown_called());
+    }
+}